@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE transactions DROP COLUMN category_id;
+ALTER TABLE budgets DROP COLUMN category_id;
+DROP TABLE IF EXISTS categories;
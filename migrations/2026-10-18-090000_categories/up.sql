@@ -0,0 +1,54 @@
+-- Your SQL goes here
+CREATE TABLE categories (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX categories_user_id_name_idx ON categories (user_id, lower(name));
+
+-- every existing user gets the categories that used to be hard-coded
+INSERT INTO categories (user_id, name)
+SELECT users.id, defaults.name
+FROM users
+CROSS JOIN (VALUES
+    ('Groceries'),
+    ('Utilities'),
+    ('Entertainment'),
+    ('Rent'),
+    ('Healthcare'),
+    ('Electricity'),
+    ('Education'),
+    ('Subscriptions'),
+    ('Other')
+) AS defaults(name);
+
+-- plus any other spelling already stored on their rows
+INSERT INTO categories (user_id, name)
+SELECT used.user_id, min(used.category)
+FROM (
+    SELECT user_id, category FROM transactions
+    UNION
+    SELECT user_id, category FROM budgets
+) AS used
+WHERE used.user_id IS NOT NULL
+GROUP BY used.user_id, lower(used.category)
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE transactions ADD COLUMN category_id INTEGER REFERENCES categories(id);
+ALTER TABLE budgets ADD COLUMN category_id INTEGER REFERENCES categories(id);
+
+UPDATE transactions
+SET category_id = categories.id, category = categories.name
+FROM categories
+WHERE categories.user_id = transactions.user_id
+  AND lower(categories.name) = lower(transactions.category);
+
+UPDATE budgets
+SET category_id = categories.id, category = categories.name
+FROM categories
+WHERE categories.user_id = budgets.user_id
+  AND lower(categories.name) = lower(budgets.category);
+
+CREATE INDEX transactions_category_id_idx ON transactions (category_id);
+CREATE INDEX budgets_category_id_idx ON budgets (category_id);
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE categories ADD COLUMN parent_id INTEGER REFERENCES categories(id);
+
+CREATE INDEX categories_parent_id_idx ON categories (parent_id);
+
+-- Electricity used to be a flat sibling of Utilities
+UPDATE categories
+SET parent_id = utilities.id
+FROM categories AS utilities
+WHERE utilities.user_id = categories.user_id
+  AND lower(utilities.name) = 'utilities'
+  AND lower(categories.name) = 'electricity';
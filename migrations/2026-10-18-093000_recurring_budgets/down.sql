@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE budgets
+    DROP COLUMN recurrence,
+    DROP COLUMN rollover,
+    DROP COLUMN rollover_amount,
+    DROP COLUMN previous_budget_id;
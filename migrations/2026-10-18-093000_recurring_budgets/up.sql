@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE budgets
+    ADD COLUMN recurrence TEXT
+        CHECK (recurrence IN ('weekly', 'biweekly', 'monthly', 'quarterly', 'yearly')),
+    ADD COLUMN rollover TEXT NOT NULL DEFAULT 'none'
+        CHECK (rollover IN ('none', 'unspent', 'overspent', 'all')),
+    ADD COLUMN rollover_amount DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN previous_budget_id INTEGER UNIQUE REFERENCES budgets(id) ON DELETE SET NULL;
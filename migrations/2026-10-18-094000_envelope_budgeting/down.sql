@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS envelope_allocations;
+ALTER TABLE users
+    DROP COLUMN budgeting_mode,
+    DROP COLUMN envelope_start;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+ALTER TABLE users
+    ADD COLUMN budgeting_mode TEXT NOT NULL DEFAULT 'standard'
+        CHECK (budgeting_mode IN ('standard', 'envelope')),
+    ADD COLUMN envelope_start DATE;
+
+CREATE TABLE envelope_allocations (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES categories(id),
+    month DATE NOT NULL,
+    amount DECIMAL NOT NULL,
+    note TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX envelope_allocations_user_id_month_idx ON envelope_allocations (user_id, month);
+CREATE INDEX envelope_allocations_category_id_idx ON envelope_allocations (category_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS alerts;
+ALTER TABLE budgets DROP COLUMN alert_thresholds;
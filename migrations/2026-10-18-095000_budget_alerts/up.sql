@@ -0,0 +1,17 @@
+-- Your SQL goes here
+ALTER TABLE budgets ADD COLUMN alert_thresholds INTEGER[] NOT NULL DEFAULT '{80,100}';
+
+CREATE TABLE alerts (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    budget_id INTEGER NOT NULL REFERENCES budgets(id) ON DELETE CASCADE,
+    transaction_id INTEGER REFERENCES transactions(id) ON DELETE SET NULL,
+    category TEXT NOT NULL,
+    threshold INTEGER NOT NULL,
+    percent_used DECIMAL NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    read_at TIMESTAMP,
+    UNIQUE (budget_id, threshold)
+);
+
+CREATE INDEX alerts_user_id_idx ON alerts (user_id, created_at);
@@ -0,0 +1,29 @@
+-- Your SQL goes here
+CREATE TABLE recurring_templates (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('transaction', 'income')),
+    amount DECIMAL NOT NULL,
+    category TEXT NOT NULL,
+    category_id INTEGER REFERENCES categories(id),
+    description TEXT,
+    rrule TEXT NOT NULL,
+    start_date DATE NOT NULL,
+    end_date DATE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX recurring_templates_user_id_idx ON recurring_templates (user_id);
+
+CREATE TABLE recurring_occurrences (
+    id SERIAL PRIMARY KEY,
+    template_id INTEGER NOT NULL REFERENCES recurring_templates(id) ON DELETE CASCADE,
+    occurrence_date DATE NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'skipped', 'posted')),
+    amount DECIMAL,
+    description TEXT,
+    date DATE,
+    transaction_id INTEGER REFERENCES transactions(id) ON DELETE SET NULL,
+    income_id INTEGER REFERENCES income(id) ON DELETE SET NULL,
+    UNIQUE (template_id, occurrence_date)
+);
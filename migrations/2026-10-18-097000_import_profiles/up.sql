@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE import_profiles (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    delimiter TEXT NOT NULL DEFAULT ',',
+    has_header BOOLEAN NOT NULL DEFAULT TRUE,
+    date_column INTEGER NOT NULL,
+    date_format TEXT NOT NULL DEFAULT '%Y-%m-%d',
+    description_column INTEGER,
+    category_column INTEGER,
+    amount_column INTEGER,
+    debit_column INTEGER,
+    credit_column INTEGER,
+    decimal_separator TEXT NOT NULL DEFAULT '.',
+    sign_convention TEXT NOT NULL DEFAULT 'negative_debit'
+        CHECK (sign_convention IN ('negative_debit', 'positive_debit')),
+    default_category TEXT NOT NULL DEFAULT 'Other',
+    UNIQUE (user_id, name)
+);
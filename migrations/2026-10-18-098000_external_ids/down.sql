@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE income DROP COLUMN external_id;
+ALTER TABLE transactions DROP COLUMN external_id;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE transactions ADD COLUMN external_id TEXT;
+ALTER TABLE income ADD COLUMN external_id TEXT;
+
+CREATE UNIQUE INDEX transactions_user_id_external_id_key ON transactions (user_id, external_id);
+CREATE UNIQUE INDEX income_user_id_external_id_key ON income (user_id, external_id);
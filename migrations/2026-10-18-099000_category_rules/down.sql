@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS category_rules;
+ALTER TABLE users
+    DROP COLUMN rule_match_mode;
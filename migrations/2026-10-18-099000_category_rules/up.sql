@@ -0,0 +1,21 @@
+-- Your SQL goes here
+ALTER TABLE users
+    ADD COLUMN rule_match_mode TEXT NOT NULL DEFAULT 'first'
+        CHECK (rule_match_mode IN ('first', 'all'));
+
+CREATE TABLE category_rules (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    description_contains TEXT,
+    description_regex TEXT,
+    min_amount DECIMAL,
+    max_amount DECIMAL,
+    day_of_month INTEGER CHECK (day_of_month BETWEEN 1 AND 31),
+    category TEXT,
+    category_id INTEGER REFERENCES categories(id),
+    new_description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX category_rules_user_id_position_idx ON category_rules (user_id, position);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS classifier_tokens;
+DROP TABLE IF EXISTS classifier_categories;
+ALTER TABLE users
+    DROP COLUMN classifier_trained_at;
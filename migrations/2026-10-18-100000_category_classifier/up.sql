@@ -0,0 +1,18 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN classifier_trained_at TIMESTAMP;
+
+CREATE TABLE classifier_categories (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    documents INTEGER NOT NULL,
+    tokens INTEGER NOT NULL,
+    PRIMARY KEY (user_id, category_id)
+);
+
+CREATE TABLE classifier_tokens (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    token TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (user_id, token, category_id)
+);
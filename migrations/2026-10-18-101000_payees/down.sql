@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE transactions
+    DROP COLUMN payee_id;
+DROP TABLE IF EXISTS payee_aliases;
+DROP TABLE IF EXISTS payees;
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE payees (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    category TEXT,
+    category_id INTEGER REFERENCES categories(id),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE payee_aliases (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    payee_id INTEGER NOT NULL REFERENCES payees(id) ON DELETE CASCADE,
+    alias TEXT NOT NULL,
+    UNIQUE (user_id, alias)
+);
+
+ALTER TABLE transactions
+    ADD COLUMN payee_id INTEGER REFERENCES payees(id) ON DELETE SET NULL;
+
+CREATE INDEX transactions_payee_id_idx ON transactions (payee_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP VIEW IF EXISTS transaction_lines;
+DROP TABLE IF EXISTS transaction_splits;
@@ -0,0 +1,27 @@
+-- Your SQL goes here
+CREATE TABLE transaction_splits (
+    id SERIAL PRIMARY KEY,
+    transaction_id INTEGER NOT NULL REFERENCES transactions(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    category TEXT NOT NULL,
+    category_id INTEGER NOT NULL REFERENCES categories(id),
+    amount DECIMAL NOT NULL CHECK (amount > 0),
+    memo TEXT
+);
+
+CREATE INDEX transaction_splits_transaction_id_idx ON transaction_splits (transaction_id);
+
+-- What the reports count: the split lines of a split transaction and every
+-- other transaction as a single line.
+CREATE VIEW transaction_lines AS
+SELECT transactions.id AS transaction_id, transactions.user_id, transactions.date,
+    transaction_splits.category_id, transaction_splits.category, transaction_splits.amount
+FROM transactions
+JOIN transaction_splits ON transaction_splits.transaction_id = transactions.id
+UNION ALL
+SELECT transactions.id, transactions.user_id, transactions.date,
+    transactions.category_id, transactions.category, transactions.amount
+FROM transactions
+WHERE NOT EXISTS (
+    SELECT 1 FROM transaction_splits WHERE transaction_splits.transaction_id = transactions.id
+);
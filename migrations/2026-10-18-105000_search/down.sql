@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE income DROP COLUMN search_vector;
+ALTER TABLE transactions DROP COLUMN search_vector;
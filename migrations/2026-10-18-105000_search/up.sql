@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- descriptions weigh more than category names in the ranking
+ALTER TABLE transactions ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', COALESCE(description, '')), 'A')
+        || setweight(to_tsvector('english', category), 'B')
+) STORED;
+
+ALTER TABLE income ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', source), 'A')
+) STORED;
+
+CREATE INDEX transactions_search_vector_idx ON transactions USING GIN (search_vector);
+CREATE INDEX income_search_vector_idx ON income USING GIN (search_vector);
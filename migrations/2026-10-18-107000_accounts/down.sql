@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE income DROP COLUMN account_id;
+ALTER TABLE transactions DROP COLUMN account_id;
+DROP TABLE accounts;
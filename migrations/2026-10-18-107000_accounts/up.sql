@@ -0,0 +1,21 @@
+-- Your SQL goes here
+CREATE TABLE accounts (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL
+        CHECK (kind IN ('checking', 'savings', 'credit_card', 'cash', 'loan')),
+    currency CHAR(3) NOT NULL DEFAULT 'USD',
+    opening_balance DECIMAL NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+ALTER TABLE transactions
+    ADD COLUMN account_id INTEGER REFERENCES accounts(id) ON DELETE SET NULL;
+
+ALTER TABLE income
+    ADD COLUMN account_id INTEGER REFERENCES accounts(id) ON DELETE SET NULL;
+
+CREATE INDEX transactions_account_id_idx ON transactions (account_id, date);
+CREATE INDEX income_account_id_idx ON income (account_id, date);
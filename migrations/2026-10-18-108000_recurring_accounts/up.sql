@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE recurring_templates
+    ADD COLUMN account_id INTEGER REFERENCES accounts(id) ON DELETE SET NULL;
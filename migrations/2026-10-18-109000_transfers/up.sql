@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE transfers (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    from_account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    to_account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    amount DECIMAL NOT NULL CHECK (amount > 0),
+    date DATE NOT NULL,
+    description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CHECK (from_account_id <> to_account_id)
+);
+
+CREATE INDEX transfers_user_id_date_idx ON transfers (user_id, date);
+CREATE INDEX transfers_from_account_id_idx ON transfers (from_account_id, date);
+CREATE INDEX transfers_to_account_id_idx ON transfers (to_account_id, date);
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- search snippets are HTML, so the text around the highlights is escaped
+CREATE FUNCTION html_escape(text TEXT) RETURNS TEXT AS $$
+    SELECT REPLACE(REPLACE(REPLACE(REPLACE(REPLACE(text,
+        '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;')
+$$ LANGUAGE SQL IMMUTABLE STRICT;
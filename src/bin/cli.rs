@@ -1,8 +1,11 @@
 extern crate env_logger;
 extern crate finance_manager;
 
-use clap::{Arg, Command};
-use finance_manager::command::commands::{create_user, delete_users, list_users, update_user};
+use clap::{Arg, ArgAction, Command};
+use finance_manager::command::commands::{
+    create_user, delete_users, find_transactions, import_transactions, list_users, update_user,
+    ImportOptions,
+};
 
 fn main() {
     env_logger::init();
@@ -59,6 +62,76 @@ fn main() {
                         .arg(Arg::new("password").help("New Password").required(true)),
                 ),
         )
+        .subcommand(
+            Command::new("transactions")
+                .about("Transaction commands")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("import")
+                        .about("Import transactions from a bank export")
+                        .arg(Arg::new("file").help("File to import").required(true))
+                        .arg(
+                            Arg::new("email")
+                                .long("email")
+                                .help("Email of the user to import for")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .help("Format of the file")
+                                .value_parser(["csv", "ofx", "qif", "camt", "mt940"])
+                                .default_value("csv"),
+                        )
+                        .arg(
+                            Arg::new("profile")
+                                .long("profile")
+                                .help("Name of the user's import profile, required for CSV files"),
+                        )
+                        .arg(
+                            Arg::new("account")
+                                .long("account")
+                                .help("Name of the user's account the rows are booked on"),
+                        )
+                        .arg(
+                            Arg::new("category")
+                                .long("category")
+                                .help("Category of the imported transactions")
+                                .default_value("Other"),
+                        )
+                        .arg(
+                            Arg::new("day_first")
+                                .long("day-first")
+                                .help("Read QIF dates as day/month/year")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("dry_run")
+                                .long("dry-run")
+                                .help("Check the file without importing it")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("find")
+                        .about("Find transactions, e.g. 'category:Rent amount>500 -desc:refund'")
+                        .arg(Arg::new("query").help("Filter query").required(true))
+                        .arg(
+                            Arg::new("email")
+                                .long("email")
+                                .help("Email of the user whose transactions to search")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .long("limit")
+                                .help("Most transactions to list, newest first")
+                                .value_parser(clap::value_parser!(i64))
+                                .default_value("100"),
+                        ),
+                ),
+        )
         .get_matches();
 
     if let Some(("users", sub_matches)) = matches.subcommand() {
@@ -90,5 +163,76 @@ fn main() {
                 Err(e) => eprintln!("Error updating user: {:?}", e),
             }
         }
+    } else if let Some(("transactions", sub_matches)) = matches.subcommand() {
+        if let Some(("import", matches)) = sub_matches.subcommand() {
+            let file = matches.get_one::<String>("file").unwrap().to_owned();
+            let email = matches.get_one::<String>("email").unwrap().to_owned();
+            let options = ImportOptions {
+                format: matches.get_one::<String>("format").unwrap().to_owned(),
+                profile: matches.get_one::<String>("profile").cloned(),
+                account: matches.get_one::<String>("account").cloned(),
+                category: matches.get_one::<String>("category").unwrap().to_owned(),
+                day_first: matches.get_flag("day_first"),
+                dry_run: matches.get_flag("dry_run"),
+            };
+
+            match import_transactions(file, email, options) {
+                Ok(report) => {
+                    for row in report.rows.iter().filter(|row| row.status != "ok") {
+                        println!(
+                            "line {}: {} ({})",
+                            row.line,
+                            row.status,
+                            row.error.as_deref().unwrap_or_default()
+                        );
+                    }
+                    for row in report
+                        .rows
+                        .iter()
+                        .filter(|row| !row.possible_duplicates.is_empty())
+                    {
+                        println!(
+                            "line {}: possible duplicate of transaction {:?}",
+                            row.line, row.possible_duplicates
+                        );
+                    }
+                    if report.committed {
+                        println!(
+                            "Imported {} rows, skipped {} rows.",
+                            report.imported, report.skipped
+                        );
+                    } else if report.failed > 0 {
+                        println!("Nothing imported: {} rows are invalid.", report.failed);
+                    } else {
+                        println!(
+                            "{} rows can be imported, {} rows will be skipped.",
+                            report.imported, report.skipped
+                        );
+                    }
+                }
+                Err(e) => eprintln!("Error importing transactions: {:?}", e),
+            }
+        } else if let Some(("find", matches)) = sub_matches.subcommand() {
+            let query = matches.get_one::<String>("query").unwrap().to_owned();
+            let email = matches.get_one::<String>("email").unwrap().to_owned();
+            let limit = *matches.get_one::<i64>("limit").unwrap();
+
+            match find_transactions(email, query, limit) {
+                Ok(transactions) => {
+                    for transaction in &transactions {
+                        println!(
+                            "{:>6}  {}  {:>12}  {:<20}  {}",
+                            transaction.id,
+                            transaction.date,
+                            transaction.amount,
+                            transaction.category,
+                            transaction.description.as_deref().unwrap_or_default()
+                        );
+                    }
+                    println!("{} transactions found.", transactions.len());
+                }
+                Err(e) => eprintln!("Error finding transactions: {}", e),
+            }
+        }
     }
 }
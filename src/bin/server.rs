@@ -1,6 +1,8 @@
 extern crate finance_manager;
 use std::str::FromStr;
 
+use finance_manager::utils::attachments::{AttachmentStore, MAX_ATTACHMENT_SIZE};
+use rocket::fairing::AdHoc;
 use rocket::{self, routes};
 use rocket_cors::{AllowedHeaders, AllowedMethods, AllowedOrigins, CorsOptions};
 
@@ -21,7 +23,13 @@ async fn main() {
     .to_cors()
     .expect("Failed to create CORS");
 
-    let _ = rocket::build()
+    // uploads are capped by the attachment routes, not by Rocket's smaller
+    // defaults, unless `limits` are configured
+    let figment = rocket::Config::figment()
+        .join(("limits.file", format!("{}MiB", MAX_ATTACHMENT_SIZE)))
+        .join(("limits.data-form", format!("{}MiB", MAX_ATTACHMENT_SIZE + 1)));
+
+    let _ = rocket::custom(figment)
         .mount(
             "/",
             routes![
@@ -33,16 +41,101 @@ async fn main() {
                 finance_manager::routes::budget::view_budget,
                 finance_manager::routes::budget::delete_budget,
                 finance_manager::routes::budget::view_budgets,
+                finance_manager::routes::budget::view_budget_status,
+                finance_manager::routes::budget::view_budgets_status,
+                finance_manager::routes::category::create_category,
+                finance_manager::routes::category::view_categories,
+                finance_manager::routes::category::view_category_tree,
+                finance_manager::routes::category::view_category,
+                finance_manager::routes::category::update_category,
+                finance_manager::routes::category::merge_category,
+                finance_manager::routes::category::delete_category,
+                finance_manager::routes::alert::view_alerts,
+                finance_manager::routes::alert::mark_alert_read,
+                finance_manager::routes::alert::mark_alerts_read,
+                finance_manager::routes::envelope::set_budgeting_mode,
+                finance_manager::routes::envelope::view_envelopes,
+                finance_manager::routes::envelope::view_unassigned,
+                finance_manager::routes::envelope::assign_money,
+                finance_manager::routes::envelope::move_money,
                 finance_manager::routes::transactions::create_transaction,
                 finance_manager::routes::transactions::view_transactions,
                 finance_manager::routes::transactions::view_transaction,
                 finance_manager::routes::transactions::update_transaction,
                 finance_manager::routes::transactions::delete_transaction,
+                finance_manager::routes::transactions::view_duplicates,
+                finance_manager::routes::transactions::merge_transactions,
+                finance_manager::routes::transactions::suggest_categories,
+                finance_manager::routes::transactions::view_splits,
+                finance_manager::routes::transactions::set_splits,
+                finance_manager::routes::transactions::bulk_update,
+                finance_manager::routes::attachment::upload_attachment,
+                finance_manager::routes::attachment::view_attachments,
+                finance_manager::routes::attachment::download_attachment,
+                finance_manager::routes::attachment::delete_attachment,
+                finance_manager::routes::import::create_profile,
+                finance_manager::routes::import::view_profiles,
+                finance_manager::routes::import::delete_profile,
+                finance_manager::routes::import::import_csv,
+                finance_manager::routes::import::import_ofx,
+                finance_manager::routes::import::import_qif,
+                finance_manager::routes::import::export_qif,
+                finance_manager::routes::import::import_camt,
+                finance_manager::routes::import::import_mt940,
                 finance_manager::routes::income::create_income,
                 finance_manager::routes::income::view_income,
                 finance_manager::routes::income::view_incomes,
                 finance_manager::routes::income::update_income,
                 finance_manager::routes::income::delete_income,
+                finance_manager::routes::recurring::create_template,
+                finance_manager::routes::recurring::view_templates,
+                finance_manager::routes::recurring::view_template,
+                finance_manager::routes::recurring::view_upcoming,
+                finance_manager::routes::recurring::skip_occurrence,
+                finance_manager::routes::recurring::edit_occurrence,
+                finance_manager::routes::recurring::delete_template,
+                finance_manager::routes::rule::create_rule,
+                finance_manager::routes::rule::view_rules,
+                finance_manager::routes::rule::view_rule,
+                finance_manager::routes::rule::set_match_mode,
+                finance_manager::routes::rule::reorder_rules,
+                finance_manager::routes::rule::preview_rules,
+                finance_manager::routes::rule::apply_rules,
+                finance_manager::routes::rule::delete_rule,
+                finance_manager::routes::payee::create_payee,
+                finance_manager::routes::payee::view_payees,
+                finance_manager::routes::payee::view_payee,
+                finance_manager::routes::payee::payee_summary,
+                finance_manager::routes::payee::update_payee,
+                finance_manager::routes::payee::add_alias,
+                finance_manager::routes::payee::delete_alias,
+                finance_manager::routes::payee::delete_payee,
+                finance_manager::routes::tag::create_tag,
+                finance_manager::routes::tag::view_tags,
+                finance_manager::routes::tag::tag_report,
+                finance_manager::routes::tag::rename_tag,
+                finance_manager::routes::tag::delete_tag,
+                finance_manager::routes::tag::view_transaction_tags,
+                finance_manager::routes::tag::set_transaction_tags,
+                finance_manager::routes::tag::view_income_tags,
+                finance_manager::routes::tag::set_income_tags,
+                finance_manager::routes::tag::view_goal_tags,
+                finance_manager::routes::tag::set_goal_tags,
+                finance_manager::routes::search::search,
+                finance_manager::routes::view::create_view,
+                finance_manager::routes::view::view_views,
+                finance_manager::routes::view::view_view,
+                finance_manager::routes::view::view_results,
+                finance_manager::routes::view::update_view,
+                finance_manager::routes::view::delete_view,
+                finance_manager::routes::account::create_account,
+                finance_manager::routes::account::view_accounts,
+                finance_manager::routes::account::view_account,
+                finance_manager::routes::account::update_account,
+                finance_manager::routes::account::delete_account,
+                finance_manager::routes::transfer::create_transfer,
+                finance_manager::routes::transfer::view_transfers,
+                finance_manager::routes::transfer::delete_transfer,
                 finance_manager::routes::goal::create_goal,
                 finance_manager::routes::goal::view_goal,
                 finance_manager::routes::goal::view_goals,
@@ -51,6 +144,8 @@ async fn main() {
             ],
         )
         .attach(finance_manager::DBConnection::fairing())
+        .attach(finance_manager::jobs::recurring_fairing())
+        .attach(AdHoc::config::<AttachmentStore>())
         .attach(cors)
         .launch()
         .await;
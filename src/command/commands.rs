@@ -1,7 +1,11 @@
 use crate::command::errors::AppError;
-use crate::model::{NewUser, UserResponse};
-use crate::repositories::UserRepository;
+use crate::import::{self, camt, csv, mt940, ofx, qif, ImportReport};
+use crate::model::{ListQuery, NewUser, Transaction, UserResponse};
+use crate::repositories::{
+    AccountRepository, ImportProfileRepository, TransactionsRepository, UserRepository,
+};
 use crate::utils::hashing::hash_password;
+use crate::utils::query::TransactionQuery;
 use diesel::{Connection, PgConnection};
 
 fn load_db_connection() -> Result<PgConnection, AppError> {
@@ -74,3 +78,93 @@ pub fn update_user(
         Err(_) => Err(AppError::UserRepositoryError),
     }
 }
+
+/// How `import_transactions` reads a file and where it books its rows.
+pub struct ImportOptions {
+    /// `csv`, `ofx`, `qif`, `camt` (camt.053) or `mt940`.
+    pub format: String,
+    /// Name of the user's import profile a `csv` file is read with.
+    pub profile: Option<String>,
+    /// Name of the user's account the rows are booked on.
+    pub account: Option<String>,
+    /// Category of the transactions the file does not categorize.
+    pub category: String,
+    /// Reads QIF dates as day/month/year.
+    pub day_first: bool,
+    pub dry_run: bool,
+}
+
+/// Imports a bank export `file` for the user with `email`.
+pub fn import_transactions(
+    file: String,
+    email: String,
+    options: ImportOptions,
+) -> Result<ImportReport, AppError> {
+    let mut connection = load_db_connection()?;
+    let data = std::fs::read(file)?;
+
+    let user = UserRepository::find_by_email(&mut connection, &email)?
+        .ok_or(AppError::UserRepositoryError)?;
+    let account_id = match &options.account {
+        Some(name) => Some(
+            AccountRepository::find_account_by_name(&mut connection, user.id, name)?
+                .ok_or(AppError::AccountNotFound)?
+                .id,
+        ),
+        None => None,
+    };
+    let mut category = options.category.clone();
+    let rows = match options.format.as_str() {
+        "ofx" => ofx::parse(&data, &category)?,
+        "qif" => qif::parse(&data, &category, options.day_first)?,
+        "camt" => camt::parse(&data, &category)?,
+        "mt940" => mt940::parse(&data, &category)?,
+        _ => {
+            let profile = options.profile.ok_or(AppError::ImportProfileNotFound)?;
+            let profile =
+                ImportProfileRepository::find_profile_by_name(&mut connection, user.id, &profile)?
+                    .ok_or(AppError::ImportProfileNotFound)?;
+            let rows = csv::parse(&profile, &data)?;
+            category = profile.default_category;
+            rows
+        }
+    };
+    // QIF categories are the user's own, so a missing one is not fatal
+    let fallback_category = (options.format == "qif").then_some(category.as_str());
+    Ok(import::import_transactions(
+        &mut connection,
+        user.id,
+        rows,
+        account_id,
+        &category,
+        fallback_category,
+        options.dry_run,
+    )?)
+}
+
+/// The newest `limit` transactions of the user matching `query`, written in
+/// the filter language of `GET /transactions?q=`.
+pub fn find_transactions(
+    email: String,
+    query: String,
+    limit: i64,
+) -> Result<Vec<Transaction>, AppError> {
+    let mut connection = load_db_connection()?;
+    let search = query.parse::<TransactionQuery>()?;
+    let filter = ListQuery {
+        limit: Some(limit),
+        ..Default::default()
+    }
+    .parse()
+    .map_err(|err| AppError::InvalidOption(err.code.into_owned()))?;
+
+    let user = UserRepository::find_by_email(&mut connection, &email)?
+        .ok_or(AppError::UserRepositoryError)?;
+    let page = TransactionsRepository::find_multiple_transactions(
+        &mut connection,
+        user.id,
+        &filter,
+        Some(&search),
+    )?;
+    Ok(page.data)
+}
@@ -10,6 +10,20 @@ pub enum AppError {
     UserRepositoryError,
     #[error("Clap error")]
     ClapError(#[from] clap::Error),
+    #[error("Database error")]
+    DbError(#[from] diesel::result::Error),
+    #[error("File error")]
+    IoError(#[from] std::io::Error),
+    #[error("Import error")]
+    ImportError(#[from] crate::import::ImportError),
+    #[error("Import profile not found")]
+    ImportProfileNotFound,
+    #[error("Account not found")]
+    AccountNotFound,
+    #[error("Invalid query: {0}")]
+    QueryError(#[from] crate::utils::query::QueryError),
+    #[error("Invalid option: {0}")]
+    InvalidOption(String),
     #[error("server error")]
     Error,
 }
@@ -0,0 +1,362 @@
+//! ISO 20022 camt.053 bank to customer statements. Every booked `Ntry` is
+//! one row: debits become transactions in the given category and credits
+//! become income. The description holds the counterparty's name and the
+//! remittance information, and the bank's reference (`AcctSvcrRef`), scoped
+//! by the statement's account, is kept as the external id.
+
+use super::{describe_entry, external_id, parse_amount, ImportError, ParsedRow, RowOutcome};
+use crate::model::{NewIncome, NewTransaction};
+use bigdecimal::{Signed, Zero};
+use chrono::NaiveDate;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+/// The elements of an `Ntry` that make up a row.
+#[derive(Default)]
+struct Entry {
+    line: u64,
+    amount: Option<String>,
+    indicator: Option<String>,
+    status: Option<String>,
+    booking_date: Option<String>,
+    value_date: Option<String>,
+    reference: Option<String>,
+    creditor: Option<String>,
+    debtor: Option<String>,
+    remittance: Vec<String>,
+    additional_info: Option<String>,
+}
+
+impl Entry {
+    /// Stores the text of the element at `path`, relative to the `Ntry`.
+    fn set(&mut self, path: &[String], text: String) {
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        match path[..] {
+            ["Amt"] => self.amount = Some(text),
+            ["CdtDbtInd"] => self.indicator = Some(text),
+            ["Sts"] | ["Sts", "Cd"] => self.status = Some(text),
+            ["BookgDt", _] => self.booking_date = Some(text),
+            ["ValDt", _] => self.value_date = Some(text),
+            ["AcctSvcrRef"] => self.reference = Some(text),
+            ["AddtlNtryInf"] => self.additional_info = Some(text),
+            _ if path.ends_with(&["Refs", "AcctSvcrRef"]) => {
+                self.reference.get_or_insert(text);
+            }
+            _ if path.contains(&"RmtInf") && matches!(path.last(), Some(&"Ustrd" | &"Ref")) => {
+                self.remittance.push(text)
+            }
+            _ if path.contains(&"RltdPties") && path.last() == Some(&"Nm") => {
+                // `Cdtr/Nm` in older versions, `Cdtr/Pty/Nm` since .001.08
+                let party = path.iter().skip_while(|name| **name != "RltdPties").nth(1);
+                match party {
+                    Some(&"Cdtr") => {
+                        self.creditor.get_or_insert(text);
+                    }
+                    Some(&"Dbtr") => {
+                        self.debtor.get_or_insert(text);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn parse(data: &[u8], category: &str) -> Result<Vec<ParsedRow>, ImportError> {
+    let mut reader = Reader::from_reader(data);
+    reader.config_mut().trim_text(true);
+
+    let mut rows = Vec::new();
+    let mut statement = false;
+    // IBAN or other id of the account of the `Stmt` being read
+    let mut account = String::new();
+    let account_paths = [account_path(&["IBAN"]), account_path(&["Othr", "Id"])];
+    let mut path: Vec<String> = Vec::new();
+    // index of the open `Ntry` in `path`, with the entry being read
+    let mut entry: Option<(usize, Entry)> = None;
+    let mut buf = Vec::new();
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|error| ImportError::Unreadable(error.to_string()))?;
+        match event {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                statement |= name == "BkToCstmrStmt";
+                if name == "Stmt" {
+                    account.clear();
+                }
+                if name == "Ntry" && entry.is_none() {
+                    // the reader stands after `<Ntry ...>`
+                    let end = (reader.buffer_position() as usize).min(data.len());
+                    let start = data[..end].iter().rposition(|byte| *byte == b'<');
+                    let line = data[..start.unwrap_or(end)]
+                        .iter()
+                        .filter(|byte| **byte == b'\n')
+                        .count() as u64
+                        + 1;
+                    entry = Some((
+                        path.len(),
+                        Entry {
+                            line,
+                            ..Entry::default()
+                        },
+                    ));
+                }
+                path.push(name);
+            }
+            Event::End(_) => {
+                path.pop();
+                if entry
+                    .as_ref()
+                    .is_some_and(|(depth, _)| *depth == path.len())
+                {
+                    let (_, entry) = entry.take().unwrap_or_default();
+                    rows.push(ParsedRow {
+                        line: entry.line,
+                        outcome: parse_entry(entry, &account, category),
+                    });
+                }
+            }
+            Event::Text(text) => {
+                let text = text
+                    .unescape()
+                    .map_err(|error| ImportError::Unreadable(error.to_string()))?;
+                if let Some((depth, entry)) = entry.as_mut() {
+                    entry.set(&path[*depth + 1..], text.into_owned());
+                } else if account_paths.iter().any(|id| path.ends_with(id)) {
+                    account = text.into_owned();
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if !statement {
+        return Err(ImportError::Unreadable(
+            "not a camt.053 statement".to_owned(),
+        ));
+    }
+    Ok(rows)
+}
+
+/// Path of an element of the statement's account id, from its `Stmt`.
+fn account_path(id: &[&str]) -> Vec<String> {
+    ["Stmt", "Acct", "Id"]
+        .iter()
+        .chain(id)
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn parse_entry(entry: Entry, account: &str, category: &str) -> RowOutcome {
+    if entry
+        .status
+        .as_deref()
+        .is_some_and(|status| status != "BOOK")
+    {
+        return RowOutcome::Skipped("entry is not booked".to_owned());
+    }
+
+    let date = |value: &Option<String>| {
+        value
+            .as_deref()
+            .and_then(|value| value.get(..10))
+            .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+    };
+    let value_date = date(&entry.value_date);
+    let Some(booking_date) = date(&entry.booking_date).or(value_date) else {
+        return RowOutcome::Invalid("missing booking date".to_owned());
+    };
+    let Some(value) = entry.amount else {
+        return RowOutcome::Invalid("missing Amt".to_owned());
+    };
+    let Some(amount) = parse_amount(&value, ".").filter(|amount| !amount.is_negative()) else {
+        return RowOutcome::Invalid(format!("invalid amount: {}", value));
+    };
+    if amount.is_zero() {
+        return RowOutcome::Skipped("zero amount".to_owned());
+    }
+
+    let external_id = entry
+        .reference
+        .map(|reference| external_id("camt", &[account], &reference));
+    let remittance = if entry.remittance.is_empty() {
+        entry.additional_info
+    } else {
+        Some(entry.remittance.join(" "))
+    };
+    match entry.indicator.as_deref() {
+        Some("DBIT") => RowOutcome::Transaction(NewTransaction {
+            user_id: None,
+            amount,
+            category: category.to_owned(),
+            description: describe_entry(
+                entry.creditor.as_deref(),
+                remittance.as_deref(),
+                booking_date,
+                value_date,
+            ),
+            date: booking_date,
+            category_id: None,
+            external_id,
+            payee_id: None,
+            account_id: None,
+        }),
+        Some("CRDT") => RowOutcome::Income(NewIncome {
+            user_id: None,
+            amount,
+            source: describe_entry(
+                entry.debtor.as_deref(),
+                remittance.as_deref(),
+                booking_date,
+                value_date,
+            )
+            .unwrap_or_else(|| "camt.053 import".to_owned()),
+            date: booking_date,
+            external_id,
+            account_id: None,
+        }),
+        _ => RowOutcome::Invalid("CdtDbtInd must be CRDT or DBIT".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    const STATEMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08">
+<BkToCstmrStmt>
+<Stmt>
+<Id>1</Id>
+<Acct><Id><IBAN>DE89370400440532013000</IBAN></Id></Acct>
+<Ntry>
+<Amt Ccy="EUR">42.50</Amt>
+<CdtDbtInd>DBIT</CdtDbtInd>
+<Sts><Cd>BOOK</Cd></Sts>
+<BookgDt><Dt>2024-03-01</Dt></BookgDt>
+<ValDt><Dt>2024-03-02</Dt></ValDt>
+<NtryDtls><TxDtls>
+<Refs><AcctSvcrRef>0915123</AcctSvcrRef></Refs>
+<RltdPties>
+<Dbtr><Pty><Nm>Jane Doe</Nm></Pty></Dbtr>
+<Cdtr><Pty><Nm>Stadtwerke &amp; Co</Nm></Pty></Cdtr>
+</RltdPties>
+<RmtInf><Ustrd>Invoice 4711</Ustrd><Ustrd>March</Ustrd></RmtInf>
+</TxDtls></NtryDtls>
+</Ntry>
+<Ntry>
+<Amt Ccy="EUR">1500.00</Amt>
+<CdtDbtInd>CRDT</CdtDbtInd>
+<Sts>BOOK</Sts>
+<BookgDt><DtTm>2024-03-05T10:00:00</DtTm></BookgDt>
+<AcctSvcrRef>0915124</AcctSvcrRef>
+<NtryDtls><TxDtls>
+<RltdPties><Dbtr><Nm>ACME Corp</Nm></Dbtr></RltdPties>
+</TxDtls></NtryDtls>
+<AddtlNtryInf>Salary</AddtlNtryInf>
+</Ntry>
+<Ntry>
+<Amt Ccy="EUR">9.99</Amt>
+<CdtDbtInd>DBIT</CdtDbtInd>
+<Sts><Cd>PDNG</Cd></Sts>
+<BookgDt><Dt>2024-03-06</Dt></BookgDt>
+</Ntry>
+<Ntry>
+<Amt Ccy="EUR">1.00</Amt>
+<CdtDbtInd>XXXX</CdtDbtInd>
+<BookgDt><Dt>2024-03-07</Dt></BookgDt>
+</Ntry>
+</Stmt>
+</BkToCstmrStmt>
+</Document>
+"#;
+
+    #[test]
+    fn reads_entries() {
+        let rows = parse(STATEMENT.as_bytes(), "Other").unwrap();
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].line, 7);
+        let RowOutcome::Transaction(invoice) = &rows[0].outcome else {
+            panic!("the debit is not a transaction");
+        };
+        assert_eq!(invoice.amount, BigDecimal::from_str("42.50").unwrap());
+        assert_eq!(invoice.date, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        assert_eq!(invoice.category, "Other");
+        // the creditor of `Cdtr/Pty/Nm`, not the debtor
+        assert_eq!(
+            invoice.description.as_deref(),
+            Some("Stadtwerke & Co: Invoice 4711 March (value date 2024-03-02)")
+        );
+        assert_eq!(
+            invoice.external_id.as_deref(),
+            Some("camt:DE89370400440532013000:0915123")
+        );
+
+        let RowOutcome::Income(salary) = &rows[1].outcome else {
+            panic!("the credit is not income");
+        };
+        assert_eq!(salary.amount, BigDecimal::from(1500));
+        assert_eq!(salary.date, NaiveDate::from_ymd_opt(2024, 3, 5).unwrap());
+        // the debtor of the older `Dbtr/Nm`
+        assert_eq!(salary.source, "ACME Corp: Salary");
+        assert_eq!(
+            salary.external_id.as_deref(),
+            Some("camt:DE89370400440532013000:0915124")
+        );
+
+        assert!(matches!(
+            &rows[2].outcome,
+            RowOutcome::Skipped(reason) if reason == "entry is not booked"
+        ));
+        assert!(matches!(
+            &rows[3].outcome,
+            RowOutcome::Invalid(error) if error == "CdtDbtInd must be CRDT or DBIT"
+        ));
+    }
+
+    #[test]
+    fn scopes_ids_by_statement_account() {
+        let statement = |account: &str| {
+            format!(
+                "<Stmt><Acct><Id>{}</Id></Acct><Ntry><Amt>1</Amt><CdtDbtInd>DBIT</CdtDbtInd>\
+                <BookgDt><Dt>2024-01-01</Dt></BookgDt><AcctSvcrRef>7</AcctSvcrRef></Ntry></Stmt>",
+                account
+            )
+        };
+        let data = format!(
+            "<Document><BkToCstmrStmt>{}{}</BkToCstmrStmt></Document>",
+            statement("<IBAN>DE02100100100006820101</IBAN>"),
+            statement("<Othr><Id>12345</Id></Othr>")
+        );
+        let ids: Vec<Option<String>> = parse(data.as_bytes(), "Other")
+            .unwrap()
+            .into_iter()
+            .map(|row| match row.outcome {
+                RowOutcome::Transaction(transaction) => transaction.external_id,
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            ids,
+            [
+                Some("camt:DE02100100100006820101:7".to_owned()),
+                Some("camt:12345:7".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(parse(b"<Document><BkToCstmrDbtCdtNtfctn/></Document>", "Other").is_err());
+        assert!(parse(b"<Document><BkToCstmrStmt><Stmt></Document>", "Other").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Bank CSV exports, read according to an `ImportProfile`.
+
+use super::{parse_amount, ImportError, ParsedRow, RowOutcome};
+use crate::model::{ImportProfile, NewTransaction, NEGATIVE_DEBIT};
+use bigdecimal::{Signed, Zero};
+use chrono::NaiveDate;
+
+pub fn parse(profile: &ImportProfile, data: &[u8]) -> Result<Vec<ParsedRow>, ImportError> {
+    let delimiter = match profile.delimiter.as_bytes() {
+        [delimiter] => *delimiter,
+        _ => return Err(ImportError::Unreadable("invalid delimiter".to_owned())),
+    };
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(profile.has_header)
+        .flexible(true)
+        .from_reader(data);
+
+    let mut rows = Vec::new();
+    for record in reader.byte_records() {
+        let row = match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |position| position.line());
+                let cells: Vec<String> = record
+                    .iter()
+                    .map(|cell| String::from_utf8_lossy(cell).trim().to_owned())
+                    .collect();
+                ParsedRow {
+                    line,
+                    outcome: parse_record(profile, &cells),
+                }
+            }
+            Err(error) => match error.position() {
+                Some(position) => ParsedRow {
+                    line: position.line(),
+                    outcome: RowOutcome::Invalid(error.to_string()),
+                },
+                None => return Err(ImportError::Unreadable(error.to_string())),
+            },
+        };
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn parse_record(profile: &ImportProfile, cells: &[String]) -> RowOutcome {
+    let cell = |column: Option<i32>| {
+        column
+            .and_then(|column| cells.get(column as usize))
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    };
+
+    let Some(date) = cell(Some(profile.date_column)) else {
+        return RowOutcome::Invalid("missing date".to_owned());
+    };
+    let Ok(date) = NaiveDate::parse_from_str(date, &profile.date_format) else {
+        return RowOutcome::Invalid(format!("invalid date: {}", date));
+    };
+
+    let amount = |column: Option<i32>| match cell(column) {
+        Some(value) => parse_amount(value, &profile.decimal_separator)
+            .map(Some)
+            .ok_or_else(|| format!("invalid amount: {}", value)),
+        None => Ok(None),
+    };
+    // the amount of money going out, `None` for money coming in
+    let outgoing = if profile.debit_column.is_some() {
+        let debit = match amount(profile.debit_column) {
+            Ok(debit) => debit,
+            Err(error) => return RowOutcome::Invalid(error),
+        };
+        let credit = match amount(profile.credit_column) {
+            Ok(credit) => credit,
+            Err(error) => return RowOutcome::Invalid(error),
+        };
+        match (debit, credit) {
+            (Some(debit), _) if !debit.is_zero() => Some(debit.abs()),
+            (_, Some(credit)) if !credit.is_zero() => None,
+            (None, None) => return RowOutcome::Invalid("missing amount".to_owned()),
+            _ => return RowOutcome::Skipped("zero amount".to_owned()),
+        }
+    } else {
+        let amount = match amount(profile.amount_column) {
+            Ok(Some(amount)) => amount,
+            Ok(None) => return RowOutcome::Invalid("missing amount".to_owned()),
+            Err(error) => return RowOutcome::Invalid(error),
+        };
+        if amount.is_zero() {
+            return RowOutcome::Skipped("zero amount".to_owned());
+        }
+        let is_debit = if profile.sign_convention == NEGATIVE_DEBIT {
+            amount.is_negative()
+        } else {
+            amount.is_positive()
+        };
+        is_debit.then(|| amount.abs())
+    };
+    let Some(amount) = outgoing else {
+        return RowOutcome::Skipped("incoming money is not a transaction".to_owned());
+    };
+
+    RowOutcome::Transaction(NewTransaction {
+        user_id: None,
+        amount,
+        category: cell(profile.category_column)
+            .unwrap_or(&profile.default_category)
+            .to_owned(),
+        description: cell(profile.description_column).map(str::to_owned),
+        date,
+        category_id: None,
+        external_id: None,
+        payee_id: None,
+        account_id: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::POSITIVE_DEBIT;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    fn profile() -> ImportProfile {
+        ImportProfile {
+            id: 1,
+            user_id: 1,
+            name: "Bank".to_owned(),
+            delimiter: ";".to_owned(),
+            has_header: true,
+            date_column: 0,
+            date_format: "%d.%m.%Y".to_owned(),
+            description_column: Some(1),
+            category_column: None,
+            amount_column: Some(2),
+            debit_column: None,
+            credit_column: None,
+            decimal_separator: ",".to_owned(),
+            sign_convention: NEGATIVE_DEBIT.to_owned(),
+            default_category: "Other".to_owned(),
+        }
+    }
+
+    fn transaction(row: &ParsedRow) -> &NewTransaction {
+        match &row.outcome {
+            RowOutcome::Transaction(transaction) => transaction,
+            _ => panic!("row {} is not a transaction", row.line),
+        }
+    }
+
+    fn error(row: &ParsedRow) -> &str {
+        match &row.outcome {
+            RowOutcome::Skipped(error) | RowOutcome::Invalid(error) => error,
+            _ => panic!("row {} was imported", row.line),
+        }
+    }
+
+    #[test]
+    fn reads_signed_amounts() {
+        let data = "Date;Text;Amount\n\
+            31.03.2024;Corner Shop;-1.234,56\n\
+            01.04.2024;Salary;2.000,00\n\
+            02.04.2024;Nothing;0,00\n";
+        let rows = parse(&profile(), data.as_bytes()).unwrap();
+
+        assert_eq!(rows.len(), 3);
+        let shop = transaction(&rows[0]);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(shop.amount, BigDecimal::from_str("1234.56").unwrap());
+        assert_eq!(shop.date, NaiveDate::from_ymd_opt(2024, 3, 31).unwrap());
+        assert_eq!(shop.description.as_deref(), Some("Corner Shop"));
+        assert_eq!(shop.category, "Other");
+        assert_eq!(error(&rows[1]), "incoming money is not a transaction");
+        assert_eq!(error(&rows[2]), "zero amount");
+    }
+
+    #[test]
+    fn reads_positive_debits() {
+        let mut profile = profile();
+        profile.sign_convention = POSITIVE_DEBIT.to_owned();
+        let rows = parse(
+            &profile,
+            "Date;Text;Amount\n05.01.2024;Card;12,50\n".as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            transaction(&rows[0]).amount,
+            BigDecimal::from_str("12.50").unwrap()
+        );
+    }
+
+    #[test]
+    fn reads_debit_and_credit_columns() {
+        let profile = ImportProfile {
+            delimiter: ",".to_owned(),
+            has_header: false,
+            date_format: "%Y-%m-%d".to_owned(),
+            category_column: Some(2),
+            amount_column: None,
+            debit_column: Some(3),
+            credit_column: Some(4),
+            decimal_separator: ".".to_owned(),
+            ..profile()
+        };
+        let data = "2024-02-01,Rent,Housing,950.00,\n2024-02-02,Refund,,,20.00\n";
+        let rows = parse(&profile, data.as_bytes()).unwrap();
+
+        let rent = transaction(&rows[0]);
+        assert_eq!(rows[0].line, 1);
+        assert_eq!(rent.amount, BigDecimal::from(950));
+        assert_eq!(rent.category, "Housing");
+        assert_eq!(error(&rows[1]), "incoming money is not a transaction");
+    }
+
+    #[test]
+    fn reports_invalid_rows() {
+        let data = "Date;Text;Amount\n\
+            2024-03-31;Shop;-1,00\n\
+            31.03.2024;Shop;abc\n\
+            31.03.2024;Shop\n";
+        let rows = parse(&profile(), data.as_bytes()).unwrap();
+
+        assert_eq!(error(&rows[0]), "invalid date: 2024-03-31");
+        assert_eq!(error(&rows[1]), "invalid amount: abc");
+        assert_eq!(error(&rows[2]), "missing amount");
+    }
+
+    #[test]
+    fn rejects_long_delimiters() {
+        let profile = ImportProfile {
+            delimiter: ";;".to_owned(),
+            ..profile()
+        };
+        assert!(parse(&profile, b"").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! Turns bank exports into transactions and income. Each format module
+//! parses a file into `ParsedRow`s, which `import_transactions` validates and
+//! stores.
+
+pub mod camt;
+pub mod csv;
+pub mod mt940;
+pub mod ofx;
+pub mod qif;
+
+use crate::model::{CategorySuggestion, NewIncome, NewTransaction};
+use crate::repositories::{
+    CategoryRepository, ClassifierRepository, IncomeRepository, PayeeRepository, RuleRepository,
+    TransactionsRepository,
+};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use diesel::{Connection, PgConnection, QueryResult};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("the file could not be read: {0}")]
+    Unreadable(String),
+}
+
+/// What became of one line of an imported file.
+pub enum RowOutcome {
+    Transaction(NewTransaction),
+    Income(NewIncome),
+    Skipped(String),
+    Invalid(String),
+}
+
+pub struct ParsedRow {
+    pub line: u64,
+    pub outcome: RowOutcome,
+}
+
+#[derive(Serialize)]
+pub struct RowReport {
+    pub line: u64,
+    pub status: &'static str,
+    pub transaction: Option<NewTransaction>,
+    pub income: Option<NewIncome>,
+    pub error: Option<String>,
+    /// Something about an imported row the user should check.
+    pub warning: Option<String>,
+    /// Ids of existing transactions this row looks like a duplicate of.
+    pub possible_duplicates: Vec<i32>,
+    /// The category the classifier finds most likely for a transaction.
+    pub suggestion: Option<CategorySuggestion>,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub committed: bool,
+    pub imported: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    /// Rows imported although they look like duplicates of a transaction.
+    pub flagged: usize,
+    pub rows: Vec<RowReport>,
+}
+
+/// Validates the parsed rows of a file for `user_id` and, unless `dry_run`
+/// is set, stores them in a single database transaction. Nothing is stored
+/// when any row is invalid. Rows whose bank id was imported before are
+/// reported as duplicates and skipped; other transactions that look like
+/// existing ones are imported and list them in `possible_duplicates`. The
+/// user's categorization rules run on every transaction before it is checked,
+/// and a payee's default category replaces `default_category`, the one given
+/// to rows whose file states none, when no rule categorized the row.
+/// Every row is booked on `account_id`, which must be one of the user's.
+/// A transaction in a category the user does not have goes to
+/// `fallback_category` with a warning when it is given, and is invalid
+/// otherwise.
+pub fn import_transactions(
+    c: &mut PgConnection,
+    user_id: i32,
+    rows: Vec<ParsedRow>,
+    account_id: Option<i32>,
+    default_category: &str,
+    fallback_category: Option<&str>,
+    dry_run: bool,
+) -> QueryResult<ImportReport> {
+    let external_ids: Vec<String> = rows
+        .iter()
+        .filter_map(|row| match &row.outcome {
+            RowOutcome::Transaction(transaction) => transaction.external_id.clone(),
+            RowOutcome::Income(income) => income.external_id.clone(),
+            _ => None,
+        })
+        .collect();
+    let mut seen: HashSet<String> =
+        TransactionsRepository::find_external_ids(c, user_id, &external_ids)?;
+    seen.extend(IncomeRepository::find_external_ids(
+        c,
+        user_id,
+        &external_ids,
+    )?);
+
+    let rules = RuleRepository::find_rule_set(c, user_id)?;
+    let aliases = PayeeRepository::find_all_aliases(c, user_id)?;
+
+    let mut report = ImportReport {
+        dry_run,
+        committed: false,
+        imported: 0,
+        skipped: 0,
+        failed: 0,
+        flagged: 0,
+        rows: Vec::new(),
+    };
+    for row in rows {
+        let mut report_row = RowReport {
+            line: row.line,
+            status: "ok",
+            transaction: None,
+            income: None,
+            error: None,
+            warning: None,
+            possible_duplicates: Vec::new(),
+            suggestion: None,
+        };
+        let external_id = match &row.outcome {
+            RowOutcome::Transaction(transaction) => transaction.external_id.clone(),
+            RowOutcome::Income(income) => income.external_id.clone(),
+            _ => None,
+        };
+        match row.outcome {
+            RowOutcome::Transaction(mut transaction) => {
+                transaction.account_id = account_id;
+                rules.apply(&mut transaction, true);
+                if let Some(description) = &transaction.description {
+                    if let Some(payee) =
+                        PayeeRepository::find_for_description(c, user_id, &aliases, description)?
+                    {
+                        // the file's and a rule's category win over the payee's
+                        let uncategorized = transaction.category_id.is_none()
+                            && transaction.category == default_category;
+                        if let (true, Some(category)) = (uncategorized, payee.category) {
+                            transaction.category = category;
+                        }
+                        transaction.payee_id = Some(payee.id);
+                    }
+                }
+                if let Some(fallback) = fallback_category {
+                    if CategoryRepository::find_by_path(c, user_id, &transaction.category)?
+                        .is_none()
+                    {
+                        report_row.warning = Some(format!(
+                            "unknown category {}, imported into {}",
+                            transaction.category, fallback
+                        ));
+                        transaction.category = fallback.to_owned();
+                    }
+                }
+                report_row.suggestion =
+                    ClassifierRepository::suggest_new(c, user_id, &transaction, 1)?.pop();
+                match check_transaction(c, user_id, transaction)? {
+                    Ok(transaction) => report_row.transaction = Some(transaction),
+                    Err((transaction, error)) => {
+                        report_row.status = "invalid";
+                        report_row.transaction = Some(transaction);
+                        report_row.error = Some(error);
+                    }
+                }
+            }
+            RowOutcome::Income(mut income) => {
+                income.user_id = Some(user_id);
+                income.account_id = account_id;
+                if let Err(error) = income.validate() {
+                    report_row.status = "invalid";
+                    report_row.error = Some(error.code.to_string());
+                }
+                report_row.income = Some(income);
+            }
+            RowOutcome::Skipped(reason) => {
+                report_row.status = "skipped";
+                report_row.error = Some(reason);
+            }
+            RowOutcome::Invalid(error) => {
+                report_row.status = "invalid";
+                report_row.error = Some(error);
+            }
+        }
+        if let Some(external_id) = external_id {
+            if report_row.status == "ok" && !seen.insert(external_id) {
+                report_row.status = "duplicate";
+                report_row.error = Some("already imported".to_owned());
+            }
+        }
+        if let (Some(transaction), "ok") = (&report_row.transaction, report_row.status) {
+            report_row.possible_duplicates = TransactionsRepository::find_possible_duplicates(
+                c,
+                user_id,
+                &transaction.amount,
+                transaction.date,
+                transaction.description.as_deref(),
+                None,
+            )?
+            .into_iter()
+            .map(|duplicate| duplicate.id)
+            .collect();
+            if !report_row.possible_duplicates.is_empty() {
+                report.flagged += 1;
+            }
+        }
+        match report_row.status {
+            "ok" => report.imported += 1,
+            "skipped" | "duplicate" => report.skipped += 1,
+            _ => report.failed += 1,
+        }
+        report.rows.push(report_row);
+    }
+
+    if dry_run || report.failed > 0 || report.imported == 0 {
+        return Ok(report);
+    }
+    c.transaction(|c| {
+        for row in report.rows.iter().filter(|row| row.status == "ok") {
+            if let Some(transaction) = &row.transaction {
+                TransactionsRepository::create_transaction(c, transaction.clone())?;
+            }
+            if let Some(income) = &row.income {
+                IncomeRepository::create_income(c, income.clone())?;
+            }
+        }
+        QueryResult::Ok(())
+    })?;
+    report.committed = true;
+    Ok(report)
+}
+
+/// Runs `NewTransaction::validate` and resolves the category of a row.
+fn check_transaction(
+    c: &mut PgConnection,
+    user_id: i32,
+    mut transaction: NewTransaction,
+) -> QueryResult<Result<NewTransaction, (NewTransaction, String)>> {
+    transaction.user_id = Some(user_id);
+    if let Err(error) = transaction.validate() {
+        let error = error.code.to_string();
+        return Ok(Err((transaction, error)));
+    }
+    match CategoryRepository::find_by_path(c, user_id, &transaction.category)? {
+        Some(category) => {
+            transaction.category_id = Some(category.id);
+            transaction.category = category.name;
+            Ok(Ok(transaction))
+        }
+        None => {
+            let error = format!("unknown category: {}", transaction.category);
+            Ok(Err((transaction, error)))
+        }
+    }
+}
+
+/// Parses an amount written with `decimal_separator` (`.` or `,`). Currency
+/// symbols, spaces and thousands separators are ignored and an amount in
+/// parentheses is negative.
+pub fn parse_amount(value: &str, decimal_separator: &str) -> Option<BigDecimal> {
+    let negative = value.trim().starts_with('(') && value.trim().ends_with(')');
+    let thousands_separator = if decimal_separator == "," { '.' } else { ',' };
+    let digits: String = value
+        .chars()
+        .filter(|ch| ch.is_ascii_digit() || matches!(ch, '+' | '-' | '.' | ','))
+        .filter(|ch| *ch != thousands_separator)
+        .map(|ch| if ch == ',' { '.' } else { ch })
+        .collect();
+    let amount = BigDecimal::from_str(&digits).ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+/// Description of a bank statement entry: the counterparty and the
+/// remittance information, followed by the value date when money moved on
+/// another day than the entry was booked.
+pub fn describe_entry(
+    counterparty: Option<&str>,
+    remittance: Option<&str>,
+    booking_date: NaiveDate,
+    value_date: Option<NaiveDate>,
+) -> Option<String> {
+    let mut description = [counterparty, remittance]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(": ");
+    if let Some(value_date) = value_date.filter(|value_date| *value_date != booking_date) {
+        if !description.is_empty() {
+            description.push(' ');
+        }
+        description.push_str(&format!("(value date {})", value_date));
+    }
+    (!description.is_empty()).then_some(description)
+}
+
+/// The external id of a statement entry. Banks only keep their entry ids
+/// unique within one account, so the id is scoped by the file `format` and
+/// the statement's `account`, e.g. `ofx:021000021:1234567:2024030101`.
+pub fn external_id<S: AsRef<str>>(format: &str, account: &[S], reference: &str) -> String {
+    let mut parts = vec![format];
+    parts.extend(account.iter().map(|part| part.as_ref().trim()));
+    parts.push(reference.trim());
+    parts.join(":")
+}
@@ -0,0 +1,400 @@
+//! SWIFT MT940 customer statements. Each `:61:` statement line is one row,
+//! described by the `:86:` field that follows it: debits become
+//! transactions in the given category and credits become income. The bank
+//! reference after `//` in the statement line, scoped by the statement's
+//! `:25:` account, is kept as the external id.
+//!
+//! `:86:` is read in the structured German layout (`?20`..`?29` remittance,
+//! `?32`/`?33` counterparty), the SWIFT `/NAME/../REMI/..` layout, or as
+//! free text.
+
+use super::{describe_entry, external_id, parse_amount, ImportError, ParsedRow, RowOutcome};
+use crate::model::{NewIncome, NewTransaction};
+use bigdecimal::Zero;
+use chrono::{Datelike, NaiveDate};
+
+/// Codes of the SWIFT `:86:` layout.
+const INFORMATION_CODES: [&str; 12] = [
+    "NAME", "REMI", "EREF", "ORDP", "BENM", "ADDR", "IBAN", "BIC", "PURP", "MARF", "CSID", "ULTD",
+];
+
+/// A `:61:` statement line with the `:86:` field that describes it.
+struct StatementLine {
+    line: u64,
+    /// The `:25:` account of the statement.
+    account: String,
+    value: String,
+    information: Option<String>,
+}
+
+pub fn parse(data: &[u8], category: &str) -> Result<Vec<ParsedRow>, ImportError> {
+    let text = String::from_utf8_lossy(data);
+
+    // fields start with `:tag:` and continue on the lines that do not
+    let mut fields: Vec<(u64, String, String)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('{') || line == "-" || line.starts_with("-}") {
+            continue;
+        }
+        let tag = line
+            .strip_prefix(':')
+            .and_then(|rest| rest.split_once(':'))
+            .filter(|(tag, _)| tag.len() <= 3 && tag.starts_with(|ch: char| ch.is_ascii_digit()));
+        match (tag, fields.last_mut()) {
+            (Some((tag, value)), _) => {
+                fields.push((index as u64 + 1, tag.to_owned(), value.to_owned()))
+            }
+            (None, Some((_, _, value))) => {
+                value.push('\n');
+                value.push_str(line);
+            }
+            (None, None) => {}
+        }
+    }
+    if !fields.iter().any(|(_, tag, _)| tag == "20") {
+        return Err(ImportError::Unreadable("not an MT940 statement".to_owned()));
+    }
+
+    let mut lines: Vec<StatementLine> = Vec::new();
+    let mut account = String::new();
+    let mut previous = String::new();
+    for (line, tag, value) in fields {
+        match tag.as_str() {
+            "20" => account.clear(),
+            "25" => account = value.lines().next().unwrap_or_default().trim().to_owned(),
+            "61" => lines.push(StatementLine {
+                line,
+                account: account.clone(),
+                value,
+                information: None,
+            }),
+            "86" if previous == "61" => {
+                if let Some(statement_line) = lines.last_mut() {
+                    statement_line.information = Some(value);
+                }
+            }
+            _ => {}
+        }
+        previous = tag;
+    }
+
+    Ok(lines
+        .into_iter()
+        .map(|statement_line| ParsedRow {
+            line: statement_line.line,
+            outcome: parse_statement_line(&statement_line, category),
+        })
+        .collect())
+}
+
+/// Reads `YYMMDD[MMDD]<mark>[funds code]<amount><type><reference>[//<bank
+/// reference>]`, where the mark is `C`, `D`, `RC` or `RD`.
+fn parse_statement_line(statement_line: &StatementLine, category: &str) -> RowOutcome {
+    let value = statement_line.value.lines().next().unwrap_or_default();
+    let Some(value_date) = value.get(..6).and_then(parse_date) else {
+        return RowOutcome::Invalid(format!("invalid value date: {}", value));
+    };
+    let mut rest = &value[6..];
+
+    let entry_date = rest
+        .get(..4)
+        .filter(|date| date.chars().all(|ch| ch.is_ascii_digit()))
+        .and_then(|date| {
+            let month = date[..2].parse().ok()?;
+            let day = date[2..].parse().ok()?;
+            // the entry date can fall in the year before or after the value date
+            let year = match (month, value_date.month()) {
+                (12, 1) => value_date.year() - 1,
+                (1, 12) => value_date.year() + 1,
+                _ => value_date.year(),
+            };
+            NaiveDate::from_ymd_opt(year, month, day)
+        });
+    if entry_date.is_some() {
+        rest = &rest[4..];
+    }
+
+    let (debit, mark_length) = if rest.starts_with("RC") {
+        (true, 2)
+    } else if rest.starts_with("RD") {
+        (false, 2)
+    } else if rest.starts_with('D') {
+        (true, 1)
+    } else if rest.starts_with('C') {
+        (false, 1)
+    } else {
+        return RowOutcome::Invalid(format!("invalid debit/credit mark: {}", value));
+    };
+    rest = &rest[mark_length..];
+    if rest.starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+        // third letter of the currency code
+        rest = &rest[1..];
+    }
+
+    let amount_length = rest
+        .find(|ch: char| !ch.is_ascii_digit() && ch != ',')
+        .unwrap_or(rest.len());
+    let Some(amount) = parse_amount(&rest[..amount_length], ",") else {
+        return RowOutcome::Invalid(format!("invalid amount: {}", value));
+    };
+    if amount.is_zero() {
+        return RowOutcome::Skipped("zero amount".to_owned());
+    }
+    // the transaction type is four characters, such as NTRF
+    let references = rest.get(amount_length + 4..).unwrap_or_default();
+    let bank_reference = references
+        .split_once("//")
+        .map(|(_, reference)| reference.trim())
+        // banks without references of their own write NONREF
+        .filter(|reference| !reference.is_empty() && !reference.eq_ignore_ascii_case("NONREF"))
+        .map(|reference| external_id("mt940", &[&statement_line.account], reference));
+
+    let (counterparty, remittance) = statement_line
+        .information
+        .as_deref()
+        .map(parse_information)
+        .unwrap_or_default();
+    let booking_date = entry_date.unwrap_or(value_date);
+    let description = describe_entry(
+        counterparty.as_deref(),
+        remittance.as_deref(),
+        booking_date,
+        Some(value_date),
+    );
+    if debit {
+        RowOutcome::Transaction(NewTransaction {
+            user_id: None,
+            amount,
+            category: category.to_owned(),
+            description,
+            date: booking_date,
+            category_id: None,
+            external_id: bank_reference,
+            payee_id: None,
+            account_id: None,
+        })
+    } else {
+        RowOutcome::Income(NewIncome {
+            user_id: None,
+            amount,
+            source: description.unwrap_or_else(|| "MT940 import".to_owned()),
+            date: booking_date,
+            external_id: bank_reference,
+            account_id: None,
+        })
+    }
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    let year: i32 = value.get(..2)?.parse().ok()?;
+    let year = if year < 70 { 2000 + year } else { 1900 + year };
+    NaiveDate::from_ymd_opt(
+        year,
+        value.get(2..4)?.parse().ok()?,
+        value.get(4..6)?.parse().ok()?,
+    )
+}
+
+/// The counterparty and remittance information of an `:86:` field.
+fn parse_information(information: &str) -> (Option<String>, Option<String>) {
+    let non_empty = |value: String| {
+        let value = value.trim().to_owned();
+        (!value.is_empty()).then_some(value)
+    };
+
+    if information.contains("?20") || information.contains("?32") {
+        // subfields run across lines, so the line breaks are not part of them
+        let information: String = information.lines().collect();
+        let mut counterparty = String::new();
+        let mut remittance = String::new();
+        for subfield in information.split('?').skip(1) {
+            let (code, value) = subfield.split_at(subfield.len().min(2));
+            match code.parse::<u8>() {
+                Ok(20..=29 | 60..=63) => remittance.push_str(value),
+                Ok(32 | 33) => {
+                    if !counterparty.is_empty() {
+                        counterparty.push(' ');
+                    }
+                    counterparty.push_str(value)
+                }
+                _ => {}
+            }
+        }
+        return (non_empty(counterparty), non_empty(remittance));
+    }
+
+    if information.starts_with('/') {
+        let information = information.replace('\n', "");
+        let parts: Vec<&str> = information.split('/').collect();
+        let mut counterparty = None;
+        let mut remittance = None;
+        let mut index = 0;
+        while index < parts.len() {
+            let code = parts[index];
+            if !INFORMATION_CODES.contains(&code) {
+                index += 1;
+                continue;
+            }
+            let end = parts[index + 1..]
+                .iter()
+                .position(|part| INFORMATION_CODES.contains(part))
+                .map_or(parts.len(), |end| index + 1 + end);
+            let value = parts[index + 1..end].join("/");
+            let value = value.trim_matches('/').to_owned();
+            match code {
+                "NAME" => counterparty = counterparty.or_else(|| non_empty(value)),
+                "REMI" => remittance = remittance.or_else(|| non_empty(value)),
+                _ => {}
+            }
+            index = end;
+        }
+        return (counterparty, remittance);
+    }
+
+    (
+        None,
+        non_empty(information.lines().collect::<Vec<_>>().join(" ")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    const STATEMENT: &str = "{1:F01BANKDEFFXXXX0000000000}{4:
+:20:STARTUMSE
+:25:10020030/1234567
+:28C:00001/001
+:60F:C240301EUR1000,00
+:61:2403010301D42,50NTRFNONREF//0915123
+:86:166?00SEPA-UEBERWEISUNG?20Invoice 4711 March 20?2124?32Stadtw
+erke?33Muenchen
+:61:240302C1500,00NTRFNONREF//0915124
+:86:/NAME/ACME Corp/REMI/Salary 03/2024/EREF/X1
+:61:2403040305RC10,00NTRFNONREF//0915125
+:86:Card payment reversed
+:61:240306RD5,00NTRFNONREF//NONREF
+:61:240307D0,00NTRFNONREF
+:62F:C240307EUR2457,50
+-}";
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn reads_statement_lines() {
+        let rows = parse(STATEMENT.as_bytes(), "Other").unwrap();
+
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0].line, 6);
+        let RowOutcome::Transaction(invoice) = &rows[0].outcome else {
+            panic!("the debit is not a transaction");
+        };
+        assert_eq!(invoice.amount, BigDecimal::from_str("42.50").unwrap());
+        assert_eq!(invoice.date, date(2024, 3, 1));
+        assert_eq!(invoice.category, "Other");
+        assert_eq!(
+            invoice.description.as_deref(),
+            Some("Stadtwerke Muenchen: Invoice 4711 March 2024")
+        );
+        assert_eq!(
+            invoice.external_id.as_deref(),
+            Some("mt940:10020030/1234567:0915123")
+        );
+
+        let RowOutcome::Income(salary) = &rows[1].outcome else {
+            panic!("the credit is not income");
+        };
+        assert_eq!(salary.amount, BigDecimal::from(1500));
+        assert_eq!(salary.source, "ACME Corp: Salary 03/2024");
+        assert!(matches!(&rows[4].outcome, RowOutcome::Skipped(reason) if reason == "zero amount"));
+    }
+
+    #[test]
+    fn reads_reversals() {
+        let rows = parse(STATEMENT.as_bytes(), "Other").unwrap();
+
+        // a reversed credit takes money out, booked on its entry date
+        let RowOutcome::Transaction(reversed_credit) = &rows[2].outcome else {
+            panic!("the reversed credit is not a transaction");
+        };
+        assert_eq!(reversed_credit.amount, BigDecimal::from(10));
+        assert_eq!(reversed_credit.date, date(2024, 3, 5));
+        assert_eq!(
+            reversed_credit.description.as_deref(),
+            Some("Card payment reversed (value date 2024-03-04)")
+        );
+
+        // a reversed debit brings it back, and NONREF is no bank reference
+        let RowOutcome::Income(reversed_debit) = &rows[3].outcome else {
+            panic!("the reversed debit is not income");
+        };
+        assert_eq!(reversed_debit.amount, BigDecimal::from(5));
+        assert_eq!(reversed_debit.external_id, None);
+    }
+
+    #[test]
+    fn reads_entry_dates_across_years() {
+        let statement = ":20:X\n:25:ACCOUNT\n:61:2312311230D1,00NTRF\n:61:2401021231D1,00NTRF\n";
+        let dates: Vec<NaiveDate> = parse(statement.as_bytes(), "Other")
+            .unwrap()
+            .into_iter()
+            .map(|row| match row.outcome {
+                RowOutcome::Transaction(transaction) => transaction.date,
+                _ => panic!("row {} is not a transaction", row.line),
+            })
+            .collect();
+
+        assert_eq!(dates, [date(2023, 12, 30), date(2023, 12, 31)]);
+    }
+
+    #[test]
+    fn scopes_ids_by_account() {
+        let statement = ":20:A\n:25:ONE\n:61:240101D1,00NTRFREF//7\n\
+            :20:B\n:25:TWO\n:61:240101D1,00NTRFREF//7\n\
+            :20:C\n:61:240101D1,00NTRFREF//7\n";
+        let ids: Vec<Option<String>> = parse(statement.as_bytes(), "Other")
+            .unwrap()
+            .into_iter()
+            .map(|row| match row.outcome {
+                RowOutcome::Transaction(transaction) => transaction.external_id,
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            ids,
+            [
+                Some("mt940:ONE:7".to_owned()),
+                Some("mt940:TWO:7".to_owned()),
+                Some("mt940::7".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        let statement = ":20:X\n:61:2413O1D1,00NTRF\n:61:240101X1,00NTRF\n";
+        let errors: Vec<String> = parse(statement.as_bytes(), "Other")
+            .unwrap()
+            .into_iter()
+            .map(|row| match row.outcome {
+                RowOutcome::Invalid(error) => error,
+                _ => panic!("row {} is valid", row.line),
+            })
+            .collect();
+
+        assert_eq!(
+            errors,
+            [
+                "invalid value date: 2413O1D1,00NTRF",
+                "invalid debit/credit mark: 240101X1,00NTRF",
+            ]
+        );
+        assert!(parse(b"<OFX>", "Other").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! OFX statements, both 1.x (SGML, where leaf elements are not closed) and
+//! 2.x (XML). Quicken's QFX files are OFX with extra elements and are read
+//! the same way. Debits become transactions in the given category and
+//! credits become income; each entry's FITID, scoped by the `BANKID` and
+//! `ACCTID` of its statement, is kept as its external id.
+
+use super::{external_id, parse_amount, ImportError, ParsedRow, RowOutcome};
+use crate::model::{NewIncome, NewTransaction};
+use bigdecimal::{Signed, Zero};
+use chrono::NaiveDate;
+
+pub fn parse(data: &[u8], category: &str) -> Result<Vec<ParsedRow>, ImportError> {
+    let text = String::from_utf8_lossy(data);
+    // ASCII upper-casing keeps byte offsets, so positions found in `upper`
+    // are valid in `text`
+    let upper = text.to_ascii_uppercase();
+    if !upper.contains("<OFX>") {
+        return Err(ImportError::Unreadable("not an OFX file".to_owned()));
+    }
+
+    let mut rows = Vec::new();
+    let mut position = 0;
+    while let Some(found) = upper[position..].find("<STMTTRN>") {
+        let start = position + found;
+        let body = start + "<STMTTRN>".len();
+        let end = ["</STMTTRN>", "<STMTTRN>", "</BANKTRANLIST>"]
+            .iter()
+            .filter_map(|tag| upper[body..].find(tag))
+            .min()
+            .map_or(text.len(), |end| body + end);
+        // the account is in the header of the statement holding the entry
+        let statement = ["<STMTRS>", "<CCSTMTRS>"]
+            .iter()
+            .filter_map(|tag| upper[..start].rfind(tag))
+            .max()
+            .unwrap_or(0);
+        let (header, header_upper) = (&text[statement..start], &upper[statement..start]);
+        let account =
+            ["BANKID", "ACCTID"].map(|tag| element(header, header_upper, tag).unwrap_or_default());
+        rows.push(ParsedRow {
+            line: text[..start].matches('\n').count() as u64 + 1,
+            outcome: parse_entry(&text[body..end], &upper[body..end], &account, category),
+        });
+        position = end;
+    }
+    Ok(rows)
+}
+
+/// The text of the first `tag` element in `text`, found in its upper cased
+/// copy `upper`.
+fn element(text: &str, upper: &str, tag: &str) -> Option<String> {
+    let start = upper.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let value = text[start..].split('<').next()?.trim();
+    (!value.is_empty()).then(|| decode_entities(value))
+}
+
+fn parse_entry(entry: &str, upper: &str, account: &[String; 2], category: &str) -> RowOutcome {
+    let field = |tag: &str| element(entry, upper, tag);
+
+    let Some(posted) = field("DTPOSTED") else {
+        return RowOutcome::Invalid("missing DTPOSTED".to_owned());
+    };
+    let Some(date) = posted
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+    else {
+        return RowOutcome::Invalid(format!("invalid date: {}", posted));
+    };
+    let Some(value) = field("TRNAMT") else {
+        return RowOutcome::Invalid("missing TRNAMT".to_owned());
+    };
+    let decimal_separator = if value.contains(',') && !value.contains('.') {
+        ","
+    } else {
+        "."
+    };
+    let Some(amount) = parse_amount(&value, decimal_separator) else {
+        return RowOutcome::Invalid(format!("invalid amount: {}", value));
+    };
+    if amount.is_zero() {
+        return RowOutcome::Skipped("zero amount".to_owned());
+    }
+
+    let external_id = field("FITID").map(|fitid| external_id("ofx", account, &fitid));
+    let description = field("NAME").or_else(|| field("MEMO"));
+    if amount.is_negative() {
+        RowOutcome::Transaction(NewTransaction {
+            user_id: None,
+            amount: amount.abs(),
+            category: category.to_owned(),
+            description,
+            date,
+            category_id: None,
+            external_id,
+            payee_id: None,
+            account_id: None,
+        })
+    } else {
+        RowOutcome::Income(NewIncome {
+            user_id: None,
+            amount,
+            source: description
+                .or_else(|| field("TRNTYPE"))
+                .unwrap_or_else(|| "OFX import".to_owned()),
+            date,
+            external_id,
+            account_id: None,
+        })
+    }
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    const SGML: &str = "OFXHEADER:100
+DATA:OFXSGML
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<BANKACCTFROM>
+<BANKID>021000021
+<ACCTID>1234567
+</BANKACCTFROM>
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240301120000[-5:EST]
+<TRNAMT>-42.50
+<FITID>2024030101
+<NAME>Joe&apos;s &amp; Co
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20240302
+<TRNAMT>1500,00
+<FITID>2024030201
+<MEMO>Payroll
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>2024
+<TRNAMT>-1.00
+</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+";
+
+    #[test]
+    fn reads_sgml_statements() {
+        let rows = parse(SGML.as_bytes(), "Groceries").unwrap();
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].line, 11);
+        let RowOutcome::Transaction(transaction) = &rows[0].outcome else {
+            panic!("the debit is not a transaction");
+        };
+        assert_eq!(transaction.amount, BigDecimal::from_str("42.50").unwrap());
+        assert_eq!(
+            transaction.date,
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+        );
+        assert_eq!(transaction.category, "Groceries");
+        assert_eq!(transaction.description.as_deref(), Some("Joe's & Co"));
+        assert_eq!(
+            transaction.external_id.as_deref(),
+            Some("ofx:021000021:1234567:2024030101")
+        );
+
+        let RowOutcome::Income(income) = &rows[1].outcome else {
+            panic!("the credit is not income");
+        };
+        assert_eq!(income.amount, BigDecimal::from(1500));
+        assert_eq!(income.source, "Payroll");
+
+        let RowOutcome::Invalid(error) = &rows[2].outcome else {
+            panic!("the short date was accepted");
+        };
+        assert_eq!(error, "invalid date: 2024");
+    }
+
+    #[test]
+    fn reads_xml_credit_card_statements() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX><CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+<CCACCTFROM><ACCTID>4111</ACCTID></CCACCTFROM>
+<BANKTRANLIST>
+<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20240105</DTPOSTED><TRNAMT>-9.99</TRNAMT><FITID>A1</FITID><NAME>NETFLIX.COM</NAME></STMTTRN>
+<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20240106</DTPOSTED><TRNAMT>0.00</TRNAMT><FITID>A2</FITID></STMTTRN>
+</BANKTRANLIST>
+</CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1></OFX>"#;
+        let rows = parse(xml.as_bytes(), "Other").unwrap();
+
+        let RowOutcome::Transaction(transaction) = &rows[0].outcome else {
+            panic!("the debit is not a transaction");
+        };
+        assert_eq!(transaction.external_id.as_deref(), Some("ofx::4111:A1"));
+        assert_eq!(transaction.description.as_deref(), Some("NETFLIX.COM"));
+        assert!(matches!(&rows[1].outcome, RowOutcome::Skipped(reason) if reason == "zero amount"));
+    }
+
+    #[test]
+    fn scopes_ids_by_statement() {
+        let statement = |acctid: &str| {
+            format!(
+                "<STMTRS><BANKACCTFROM><BANKID>1<ACCTID>{}</BANKACCTFROM>\
+                <STMTTRN><DTPOSTED>20240101<TRNAMT>-1<FITID>7</STMTTRN></STMTRS>",
+                acctid
+            )
+        };
+        let data = format!("<OFX>{}{}</OFX>", statement("A"), statement("B"));
+        let ids: Vec<Option<String>> = parse(data.as_bytes(), "Other")
+            .unwrap()
+            .into_iter()
+            .map(|row| match row.outcome {
+                RowOutcome::Transaction(transaction) => transaction.external_id,
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            ids,
+            [Some("ofx:1:A:7".to_owned()), Some("ofx:1:B:7".to_owned())]
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse(b"Date,Amount\n", "Other").is_err());
+    }
+}
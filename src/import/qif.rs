@@ -0,0 +1,433 @@
+//! Quicken Interchange Format files. `parse` reads the `!Type:Bank`,
+//! `!Type:CCard` and `!Type:Cash` sections of a QIF file, `write` exports
+//! transactions and income as a `!Type:Bank` section.
+//!
+//! QIF categories are written as `Parent:Child` and map onto category paths
+//! (`Parent > Child`). Split transactions are not supported and are reported
+//! as invalid records.
+
+use super::{parse_amount, ImportError, ParsedRow, RowOutcome};
+use crate::model::{
+    Category, Income, NewIncome, NewTransaction, Transaction, CATEGORY_PATH_SEPARATOR,
+};
+use bigdecimal::{Signed, Zero};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const SUPPORTED_TYPES: [&str; 3] = ["!TYPE:BANK", "!TYPE:CCARD", "!TYPE:CASH"];
+
+/// Lines of one record, keyed by their field letter.
+struct Record {
+    line: u64,
+    fields: Vec<(char, String)>,
+}
+
+impl Record {
+    fn field(&self, code: char) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, value)| *field == code && !value.is_empty())
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads the records of a QIF file. Dates are read month first
+/// (`03/31/2024`, `3/31'24`) unless `day_first` is set; ISO dates are
+/// always accepted. Debits go to their record's category or `category`.
+pub fn parse(data: &[u8], category: &str, day_first: bool) -> Result<Vec<ParsedRow>, ImportError> {
+    let text = String::from_utf8_lossy(data);
+    if !text.trim_start().starts_with('!') {
+        return Err(ImportError::Unreadable("not a QIF file".to_owned()));
+    }
+
+    let mut rows = Vec::new();
+    let mut section = String::new();
+    let mut record: Option<Record> = None;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.starts_with('!') {
+            section = line.trim().to_owned();
+            record = None;
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('^') {
+            if let Some(record) = record.take() {
+                if let Some(outcome) = parse_record(&section, &record, category, day_first) {
+                    rows.push(ParsedRow {
+                        line: record.line,
+                        outcome,
+                    });
+                }
+            }
+            continue;
+        }
+
+        let mut chars = line.chars();
+        let code = chars.next().unwrap_or_default();
+        record
+            .get_or_insert_with(|| Record {
+                line: index as u64 + 1,
+                fields: Vec::new(),
+            })
+            .fields
+            .push((code, chars.as_str().trim().to_owned()));
+    }
+    Ok(rows)
+}
+
+/// `None` for records that are not entries, such as account headers.
+fn parse_record(
+    section: &str,
+    record: &Record,
+    category: &str,
+    day_first: bool,
+) -> Option<RowOutcome> {
+    let upper = section.to_ascii_uppercase();
+    if !upper.starts_with("!TYPE:") {
+        return None;
+    }
+    if !SUPPORTED_TYPES.contains(&upper.as_str()) {
+        return Some(RowOutcome::Skipped(format!(
+            "{} records are not supported",
+            &section["!Type:".len()..]
+        )));
+    }
+    if record.fields.iter().any(|(code, _)| *code == 'S') {
+        return Some(RowOutcome::Invalid(
+            "split transactions are not supported".to_owned(),
+        ));
+    }
+
+    let Some(date) = record.field('D') else {
+        return Some(RowOutcome::Invalid("missing date".to_owned()));
+    };
+    let Some(date) = parse_date(date, day_first) else {
+        return Some(RowOutcome::Invalid(format!("invalid date: {}", date)));
+    };
+    let Some(value) = record.field('T').or_else(|| record.field('U')) else {
+        return Some(RowOutcome::Invalid("missing amount".to_owned()));
+    };
+    let decimal_separator = if value.contains(',') && !value.contains('.') {
+        ","
+    } else {
+        "."
+    };
+    let Some(amount) = parse_amount(value, decimal_separator) else {
+        return Some(RowOutcome::Invalid(format!("invalid amount: {}", value)));
+    };
+    if amount.is_zero() {
+        return Some(RowOutcome::Skipped("zero amount".to_owned()));
+    }
+
+    // `L` holds `Category:Subcategory/Class` or `[Account]` for transfers
+    let label = record.field('L');
+    if label.is_some_and(|label| label.starts_with('[')) {
+        return Some(RowOutcome::Skipped(
+            "transfers between accounts are not imported".to_owned(),
+        ));
+    }
+    let record_category = label
+        .and_then(|label| label.split('/').next())
+        .filter(|label| !label.is_empty())
+        .map(|label| {
+            label
+                .split(':')
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join(&format!(" {} ", CATEGORY_PATH_SEPARATOR))
+        });
+    let payee = record.field('P').or_else(|| record.field('M'));
+
+    Some(if amount.is_negative() {
+        RowOutcome::Transaction(NewTransaction {
+            user_id: None,
+            amount: amount.abs(),
+            category: record_category.unwrap_or_else(|| category.to_owned()),
+            description: payee.map(str::to_owned),
+            date,
+            category_id: None,
+            external_id: None,
+            payee_id: None,
+            account_id: None,
+        })
+    } else {
+        RowOutcome::Income(NewIncome {
+            user_id: None,
+            amount,
+            source: payee
+                .map(str::to_owned)
+                .or(record_category)
+                .unwrap_or_else(|| "QIF import".to_owned()),
+            date,
+            external_id: None,
+            account_id: None,
+        })
+    })
+}
+
+fn parse_date(value: &str, day_first: bool) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date);
+    }
+    let parts: Vec<u32> = value
+        .split(['/', '\'', '-', '.'])
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let [first, second, year] = parts[..] else {
+        return None;
+    };
+    let year = match year {
+        0..=69 => 2000 + year,
+        70..=99 => 1900 + year,
+        _ => year,
+    };
+    let (month, day) = if day_first {
+        (second, first)
+    } else {
+        (first, second)
+    };
+    NaiveDate::from_ymd_opt(year as i32, month, day)
+}
+
+/// Writes transactions as debits and income as credits of one bank
+/// account, ordered by date.
+pub fn write(transactions: &[Transaction], income: &[Income], categories: &[Category]) -> String {
+    let names: HashMap<i32, &Category> = categories
+        .iter()
+        .map(|category| (category.id, category))
+        .collect();
+    let path = |id: i32| {
+        let mut path = Vec::new();
+        let mut next = names.get(&id);
+        while let Some(category) = next {
+            if path.len() > categories.len() {
+                break;
+            }
+            path.push(category.name.as_str());
+            next = category
+                .parent_id
+                .and_then(|parent_id| names.get(&parent_id));
+        }
+        path.reverse();
+        path.join(":")
+    };
+
+    let mut entries: Vec<(NaiveDate, String)> = Vec::new();
+    for transaction in transactions {
+        let mut entry = format!(
+            "D{}\nT-{}\n",
+            transaction.date.format("%m/%d/%Y"),
+            transaction.amount
+        );
+        if let Some(description) = &transaction.description {
+            let _ = writeln!(entry, "P{}", description);
+        }
+        let category = match transaction.category_id {
+            Some(id) => path(id),
+            None => transaction.category.clone(),
+        };
+        let _ = writeln!(entry, "L{}", category);
+        entries.push((transaction.date, entry));
+    }
+    for income in income {
+        let entry = format!(
+            "D{}\nT{}\nP{}\n",
+            income.date.format("%m/%d/%Y"),
+            income.amount,
+            income.source
+        );
+        entries.push((income.date, entry));
+    }
+    entries.sort_by_key(|(date, _)| *date);
+
+    let mut qif = String::from("!Type:Bank\n");
+    for (_, entry) in entries {
+        qif.push_str(&entry);
+        qif.push_str("^\n");
+    }
+    qif
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn reads_two_digit_years() {
+        assert_eq!(parse_date("3/31'24", false), Some(date(2024, 3, 31)));
+        assert_eq!(parse_date("12/ 1'99", false), Some(date(1999, 12, 1)));
+        assert_eq!(parse_date("1/2/69", false), Some(date(2069, 1, 2)));
+        assert_eq!(parse_date("1/2/70", false), Some(date(1970, 1, 2)));
+        assert_eq!(parse_date("03/31/2024", false), Some(date(2024, 3, 31)));
+        assert_eq!(parse_date("31.03.2024", true), Some(date(2024, 3, 31)));
+        assert_eq!(parse_date("2024-03-31", true), Some(date(2024, 3, 31)));
+        assert_eq!(parse_date("31/03/2024", false), None);
+        assert_eq!(parse_date("03/2024", false), None);
+    }
+
+    #[test]
+    fn reads_bank_records() {
+        let data = "!Type:Bank
+D3/31'24
+T-1,234.56
+PPower Co
+LUtilities:Electricity/Home
+^
+D4/1'24
+T2000.00
+LSalary
+^
+D4/2'24
+T-5.00
+LPocket money
+^
+D4/3'24
+T-100.00
+L[Savings]
+^
+D4/4'24
+T-30.00
+SGroceries
+$-20.00
+SHealthcare
+$-10.00
+^
+D4/5'24
+T0.00
+^
+D13/5'24
+T-1.00
+^
+";
+        let rows = parse(data.as_bytes(), "Other", false).unwrap();
+
+        assert_eq!(rows.len(), 7);
+        assert_eq!(rows[0].line, 2);
+        let RowOutcome::Transaction(power) = &rows[0].outcome else {
+            panic!("the debit is not a transaction");
+        };
+        assert_eq!(power.amount, BigDecimal::from_str("1234.56").unwrap());
+        assert_eq!(power.date, date(2024, 3, 31));
+        assert_eq!(power.category, "Utilities > Electricity");
+        assert_eq!(power.description.as_deref(), Some("Power Co"));
+
+        let RowOutcome::Income(salary) = &rows[1].outcome else {
+            panic!("the credit is not income");
+        };
+        assert_eq!(salary.amount, BigDecimal::from(2000));
+        assert_eq!(salary.source, "Salary");
+
+        let RowOutcome::Transaction(pocket_money) = &rows[2].outcome else {
+            panic!("the debit is not a transaction");
+        };
+        assert_eq!(pocket_money.category, "Pocket money");
+        assert_eq!(pocket_money.description, None);
+
+        let errors: Vec<&str> = rows[3..]
+            .iter()
+            .map(|row| match &row.outcome {
+                RowOutcome::Skipped(error) | RowOutcome::Invalid(error) => error.as_str(),
+                _ => panic!("row {} was imported", row.line),
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "transfers between accounts are not imported",
+                "split transactions are not supported",
+                "zero amount",
+                "invalid date: 13/5'24",
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_category() {
+        let rows = parse(
+            b"!Type:CCard\nD2024-01-05\nT-9.99\nMNetflix\n^\n",
+            "Other",
+            false,
+        )
+        .unwrap();
+
+        let RowOutcome::Transaction(transaction) = &rows[0].outcome else {
+            panic!("the debit is not a transaction");
+        };
+        assert_eq!(transaction.category, "Other");
+        assert_eq!(transaction.description.as_deref(), Some("Netflix"));
+    }
+
+    #[test]
+    fn skips_other_sections() {
+        let data = "!Account\nNChecking\nTBank\n^\n!Type:Invst\nD1/1'24\nT-1.00\n^\n";
+        let rows = parse(data.as_bytes(), "Other", false).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert!(matches!(
+            &rows[0].outcome,
+            RowOutcome::Skipped(reason) if reason == "Invst records are not supported"
+        ));
+        assert!(parse(b"Date,Amount\n", "Other", false).is_err());
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let categories = [
+            Category {
+                id: 1,
+                user_id: 1,
+                name: "Utilities".to_owned(),
+                parent_id: None,
+            },
+            Category {
+                id: 2,
+                user_id: 1,
+                name: "Electricity".to_owned(),
+                parent_id: Some(1),
+            },
+        ];
+        let transaction = Transaction {
+            id: 1,
+            user_id: Some(1),
+            amount: BigDecimal::from_str("80.25").unwrap(),
+            category: "Electricity".to_owned(),
+            description: Some("Power Co".to_owned()),
+            date: date(2024, 3, 2),
+            category_id: Some(2),
+            external_id: None,
+            payee_id: None,
+            account_id: None,
+        };
+        let income = Income {
+            id: 1,
+            user_id: Some(1),
+            amount: BigDecimal::from(2000),
+            source: "Salary".to_owned(),
+            date: date(2024, 3, 1),
+            external_id: None,
+            account_id: None,
+        };
+        let qif = write(&[transaction], &[income], &categories);
+
+        assert_eq!(
+            qif,
+            "!Type:Bank\nD03/01/2024\nT2000\nPSalary\n^\n\
+            D03/02/2024\nT-80.25\nPPower Co\nLUtilities:Electricity\n^\n"
+        );
+        let rows = parse(qif.as_bytes(), "Other", false).unwrap();
+        let RowOutcome::Transaction(transaction) = &rows[1].outcome else {
+            panic!("the debit is not a transaction");
+        };
+        assert_eq!(transaction.category, "Utilities > Electricity");
+    }
+}
@@ -0,0 +1,58 @@
+//! Work the server does in the background, outside of any request.
+
+use crate::repositories::{BudgetRepository, RecurringRepository};
+use crate::DBConnection;
+use chrono::Local;
+use rocket::fairing::AdHoc;
+use rocket::tokio::time::{interval, Duration};
+
+/// Seconds between two runs, overridden by the `recurring_interval` setting
+/// (e.g. `ROCKET_RECURRING_INTERVAL=600`).
+const DEFAULT_RECURRING_INTERVAL: u64 = 3600;
+
+/// Posts the due occurrences of recurring templates and creates the new
+/// periods of recurring budgets when the server starts and then periodically.
+pub fn recurring_fairing() -> AdHoc {
+    AdHoc::on_liftoff("Recurring transactions", |rocket| {
+        Box::pin(async move {
+            let seconds = rocket
+                .figment()
+                .extract_inner::<u64>("recurring_interval")
+                .unwrap_or(DEFAULT_RECURRING_INTERVAL)
+                .max(1);
+            let Some(pool) = DBConnection::pool(rocket).cloned() else {
+                log::error!("recurring transactions are not posted: no database pool");
+                return;
+            };
+
+            rocket::tokio::spawn(async move {
+                let mut ticks = interval(Duration::from_secs(seconds));
+                loop {
+                    ticks.tick().await;
+                    let Some(db) = pool.get().await else {
+                        continue;
+                    };
+                    let today = Local::now().date_naive();
+                    match db
+                        .run(move |c| RecurringRepository::post_due(c, None, today))
+                        .await
+                    {
+                        Ok(0) => {}
+                        Ok(posted) => log::info!("posted {} recurring occurrences", posted),
+                        Err(error) => {
+                            log::error!("posting recurring occurrences failed: {}", error)
+                        }
+                    }
+                    match db
+                        .run(move |c| BudgetRepository::materialize_periods(c, None, today))
+                        .await
+                    {
+                        Ok(0) => {}
+                        Ok(created) => log::info!("created {} budget periods", created),
+                        Err(error) => log::error!("creating budget periods failed: {}", error),
+                    }
+                }
+            });
+        })
+    })
+}
@@ -3,6 +3,8 @@ extern crate diesel_migrations;
 extern crate rocket;
 
 pub mod command;
+pub mod import;
+pub mod jobs;
 mod model;
 pub mod repositories;
 pub mod routes;
@@ -1,8 +1,14 @@
 use crate::schema::*;
+use crate::utils::schedule::Schedule;
+use crate::utils::query::TransactionQuery;
+use crate::utils::search::prefix_query;
 use bigdecimal::BigDecimal;
-use chrono::NaiveDate;
-use diesel::{Associations, Insertable, Queryable};
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime};
+use diesel::sql_types::{BigInt, Date, Float, Integer, Nullable, Numeric, Text};
+use diesel::{Associations, Insertable, Queryable, QueryableByName};
+use rocket::FromForm;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use validator::{Validate as ValidatorValidate, ValidationError};
 use validator_derive::Validate;
 
@@ -23,37 +29,54 @@ fn validate_start_date_before_end_date(
     Ok(())
 }
 
-fn capitalize_first_letter(word: String) -> String {
-    if let Some(first_char) = word.chars().next() {
-        let first = first_char.to_uppercase().collect::<String>();
-        let rest = word.chars().skip(1).collect::<String>().to_lowercase();
-        return format!("{}{}", first, rest);
+/// Separates the levels of a category path such as `Utilities > Electricity`.
+pub const CATEGORY_PATH_SEPARATOR: char = '>';
+
+fn validate_category(category: &str) -> Result<(), ValidationError> {
+    if category.trim().chars().count() < 3 {
+        return Err(ValidationError::new(
+            "Category should be more than 2 characters",
+        ));
     }
-    String::new()
+    Ok(())
 }
 
-fn validate_category(category: String) -> Result<(), ValidationError> {
-    let value = capitalize_first_letter(category);
-    match value.as_str() {
-        "Groceries" => Ok(()),
-        "Utilities" => Ok(()),
-        "Entertainment" => Ok(()),
-        "Rent" => Ok(()),
-        "Healthcare" => Ok(()),
-        "Electricity" => Ok(()),
-        "Education" => Ok(()),
-        "Subscriptions" => Ok(()),
-        "Other" => Ok(()),
-        _ => Err(ValidationError::new("wrong category")),
+fn validate_category_name(name: &str) -> Result<(), ValidationError> {
+    validate_category(name)?;
+    if name.contains(CATEGORY_PATH_SEPARATOR) {
+        return Err(ValidationError::new("Category name cannot contain '>'"));
     }
+    Ok(())
 }
 
+/// Categories every new user starts with, as `(name, parent name)`. Parents
+/// are listed before their children.
+pub const DEFAULT_CATEGORIES: [(&str, Option<&str>); 9] = [
+    ("Groceries", None),
+    ("Utilities", None),
+    ("Entertainment", None),
+    ("Rent", None),
+    ("Healthcare", None),
+    ("Electricity", Some("Utilities")),
+    ("Education", None),
+    ("Subscriptions", None),
+    ("Other", None),
+];
+
+/// Category of new transactions that neither name a category nor match a
+/// categorization rule. It is one of `DEFAULT_CATEGORIES`.
+pub const FALLBACK_CATEGORY: &str = "Other";
+
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub email: String,
     pub password_hash: String,
+    pub budgeting_mode: String,
+    pub envelope_start: Option<NaiveDate>,
+    pub rule_match_mode: String,
+    pub classifier_trained_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Insertable, Deserialize, Validate)]
@@ -86,6 +109,12 @@ pub struct Budget {
     pub amount: BigDecimal,
     pub start_date: NaiveDate,
     pub end_date: NaiveDate,
+    pub category_id: Option<i32>,
+    pub recurrence: Option<String>,
+    pub rollover: String,
+    pub rollover_amount: BigDecimal,
+    pub previous_budget_id: Option<i32>,
+    pub alert_thresholds: Vec<Option<i32>>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Validate)]
@@ -97,6 +126,18 @@ pub struct NewBudget {
     pub amount: BigDecimal,
     pub start_date: NaiveDate,
     pub end_date: NaiveDate,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    #[serde(default = "default_rollover")]
+    pub rollover: String,
+    #[serde(skip)]
+    pub rollover_amount: BigDecimal,
+    #[serde(skip)]
+    pub previous_budget_id: Option<i32>,
+    #[serde(default = "default_alert_thresholds")]
+    pub alert_thresholds: Vec<Option<i32>>,
 }
 
 impl NewBudget {
@@ -108,12 +149,179 @@ impl NewBudget {
         validate_start_date_before_end_date(&self.start_date, &self.end_date)?;
 
         // validate category
-        validate_category(self.category.clone())?;
+        validate_category(&self.category)?;
+
+        // validate recurrence and rollover policy
+        validate_recurrence(&self.recurrence, &self.rollover)?;
+
+        // validate alert thresholds
+        validate_alert_thresholds(&self.alert_thresholds)?;
 
         Ok(())
     }
 }
 
+fn default_rollover() -> String {
+    Rollover::None.as_str().to_owned()
+}
+
+fn default_alert_thresholds() -> Vec<Option<i32>> {
+    vec![Some(80), Some(100)]
+}
+
+/// Thresholds are percentages of the budgeted amount, e.g. `[80, 100]`.
+fn validate_alert_thresholds(thresholds: &[Option<i32>]) -> Result<(), ValidationError> {
+    if thresholds.len() > 10 {
+        return Err(ValidationError::new("at most 10 alert thresholds are allowed"));
+    }
+    let mut seen = HashSet::new();
+    for threshold in thresholds {
+        match threshold {
+            Some(threshold) if (1..=1000).contains(threshold) => {
+                if !seen.insert(*threshold) {
+                    return Err(ValidationError::new("alert thresholds must be unique"));
+                }
+            }
+            _ => {
+                return Err(ValidationError::new(
+                    "alert thresholds must be between 1 and 1000",
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_recurrence(
+    recurrence: &Option<String>,
+    rollover: &str,
+) -> Result<(), ValidationError> {
+    if let Some(recurrence) = recurrence {
+        if Recurrence::parse(recurrence).is_none() {
+            return Err(ValidationError::new(
+                "recurrence must be weekly, biweekly, monthly, quarterly or yearly",
+            ));
+        }
+    }
+    if Rollover::parse(rollover).is_none() {
+        return Err(ValidationError::new(
+            "rollover must be none, unspent, overspent or all",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recurrence {
+    Weekly,
+    Biweekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Recurrence {
+    pub fn parse(value: &str) -> Option<Recurrence> {
+        match value {
+            "weekly" => Some(Recurrence::Weekly),
+            "biweekly" => Some(Recurrence::Biweekly),
+            "monthly" => Some(Recurrence::Monthly),
+            "quarterly" => Some(Recurrence::Quarterly),
+            "yearly" => Some(Recurrence::Yearly),
+            _ => None,
+        }
+    }
+
+    /// The period that starts the day after `end_date`.
+    pub fn next_period(&self, end_date: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+        let start = end_date.succ_opt()?;
+        let after = match self {
+            Recurrence::Weekly => start.checked_add_days(Days::new(7))?,
+            Recurrence::Biweekly => start.checked_add_days(Days::new(14))?,
+            Recurrence::Monthly => start.checked_add_months(Months::new(1))?,
+            Recurrence::Quarterly => start.checked_add_months(Months::new(3))?,
+            Recurrence::Yearly => start.checked_add_months(Months::new(12))?,
+        };
+        Some((start, after.pred_opt()?))
+    }
+}
+
+/// What happens to the money left in a recurring budget's period when the
+/// next one is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rollover {
+    None,
+    Unspent,
+    Overspent,
+    All,
+}
+
+impl Rollover {
+    pub fn parse(value: &str) -> Option<Rollover> {
+        match value {
+            "none" => Some(Rollover::None),
+            "unspent" => Some(Rollover::Unspent),
+            "overspent" => Some(Rollover::Overspent),
+            "all" => Some(Rollover::All),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rollover::None => "none",
+            Rollover::Unspent => "unspent",
+            Rollover::Overspent => "overspent",
+            Rollover::All => "all",
+        }
+    }
+
+    /// The amount carried into the next period given what is `remaining`
+    /// (negative when overspent) at the end of this one.
+    pub fn carry(&self, remaining: &BigDecimal) -> BigDecimal {
+        let zero = BigDecimal::from(0);
+        match self {
+            Rollover::None => zero,
+            Rollover::Unspent if remaining > &zero => remaining.clone(),
+            Rollover::Overspent if remaining < &zero => remaining.clone(),
+            Rollover::All => remaining.clone(),
+            _ => zero,
+        }
+    }
+}
+
+/// How far a budget's period has been used up, see
+/// `BudgetRepository::find_status`.
+#[derive(QueryableByName, Serialize)]
+pub struct BudgetStatus {
+    #[diesel(sql_type = Integer)]
+    pub budget_id: i32,
+    #[diesel(sql_type = Text)]
+    pub category: String,
+    #[diesel(sql_type = Date)]
+    pub start_date: NaiveDate,
+    #[diesel(sql_type = Date)]
+    pub end_date: NaiveDate,
+    #[diesel(sql_type = Numeric)]
+    pub budgeted: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub rollover_amount: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub spent: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub remaining: BigDecimal,
+    #[diesel(sql_type = Nullable<Numeric>)]
+    pub percent_used: Option<BigDecimal>,
+    #[diesel(sql_type = Numeric)]
+    pub daily_burn_rate: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub projected_spend: BigDecimal,
+    #[diesel(sql_type = Integer)]
+    pub days_elapsed: i32,
+    #[diesel(sql_type = Integer)]
+    pub days_total: i32,
+}
+
 #[derive(Insertable, Serialize, Deserialize, Validate)]
 #[diesel(table_name = budgets)]
 pub struct UpdateBudget {
@@ -122,6 +330,14 @@ pub struct UpdateBudget {
     pub amount: BigDecimal,
     pub start_date: NaiveDate,
     pub end_date: NaiveDate,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    #[serde(default = "default_rollover")]
+    pub rollover: String,
+    #[serde(default = "default_alert_thresholds")]
+    pub alert_thresholds: Vec<Option<i32>>,
 }
 
 impl UpdateBudget {
@@ -133,22 +349,43 @@ impl UpdateBudget {
         validate_start_date_before_end_date(&self.start_date, &self.end_date)?;
 
         // validate category
-        validate_category(self.category.clone())?;
+        validate_category(&self.category)?;
+
+        // validate recurrence and rollover policy
+        validate_recurrence(&self.recurrence, &self.rollover)?;
+
+        // validate alert thresholds
+        validate_alert_thresholds(&self.alert_thresholds)?;
 
         Ok(())
     }
 }
 
-#[derive(Insertable, Serialize, Deserialize, Validate)]
+#[derive(Insertable, Serialize, Deserialize, Validate, Clone)]
 #[diesel(table_name = transactions)]
 pub struct NewTransaction {
     pub user_id: Option<i32>,
     pub amount: BigDecimal,
+    /// May be left out when a categorization rule or `FALLBACK_CATEGORY`
+    /// should pick the category.
+    #[serde(default)]
     #[validate(length(min = 3, message = "Category should be more than 2 characters"))]
     pub category: String,
     #[validate(length(min = 3, message = "Description should be more than 2 characters"))]
     pub description: Option<String>,
     pub date: NaiveDate,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    /// The bank's id of an imported transaction, used to skip entries that
+    /// were already imported.
+    #[serde(skip_deserializing)]
+    pub external_id: Option<String>,
+    /// Found from the description when the transaction is created.
+    #[serde(skip_deserializing)]
+    pub payee_id: Option<i32>,
+    /// The account the money was paid from.
+    #[serde(default)]
+    pub account_id: Option<i32>,
 }
 
 impl NewTransaction {
@@ -157,7 +394,7 @@ impl NewTransaction {
         validate_positive_amount(&self.amount)?;
 
         // validate category
-        validate_category(self.category.clone())?;
+        validate_category(&self.category)?;
 
         Ok(())
     }
@@ -172,6 +409,10 @@ pub struct UpdateTransaction {
     #[validate(length(min = 3, message = "Description should be more than 2 characters"))]
     pub description: Option<String>,
     pub date: NaiveDate,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub account_id: Option<i32>,
 }
 
 impl UpdateTransaction {
@@ -180,12 +421,133 @@ impl UpdateTransaction {
         validate_positive_amount(&self.amount)?;
 
         // validate category
-        validate_category(self.category.clone())?;
+        validate_category(&self.category)?;
 
         Ok(())
     }
 }
 
+/// Body of `POST /transaction/<id>/merge`: the duplicates of the kept
+/// transaction that are deleted.
+#[derive(Serialize, Deserialize)]
+pub struct MergeTransactions {
+    pub duplicates: Vec<i32>,
+}
+
+/// Most transactions one bulk operation changes.
+pub const MAX_BULK_ROWS: usize = 1000;
+
+/// Largest shift of `shift_date`, in days.
+const MAX_BULK_SHIFT_DAYS: u64 = 3660;
+
+/// A validation error whose message names a limit, which the static code
+/// cannot.
+fn limit_error(code: &'static str, message: String) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    error
+}
+
+/// What `POST /transactions/bulk` does to each transaction, e.g.
+/// `{"type": "set_category", "category": "Rent"}`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BulkOperation {
+    SetCategory {
+        category: String,
+        #[serde(skip)]
+        category_id: Option<i32>,
+    },
+    /// Appends `text` to the description, after a space.
+    AppendDescription { text: String },
+    /// Moves the date by `days`, backwards when negative.
+    ShiftDate { days: i64 },
+    Delete,
+}
+
+/// Body of `POST /transactions/bulk`: the transactions, given by `ids` or
+/// by a `filter` query as taken by `GET /transactions?q=`, and the operation.
+#[derive(Deserialize)]
+pub struct BulkRequest {
+    #[serde(default)]
+    pub ids: Vec<i32>,
+    pub filter: Option<String>,
+    pub operation: BulkOperation,
+}
+
+impl BulkRequest {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match (self.ids.is_empty(), &self.filter) {
+            (true, None) => return Err(ValidationError::new("give either ids or a filter")),
+            (false, Some(_)) => {
+                return Err(ValidationError::new("ids and filter cannot be combined"))
+            }
+            _ => {}
+        }
+        if self.ids.len() > MAX_BULK_ROWS {
+            return Err(limit_error(
+                "too many ids",
+                format!("at most {} transactions can be changed at once", MAX_BULK_ROWS),
+            ));
+        }
+        match &self.operation {
+            BulkOperation::SetCategory { category, .. } => validate_category(category),
+            BulkOperation::AppendDescription { text } if text.trim().is_empty() => {
+                Err(ValidationError::new("text must not be empty"))
+            }
+            BulkOperation::ShiftDate { days }
+                if *days == 0 || days.unsigned_abs() > MAX_BULK_SHIFT_DAYS =>
+            {
+                Err(limit_error(
+                    "invalid days",
+                    format!(
+                        "days must be between -{0} and {0}, and not 0",
+                        MAX_BULK_SHIFT_DAYS
+                    ),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What became of one transaction of a bulk operation.
+#[derive(Serialize)]
+pub struct BulkRowReport {
+    pub id: i32,
+    /// `updated`, `deleted` or `failed`.
+    pub status: &'static str,
+    /// The transaction as it is after the operation.
+    pub transaction: Option<Transaction>,
+    pub error: Option<String>,
+}
+
+impl BulkRowReport {
+    pub fn failed(id: i32, error: &str) -> BulkRowReport {
+        BulkRowReport {
+            id,
+            status: "failed",
+            transaction: None,
+            error: Some(error.to_owned()),
+        }
+    }
+}
+
+/// The outcome of a bulk operation. Nothing is changed, and `committed` is
+/// false, unless the operation succeeded on every transaction.
+#[derive(Serialize)]
+pub struct BulkReport {
+    pub committed: bool,
+    pub matched: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub failed: usize,
+    pub rows: Vec<BulkRowReport>,
+    /// Attachments of the deleted transactions, whose files may have to go.
+    #[serde(skip)]
+    pub attachments: Vec<Attachment>,
+}
+
 #[derive(Queryable, Associations, Serialize, Deserialize)]
 #[diesel(belongs_to(User))]
 pub struct Transaction {
@@ -195,6 +557,393 @@ pub struct Transaction {
     pub category: String,
     pub description: Option<String>,
     pub date: NaiveDate,
+    pub category_id: Option<i32>,
+    pub external_id: Option<String>,
+    pub payee_id: Option<i32>,
+    pub account_id: Option<i32>,
+}
+
+/// The share of a split transaction booked on one category.
+#[derive(Queryable, Associations, Serialize, Deserialize, Clone)]
+#[diesel(table_name = transaction_splits)]
+#[diesel(belongs_to(Transaction))]
+pub struct TransactionSplit {
+    pub id: i32,
+    pub transaction_id: i32,
+    pub user_id: i32,
+    pub category: String,
+    pub category_id: i32,
+    pub amount: BigDecimal,
+    pub memo: Option<String>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = transaction_splits)]
+pub struct NewTransactionSplit {
+    #[serde(skip_deserializing)]
+    pub transaction_id: i32,
+    #[serde(skip_deserializing)]
+    pub user_id: i32,
+    pub category: String,
+    #[serde(skip_deserializing)]
+    pub category_id: i32,
+    pub amount: BigDecimal,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// Body of `PUT /transaction/<id>/splits`: the lines that replace the
+/// transaction's splits. An empty list makes it a plain transaction again.
+#[derive(Serialize, Deserialize)]
+pub struct TransactionSplits {
+    pub splits: Vec<NewTransactionSplit>,
+}
+
+impl TransactionSplits {
+    /// The lines of a split must add up to the transaction's `amount`.
+    pub fn validate(&self, amount: &BigDecimal) -> Result<(), ValidationError> {
+        if self.splits.is_empty() {
+            return Ok(());
+        }
+        if self.splits.len() < 2 {
+            return Err(ValidationError::new(
+                "a split needs at least two lines, change the category instead",
+            ));
+        }
+        for split in &self.splits {
+            validate_positive_amount(&split.amount)?;
+            validate_category(&split.category)?;
+        }
+        let total: BigDecimal = self.splits.iter().map(|split| &split.amount).sum();
+        if total != *amount {
+            return Err(ValidationError::new(
+                "the split amounts must add up to the transaction amount",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A file such as a receipt attached to a transaction. The content is stored
+/// once per `sha256`, however many attachments share it.
+#[derive(Queryable, Associations, Serialize, Deserialize, Clone)]
+#[diesel(table_name = attachments)]
+#[diesel(belongs_to(Transaction))]
+pub struct Attachment {
+    pub id: i32,
+    pub user_id: i32,
+    pub transaction_id: i32,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: i64,
+    pub sha256: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = attachments)]
+pub struct NewAttachment {
+    pub user_id: i32,
+    pub transaction_id: i32,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: i64,
+    pub sha256: String,
+}
+
+/// A category suggested for a transaction, with the classifier's confidence
+/// between 0 and 1.
+#[derive(Serialize, Deserialize)]
+pub struct CategorySuggestion {
+    pub category_id: i32,
+    pub category: String,
+    pub confidence: f64,
+}
+
+#[derive(Queryable, Associations, Serialize, Deserialize)]
+#[diesel(table_name = categories)]
+#[diesel(belongs_to(User))]
+pub struct Category {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[diesel(table_name = categories)]
+pub struct NewCategory {
+    #[serde(default)]
+    pub user_id: i32,
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+}
+
+impl NewCategory {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_category_name(&self.name)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateCategory {
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+}
+
+impl UpdateCategory {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_category_name(&self.name)
+    }
+}
+
+/// Body of `POST /category/<id>/merge`: the category the rows move to.
+#[derive(Serialize, Deserialize)]
+pub struct MergeCategory {
+    pub into: i32,
+}
+
+/// A category with the spending booked on it over a date range. `total` only
+/// counts the category itself, `rollup_total` adds all of its descendants.
+#[derive(Serialize)]
+pub struct CategoryNode {
+    pub id: i32,
+    pub name: String,
+    pub path: String,
+    pub parent_id: Option<i32>,
+    pub total: BigDecimal,
+    pub rollup_total: BigDecimal,
+    pub children: Vec<CategoryNode>,
+}
+
+impl CategoryNode {
+    /// Arranges a user's categories into a forest, rolling `totals` (keyed by
+    /// category id) up from the leaves.
+    pub fn build_tree(
+        categories: Vec<Category>,
+        totals: &HashMap<i32, BigDecimal>,
+    ) -> Vec<CategoryNode> {
+        let ids: HashSet<i32> = categories.iter().map(|category| category.id).collect();
+        let mut children: HashMap<Option<i32>, Vec<Category>> = HashMap::new();
+        for category in categories {
+            let parent_id = category.parent_id.filter(|id| ids.contains(id));
+            children.entry(parent_id).or_default().push(category);
+        }
+        Self::build_level(None, "", &mut children, totals)
+    }
+
+    fn build_level(
+        parent_id: Option<i32>,
+        parent_path: &str,
+        children: &mut HashMap<Option<i32>, Vec<Category>>,
+        totals: &HashMap<i32, BigDecimal>,
+    ) -> Vec<CategoryNode> {
+        let level = children.remove(&parent_id).unwrap_or_default();
+        level
+            .into_iter()
+            .map(|category| {
+                let path = if parent_path.is_empty() {
+                    category.name.clone()
+                } else {
+                    format!("{} {} {}", parent_path, CATEGORY_PATH_SEPARATOR, category.name)
+                };
+                let nodes = Self::build_level(Some(category.id), &path, children, totals);
+                let total = totals
+                    .get(&category.id)
+                    .cloned()
+                    .unwrap_or_else(|| BigDecimal::from(0));
+                let rollup_total = nodes
+                    .iter()
+                    .fold(total.clone(), |sum, node| sum + &node.rollup_total);
+                CategoryNode {
+                    id: category.id,
+                    name: category.name,
+                    path,
+                    parent_id: category.parent_id,
+                    total,
+                    rollup_total,
+                    children: nodes,
+                }
+            })
+            .collect()
+    }
+}
+
+pub const STANDARD_MODE: &str = "standard";
+pub const ENVELOPE_MODE: &str = "envelope";
+
+/// Parses a `YYYY-MM` month into its first day.
+pub fn parse_month(value: &str) -> Result<NaiveDate, ValidationError> {
+    NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d")
+        .map_err(|_| ValidationError::new("month must look like 2024-01"))
+}
+
+/// The last day of the month starting on `month`.
+pub fn month_end(month: NaiveDate) -> NaiveDate {
+    month
+        .checked_add_months(Months::new(1))
+        .and_then(|next| next.pred_opt())
+        .unwrap_or(month)
+}
+
+/// Body of `PUT /envelopes/mode`.
+#[derive(Serialize, Deserialize)]
+pub struct BudgetingMode {
+    pub mode: String,
+    /// First month (`YYYY-MM`) counted by envelope budgeting, defaults to
+    /// the current month.
+    #[serde(default)]
+    pub start_month: Option<String>,
+}
+
+impl BudgetingMode {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.mode != STANDARD_MODE && self.mode != ENVELOPE_MODE {
+            return Err(ValidationError::new("mode must be standard or envelope"));
+        }
+        if let Some(month) = &self.start_month {
+            parse_month(month)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Queryable, Associations, Serialize, Deserialize)]
+#[diesel(table_name = envelope_allocations)]
+#[diesel(belongs_to(User))]
+#[diesel(belongs_to(Category))]
+pub struct Allocation {
+    pub id: i32,
+    pub user_id: i32,
+    pub category_id: i32,
+    pub month: NaiveDate,
+    pub amount: BigDecimal,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = envelope_allocations)]
+pub struct NewAllocation {
+    pub user_id: i32,
+    pub category_id: i32,
+    pub month: NaiveDate,
+    pub amount: BigDecimal,
+    pub note: Option<String>,
+}
+
+/// Raised when a transaction pushes a budget's spending past one of its
+/// alert thresholds. Each threshold fires at most once per budget period.
+#[derive(Queryable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(User))]
+#[diesel(belongs_to(Budget))]
+pub struct Alert {
+    pub id: i32,
+    pub user_id: i32,
+    pub budget_id: i32,
+    pub transaction_id: Option<i32>,
+    pub category: String,
+    pub threshold: i32,
+    pub percent_used: BigDecimal,
+    pub created_at: NaiveDateTime,
+    pub read_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = alerts)]
+pub struct NewAlert {
+    pub user_id: i32,
+    pub budget_id: i32,
+    pub transaction_id: Option<i32>,
+    pub category: String,
+    pub threshold: i32,
+    pub percent_used: BigDecimal,
+}
+
+/// Body of `POST /envelopes/assign`. A negative amount takes money back out
+/// of the envelope, at most what it still holds.
+#[derive(Serialize, Deserialize)]
+pub struct AssignMoney {
+    pub category_id: i32,
+    pub month: String,
+    pub amount: BigDecimal,
+}
+
+impl AssignMoney {
+    pub fn validate(&self) -> Result<NaiveDate, ValidationError> {
+        if self.amount == BigDecimal::from(0) {
+            return Err(ValidationError::new("amount cannot be zero"));
+        }
+        parse_month(&self.month)
+    }
+}
+
+/// Body of `POST /envelopes/move`.
+#[derive(Serialize, Deserialize)]
+pub struct MoveMoney {
+    pub from_category_id: i32,
+    pub to_category_id: i32,
+    pub month: String,
+    pub amount: BigDecimal,
+}
+
+impl MoveMoney {
+    pub fn validate(&self) -> Result<NaiveDate, ValidationError> {
+        validate_positive_amount(&self.amount)?;
+        if self.from_category_id == self.to_category_id {
+            return Err(ValidationError::new("cannot move money into the same envelope"));
+        }
+        parse_month(&self.month)
+    }
+}
+
+/// One category envelope for a month: what was assigned to it this month,
+/// what was spent from it this month, and what is left in it overall.
+#[derive(QueryableByName, Serialize)]
+pub struct Envelope {
+    #[diesel(sql_type = Integer)]
+    pub category_id: i32,
+    #[diesel(sql_type = Text)]
+    pub category: String,
+    #[diesel(sql_type = Numeric)]
+    pub assigned: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub activity: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub available: BigDecimal,
+}
+
+/// Income received so far that has not been assigned to an envelope yet.
+#[derive(QueryableByName, Serialize)]
+pub struct UnassignedMoney {
+    #[diesel(sql_type = Date)]
+    pub month: NaiveDate,
+    #[diesel(sql_type = Numeric)]
+    pub income: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub assigned: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub to_be_assigned: BigDecimal,
+}
+
+/// Optional `from`/`to` query parameters of report endpoints.
+#[derive(FromForm, Default)]
+pub struct DateRangeQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl DateRangeQuery {
+    pub fn parse(&self) -> Result<(Option<NaiveDate>, Option<NaiveDate>), ValidationError> {
+        let from = parse_optional(&self.from, "invalid from date")?;
+        let to = parse_optional(&self.to, "invalid to date")?;
+        if let (Some(from), Some(to)) = (&from, &to) {
+            validate_start_date_before_end_date(from, to)?;
+        }
+        Ok((from, to))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ValidatorValidate)]
@@ -206,7 +955,7 @@ pub struct LoginCredentials {
     pub password_hash: String,
 }
 
-#[derive(Insertable, Serialize, Deserialize, Validate)]
+#[derive(Insertable, Serialize, Deserialize, Validate, Clone)]
 #[diesel(table_name= income)]
 pub struct NewIncome {
     pub user_id: Option<i32>,
@@ -214,6 +963,11 @@ pub struct NewIncome {
     #[validate(length(min = 3, message = "Description should be more than 2 characters"))]
     pub source: String,
     pub date: NaiveDate,
+    #[serde(skip_deserializing)]
+    pub external_id: Option<String>,
+    /// The account the money was paid into.
+    #[serde(default)]
+    pub account_id: Option<i32>,
 }
 
 impl NewIncome {
@@ -233,6 +987,8 @@ pub struct Income {
     pub amount: BigDecimal,
     pub source: String,
     pub date: NaiveDate,
+    pub external_id: Option<String>,
+    pub account_id: Option<i32>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Validate)]
@@ -265,3 +1021,1230 @@ pub struct Goals {
     pub deadline: NaiveDate,
     pub saving: Option<BigDecimal>,
 }
+
+pub const TEMPLATE_TRANSACTION: &str = "transaction";
+pub const TEMPLATE_INCOME: &str = "income";
+
+pub const OCCURRENCE_PENDING: &str = "pending";
+pub const OCCURRENCE_SKIPPED: &str = "skipped";
+pub const OCCURRENCE_POSTED: &str = "posted";
+
+/// A transaction or income that is posted on every date of its `rrule`
+/// schedule, see `utils::schedule`. For income templates `category` holds
+/// the source.
+#[derive(Queryable, Associations, Serialize, Deserialize)]
+#[diesel(table_name = recurring_templates)]
+#[diesel(belongs_to(User))]
+pub struct RecurringTemplate {
+    pub id: i32,
+    pub user_id: i32,
+    pub kind: String,
+    pub amount: BigDecimal,
+    pub category: String,
+    pub category_id: Option<i32>,
+    pub description: Option<String>,
+    pub rrule: String,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub created_at: NaiveDateTime,
+    /// The account the postings are booked on.
+    pub account_id: Option<i32>,
+}
+
+impl RecurringTemplate {
+    pub fn schedule(&self) -> Option<Schedule> {
+        Schedule::parse(&self.rrule).ok()
+    }
+
+    /// The template's dates between `from` and `to`, both inclusive.
+    pub fn occurrences(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let to = self.end_date.map_or(to, |end_date| end_date.min(to));
+        self.schedule()
+            .map(|schedule| schedule.occurrences(self.start_date, from, to))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[diesel(table_name = recurring_templates)]
+pub struct NewRecurringTemplate {
+    #[serde(default)]
+    pub user_id: i32,
+    pub kind: String,
+    pub amount: BigDecimal,
+    pub category: String,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub rrule: String,
+    pub start_date: NaiveDate,
+    #[serde(default)]
+    pub end_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub account_id: Option<i32>,
+}
+
+impl NewRecurringTemplate {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.kind != TEMPLATE_TRANSACTION && self.kind != TEMPLATE_INCOME {
+            return Err(ValidationError::new("kind must be transaction or income"));
+        }
+
+        validate_positive_amount(&self.amount)?;
+
+        validate_category(&self.category)?;
+
+        Schedule::parse(&self.rrule).map_err(ValidationError::new)?;
+
+        if let Some(end_date) = &self.end_date {
+            validate_start_date_before_end_date(&self.start_date, end_date)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single date of a template that was posted, skipped or edited before
+/// being posted. Dates without a row have not been posted yet.
+#[derive(Queryable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(RecurringTemplate, foreign_key = template_id))]
+pub struct RecurringOccurrence {
+    pub id: i32,
+    pub template_id: i32,
+    pub occurrence_date: NaiveDate,
+    pub status: String,
+    pub amount: Option<BigDecimal>,
+    pub description: Option<String>,
+    pub date: Option<NaiveDate>,
+    pub transaction_id: Option<i32>,
+    pub income_id: Option<i32>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = recurring_occurrences)]
+pub struct NewRecurringOccurrence {
+    pub template_id: i32,
+    pub occurrence_date: NaiveDate,
+    pub status: String,
+    pub amount: Option<BigDecimal>,
+    pub description: Option<String>,
+    pub date: Option<NaiveDate>,
+}
+
+/// Body of `PUT /recurring/<id>/occurrence/<date>`. Every field left out
+/// keeps the template's value.
+#[derive(Serialize, Deserialize)]
+pub struct EditOccurrence {
+    #[serde(default)]
+    pub amount: Option<BigDecimal>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+}
+
+impl EditOccurrence {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if let Some(amount) = &self.amount {
+            validate_positive_amount(amount)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An upcoming date of a template with any edits applied.
+#[derive(Serialize)]
+pub struct UpcomingOccurrence {
+    pub template_id: i32,
+    pub kind: String,
+    pub occurrence_date: NaiveDate,
+    pub date: NaiveDate,
+    pub amount: BigDecimal,
+    pub category: String,
+    pub description: Option<String>,
+    pub status: String,
+}
+
+pub const NEGATIVE_DEBIT: &str = "negative_debit";
+pub const POSITIVE_DEBIT: &str = "positive_debit";
+
+/// How the columns of a bank's CSV export map onto transactions. Columns are
+/// counted from 0. Either `amount_column` or `debit_column` (optionally with
+/// `credit_column`) must be set; with a single amount column the
+/// `sign_convention` tells which sign marks money going out.
+#[derive(Queryable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(User))]
+pub struct ImportProfile {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub delimiter: String,
+    pub has_header: bool,
+    pub date_column: i32,
+    pub date_format: String,
+    pub description_column: Option<i32>,
+    pub category_column: Option<i32>,
+    pub amount_column: Option<i32>,
+    pub debit_column: Option<i32>,
+    pub credit_column: Option<i32>,
+    pub decimal_separator: String,
+    pub sign_convention: String,
+    pub default_category: String,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[diesel(table_name = import_profiles)]
+pub struct NewImportProfile {
+    #[serde(default)]
+    pub user_id: i32,
+    pub name: String,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: String,
+    #[serde(default = "default_has_header")]
+    pub has_header: bool,
+    pub date_column: i32,
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    #[serde(default)]
+    pub description_column: Option<i32>,
+    #[serde(default)]
+    pub category_column: Option<i32>,
+    #[serde(default)]
+    pub amount_column: Option<i32>,
+    #[serde(default)]
+    pub debit_column: Option<i32>,
+    #[serde(default)]
+    pub credit_column: Option<i32>,
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: String,
+    #[serde(default = "default_sign_convention")]
+    pub sign_convention: String,
+    #[serde(default = "default_import_category")]
+    pub default_category: String,
+}
+
+fn default_delimiter() -> String {
+    ",".to_owned()
+}
+
+fn default_has_header() -> bool {
+    true
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_owned()
+}
+
+fn default_decimal_separator() -> String {
+    ".".to_owned()
+}
+
+fn default_sign_convention() -> String {
+    NEGATIVE_DEBIT.to_owned()
+}
+
+fn default_import_category() -> String {
+    FALLBACK_CATEGORY.to_owned()
+}
+
+impl NewImportProfile {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.name.trim().is_empty() {
+            return Err(ValidationError::new("name must not be empty"));
+        }
+
+        if !self.delimiter.is_ascii() || self.delimiter.len() != 1 {
+            return Err(ValidationError::new("delimiter must be a single character"));
+        }
+
+        if self.decimal_separator != "." && self.decimal_separator != "," {
+            return Err(ValidationError::new("decimal_separator must be . or ,"));
+        }
+
+        if self.sign_convention != NEGATIVE_DEBIT && self.sign_convention != POSITIVE_DEBIT {
+            return Err(ValidationError::new(
+                "sign_convention must be negative_debit or positive_debit",
+            ));
+        }
+
+        if self.amount_column.is_none() && self.debit_column.is_none() {
+            return Err(ValidationError::new(
+                "either amount_column or debit_column is required",
+            ));
+        }
+
+        let columns = [
+            Some(self.date_column),
+            self.description_column,
+            self.category_column,
+            self.amount_column,
+            self.debit_column,
+            self.credit_column,
+        ];
+        if columns.iter().flatten().any(|column| *column < 0) {
+            return Err(ValidationError::new("columns are counted from 0"));
+        }
+
+        validate_category(&self.default_category)?;
+
+        Ok(())
+    }
+}
+
+pub const MATCH_FIRST: &str = "first";
+pub const MATCH_ALL: &str = "all";
+
+/// Assigns a category and/or a cleaned-up description to the transactions
+/// that meet all of its conditions. `new_description` may refer to groups of
+/// `description_regex` as `$1` or `${name}`.
+#[derive(Queryable, Associations, Serialize, Deserialize, Clone)]
+#[diesel(belongs_to(User))]
+pub struct CategoryRule {
+    pub id: i32,
+    pub user_id: i32,
+    pub position: i32,
+    pub description_contains: Option<String>,
+    pub description_regex: Option<String>,
+    pub min_amount: Option<BigDecimal>,
+    pub max_amount: Option<BigDecimal>,
+    pub day_of_month: Option<i32>,
+    pub category: Option<String>,
+    pub category_id: Option<i32>,
+    pub new_description: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[diesel(table_name = category_rules)]
+pub struct NewCategoryRule {
+    #[serde(default)]
+    pub user_id: i32,
+    #[serde(skip_deserializing)]
+    pub position: i32,
+    #[serde(default)]
+    pub description_contains: Option<String>,
+    #[serde(default)]
+    pub description_regex: Option<String>,
+    #[serde(default)]
+    pub min_amount: Option<BigDecimal>,
+    #[serde(default)]
+    pub max_amount: Option<BigDecimal>,
+    #[serde(default)]
+    pub day_of_month: Option<i32>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub new_description: Option<String>,
+}
+
+impl NewCategoryRule {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.description_contains.is_none()
+            && self.description_regex.is_none()
+            && self.min_amount.is_none()
+            && self.max_amount.is_none()
+            && self.day_of_month.is_none()
+        {
+            return Err(ValidationError::new("a rule needs at least one condition"));
+        }
+        if self.category.is_none() && self.new_description.is_none() {
+            return Err(ValidationError::new(
+                "a rule must set a category or a new_description",
+            ));
+        }
+
+        if self
+            .description_contains
+            .as_ref()
+            .is_some_and(|text| text.trim().is_empty())
+        {
+            return Err(ValidationError::new("description_contains must not be empty"));
+        }
+        if let Some(pattern) = &self.description_regex {
+            if regex::Regex::new(pattern).is_err() {
+                return Err(ValidationError::new("description_regex is not a valid regex"));
+            }
+        }
+        if let (Some(min_amount), Some(max_amount)) = (&self.min_amount, &self.max_amount) {
+            if min_amount > max_amount {
+                return Err(ValidationError::new(
+                    "min_amount must not be greater than max_amount",
+                ));
+            }
+        }
+        if self
+            .day_of_month
+            .is_some_and(|day| !(1..=31).contains(&day))
+        {
+            return Err(ValidationError::new("day_of_month must be between 1 and 31"));
+        }
+
+        if let Some(category) = &self.category {
+            validate_category(category)?;
+        }
+        if self
+            .new_description
+            .as_ref()
+            .is_some_and(|description| description.trim().chars().count() < 3)
+        {
+            return Err(ValidationError::new(
+                "Description should be more than 2 characters",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Body of `PUT /rules/mode`: whether only the first matching rule applies
+/// or all of them, in order.
+#[derive(Serialize, Deserialize)]
+pub struct RuleMatchMode {
+    pub mode: String,
+}
+
+impl RuleMatchMode {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.mode != MATCH_FIRST && self.mode != MATCH_ALL {
+            return Err(ValidationError::new("mode must be first or all"));
+        }
+        Ok(())
+    }
+}
+
+/// Body of `PUT /rules/order`: the ids of all of the user's rules in the
+/// order they should run.
+#[derive(Serialize, Deserialize)]
+pub struct RuleOrder {
+    pub ids: Vec<i32>,
+}
+
+/// What running the rules again would change on an existing transaction.
+#[derive(Serialize)]
+pub struct RuleChange {
+    pub transaction_id: i32,
+    pub rule_ids: Vec<i32>,
+    pub category: String,
+    pub new_category: String,
+    #[serde(skip)]
+    pub new_category_id: Option<i32>,
+    pub description: Option<String>,
+    pub new_description: Option<String>,
+}
+
+#[derive(Queryable, Associations, Serialize, Deserialize, Clone)]
+#[diesel(table_name = payees)]
+#[diesel(belongs_to(User))]
+pub struct Payee {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    /// Category of new transactions of this payee that no rule categorizes.
+    pub category: Option<String>,
+    pub category_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Body of `POST /payee` and `PUT /payee/<id>`.
+#[derive(Insertable, Serialize, Deserialize)]
+#[diesel(table_name = payees)]
+pub struct NewPayee {
+    #[serde(default)]
+    pub user_id: i32,
+    pub name: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+}
+
+impl NewPayee {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.name.trim().chars().count() < 2 {
+            return Err(ValidationError::new(
+                "Name should be at least 2 characters",
+            ));
+        }
+        if let Some(category) = &self.category {
+            validate_category(category)?;
+        }
+        Ok(())
+    }
+}
+
+/// A normalized description that belongs to a payee.
+#[derive(Queryable, Associations, Serialize, Deserialize, Clone)]
+#[diesel(table_name = payee_aliases)]
+#[diesel(belongs_to(Payee))]
+pub struct PayeeAlias {
+    pub id: i32,
+    pub user_id: i32,
+    pub payee_id: i32,
+    pub alias: String,
+}
+
+/// Body of `POST /payee/<id>/alias`, e.g. `{"alias": "AMZN Mktp"}`.
+#[derive(Serialize, Deserialize)]
+pub struct NewPayeeAlias {
+    pub alias: String,
+}
+
+/// Spending at a payee in one month, e.g. `2024-01`.
+#[derive(Serialize)]
+pub struct MonthlySpending {
+    pub month: String,
+    pub count: i64,
+    pub total: BigDecimal,
+}
+
+#[derive(Serialize)]
+pub struct PayeeSummary {
+    pub payee: Payee,
+    pub aliases: Vec<PayeeAlias>,
+    pub count: i64,
+    pub total: BigDecimal,
+    pub average: BigDecimal,
+    pub first_date: Option<NaiveDate>,
+    pub last_date: Option<NaiveDate>,
+    pub months: Vec<MonthlySpending>,
+}
+
+#[derive(Queryable, Associations, Serialize, Deserialize, Clone)]
+#[diesel(table_name = tags)]
+#[diesel(belongs_to(User))]
+pub struct Tag {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Body of `POST /tag` and `PUT /tag/<id>`.
+#[derive(Insertable, Serialize, Deserialize)]
+#[diesel(table_name = tags)]
+pub struct NewTag {
+    #[serde(default)]
+    pub user_id: i32,
+    pub name: String,
+}
+
+impl NewTag {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_tag(&self.name)
+    }
+}
+
+/// Tags are compared trimmed and lowercased.
+pub fn normalize_tag(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+fn validate_tag(name: &str) -> Result<(), ValidationError> {
+    if !(2..=40).contains(&name.chars().count()) {
+        return Err(ValidationError::new(
+            "Tag should be between 2 and 40 characters",
+        ));
+    }
+    if name.contains(',') {
+        return Err(ValidationError::new("Tag cannot contain ','"));
+    }
+    Ok(())
+}
+
+/// Body of `PUT /transaction/<id>/tags` and its income and goal
+/// counterparts: the complete set of tags, created as needed.
+#[derive(Serialize, Deserialize)]
+pub struct TagNames {
+    pub tags: Vec<String>,
+}
+
+impl TagNames {
+    pub fn normalize(&mut self) -> Result<(), ValidationError> {
+        for tag in &mut self.tags {
+            *tag = normalize_tag(tag);
+            validate_tag(tag)?;
+        }
+        self.tags.sort();
+        self.tags.dedup();
+        Ok(())
+    }
+}
+
+/// What a tag adds up to in `TagRepository::report`.
+#[derive(QueryableByName)]
+pub struct TagTotals {
+    #[diesel(sql_type = Integer)]
+    pub tag_id: i32,
+    #[diesel(sql_type = Text)]
+    pub tag: String,
+    #[diesel(sql_type = BigInt)]
+    pub transactions: i64,
+    #[diesel(sql_type = Numeric)]
+    pub spent: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub income: BigDecimal,
+}
+
+/// Spending of a tag on one category, split lines counted separately.
+#[derive(QueryableByName, Serialize)]
+pub struct TagCategorySpending {
+    #[diesel(sql_type = Integer)]
+    #[serde(skip)]
+    pub tag_id: i32,
+    #[diesel(sql_type = Nullable<Integer>)]
+    pub category_id: Option<i32>,
+    #[diesel(sql_type = Text)]
+    pub category: String,
+    #[diesel(sql_type = Numeric)]
+    pub spent: BigDecimal,
+}
+
+/// One row of `GET /tags/report`.
+#[derive(Serialize)]
+pub struct TagReport {
+    pub tag_id: i32,
+    pub tag: String,
+    pub transactions: i64,
+    pub spent: BigDecimal,
+    pub income: BigDecimal,
+    pub net: BigDecimal,
+    pub categories: Vec<TagCategorySpending>,
+}
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    Date,
+    Amount,
+    Category,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone)]
+pub enum CursorValue {
+    Date(NaiveDate),
+    Amount(BigDecimal),
+    Text(String),
+}
+
+/// Position of the last row of a page, encoded as `<id>:<sort value>`.
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    pub id: i32,
+    pub value: CursorValue,
+}
+
+impl Cursor {
+    fn parse(raw: &str, sort: SortField) -> Result<Cursor, ValidationError> {
+        let invalid = || ValidationError::new("invalid cursor");
+        let (id, value) = raw.split_once(':').ok_or_else(invalid)?;
+        let id = id.parse::<i32>().map_err(|_| invalid())?;
+        let value = match sort {
+            SortField::Date => CursorValue::Date(value.parse().map_err(|_| invalid())?),
+            SortField::Amount => CursorValue::Amount(value.parse().map_err(|_| invalid())?),
+            SortField::Category => CursorValue::Text(value.to_string()),
+        };
+        Ok(Cursor { id, value })
+    }
+
+    pub fn encode(&self) -> String {
+        match &self.value {
+            CursorValue::Date(date) => format!("{}:{}", self.id, date),
+            CursorValue::Amount(amount) => format!("{}:{}", self.id, amount),
+            CursorValue::Text(text) => format!("{}:{}", self.id, text),
+        }
+    }
+
+    pub fn date(&self) -> Option<(NaiveDate, i32)> {
+        match &self.value {
+            CursorValue::Date(date) => Some((*date, self.id)),
+            _ => None,
+        }
+    }
+
+    pub fn amount(&self) -> Option<(BigDecimal, i32)> {
+        match &self.value {
+            CursorValue::Amount(amount) => Some((amount.clone(), self.id)),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self) -> Option<(String, i32)> {
+        match &self.value {
+            CursorValue::Text(text) => Some((text.clone(), self.id)),
+            _ => None,
+        }
+    }
+}
+
+/// Raw query string of the list endpoints, e.g.
+/// `GET /transactions?sort=amount&direction=desc&from=2024-01-01&limit=50`.
+#[derive(FromForm, Default)]
+pub struct ListQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    pub sort: Option<String>,
+    pub direction: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub min_amount: Option<String>,
+    pub max_amount: Option<String>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+}
+
+pub struct ListFilter {
+    pub limit: i64,
+    pub sort: SortField,
+    pub direction: SortDirection,
+    pub cursor: Option<Cursor>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub min_amount: Option<BigDecimal>,
+    pub max_amount: Option<BigDecimal>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl ListQuery {
+    pub fn parse(&self) -> Result<ListFilter, ValidationError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(ValidationError::new("limit must be between 1 and 500"));
+        }
+
+        let sort = match self.sort.as_deref() {
+            None | Some("date") => SortField::Date,
+            Some("amount") => SortField::Amount,
+            Some("category") => SortField::Category,
+            Some(_) => return Err(ValidationError::new("sort must be date, amount or category")),
+        };
+
+        let direction = match self.direction.as_deref() {
+            None | Some("desc") => SortDirection::Desc,
+            Some("asc") => SortDirection::Asc,
+            Some(_) => return Err(ValidationError::new("direction must be asc or desc")),
+        };
+
+        let cursor = match &self.cursor {
+            Some(raw) => Some(Cursor::parse(raw, sort)?),
+            None => None,
+        };
+
+        let from = parse_optional(&self.from, "invalid from date")?;
+        let to = parse_optional(&self.to, "invalid to date")?;
+        if let (Some(from), Some(to)) = (&from, &to) {
+            validate_start_date_before_end_date(from, to)?;
+        }
+
+        Ok(ListFilter {
+            limit,
+            sort,
+            direction,
+            cursor,
+            from,
+            to,
+            min_amount: parse_optional(&self.min_amount, "invalid min_amount")?,
+            max_amount: parse_optional(&self.max_amount, "invalid max_amount")?,
+            category: self.category.clone(),
+            tag: self.tag.as_deref().map(normalize_tag),
+        })
+    }
+}
+
+fn parse_optional<T: std::str::FromStr>(
+    value: &Option<String>,
+    code: &'static str,
+) -> Result<Option<T>, ValidationError> {
+    match value {
+        Some(raw) => raw
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| ValidationError::new(code)),
+        None => Ok(None),
+    }
+}
+
+const DEFAULT_SEARCH_RESULTS: i64 = 50;
+const MAX_SEARCH_RESULTS: i64 = 200;
+
+/// Query string of `GET /search`, e.g.
+/// `GET /search?q=whole foods&kind=transactions&from=2024-01-01`.
+#[derive(FromForm, Default)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+    pub kind: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub min_amount: Option<String>,
+    pub max_amount: Option<String>,
+    pub limit: Option<i64>,
+}
+
+pub struct SearchFilter {
+    /// The text to look for as a `tsquery`.
+    pub query: String,
+    pub transactions: bool,
+    pub income: bool,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub min_amount: Option<BigDecimal>,
+    pub max_amount: Option<BigDecimal>,
+    pub limit: i64,
+}
+
+impl SearchQuery {
+    pub fn parse(&self) -> Result<SearchFilter, ValidationError> {
+        let Some(query) = self.q.as_deref().and_then(prefix_query) else {
+            return Err(ValidationError::new("q must contain a word to search for"));
+        };
+
+        let (transactions, income) = match self.kind.as_deref() {
+            None => (true, true),
+            Some("transactions") => (true, false),
+            Some("income") => (false, true),
+            Some(_) => return Err(ValidationError::new("kind must be transactions or income")),
+        };
+
+        let limit = self.limit.unwrap_or(DEFAULT_SEARCH_RESULTS);
+        if !(1..=MAX_SEARCH_RESULTS).contains(&limit) {
+            return Err(ValidationError::new("limit must be between 1 and 200"));
+        }
+
+        let from = parse_optional(&self.from, "invalid from date")?;
+        let to = parse_optional(&self.to, "invalid to date")?;
+        if let (Some(from), Some(to)) = (&from, &to) {
+            validate_start_date_before_end_date(from, to)?;
+        }
+
+        Ok(SearchFilter {
+            query,
+            transactions,
+            income,
+            from,
+            to,
+            min_amount: parse_optional(&self.min_amount, "invalid min_amount")?,
+            max_amount: parse_optional(&self.max_amount, "invalid max_amount")?,
+            limit,
+        })
+    }
+}
+
+/// A transaction or income matching a search. Incomes have no `category`
+/// and their `source` as `description`. `snippet` is the matched text as
+/// escaped HTML with the matches wrapped in `<mark>` tags.
+#[derive(QueryableByName, Serialize)]
+pub struct SearchResult {
+    #[diesel(sql_type = Text)]
+    pub kind: String,
+    #[diesel(sql_type = Integer)]
+    pub id: i32,
+    #[diesel(sql_type = Date)]
+    pub date: NaiveDate,
+    #[diesel(sql_type = Numeric)]
+    pub amount: BigDecimal,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub category: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub description: Option<String>,
+    #[diesel(sql_type = Float)]
+    pub rank: f32,
+    #[diesel(sql_type = Text)]
+    pub snippet: String,
+}
+
+/// Columns of a transaction a saved view can show. The `id` is always shown.
+const VIEW_COLUMNS: [&str; 8] = [
+    "id",
+    "date",
+    "amount",
+    "category",
+    "category_id",
+    "description",
+    "payee_id",
+    "external_id",
+];
+
+/// Longest rolling date range of a saved view, in days.
+const MAX_VIEW_DAYS: u32 = 3660;
+
+/// The filters of a saved view, stored as JSON. The dates are either fixed
+/// (`from`/`to`) or the `last_days` up to the day the view is run, and a
+/// transaction on any of `categories` (or their subcategories) matches.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ViewFilters {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub last_days: Option<u32>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub min_amount: Option<BigDecimal>,
+    pub max_amount: Option<BigDecimal>,
+    pub tag: Option<String>,
+    /// A filter query, as taken by `GET /transactions?q=`.
+    pub q: Option<String>,
+    pub sort: Option<String>,
+    pub direction: Option<String>,
+    /// The columns to show, all of them when empty.
+    #[serde(default)]
+    pub columns: Vec<String>,
+}
+
+impl ViewFilters {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if let Some(days) = self.last_days {
+            if self.from.is_some() || self.to.is_some() {
+                return Err(ValidationError::new(
+                    "last_days cannot be combined with from or to",
+                ));
+            }
+            if !(1..=MAX_VIEW_DAYS).contains(&days) {
+                return Err(limit_error(
+                    "invalid last_days",
+                    format!("last_days must be between 1 and {}", MAX_VIEW_DAYS),
+                ));
+            }
+        }
+        if let (Some(min_amount), Some(max_amount)) = (&self.min_amount, &self.max_amount) {
+            if min_amount > max_amount {
+                return Err(ValidationError::new("min_amount must not exceed max_amount"));
+            }
+        }
+        if let Some(column) = self
+            .columns
+            .iter()
+            .find(|column| !VIEW_COLUMNS.contains(&column.as_str()))
+        {
+            let mut error = ValidationError::new("unknown column");
+            error.add_param("column".into(), column);
+            return Err(error);
+        }
+        self.search()?;
+        self.list_query(NaiveDate::MIN, None, None).parse()?;
+        Ok(())
+    }
+
+    /// The list query the view runs as on `today`.
+    pub fn list_query(
+        &self,
+        today: NaiveDate,
+        cursor: Option<String>,
+        limit: Option<i64>,
+    ) -> ListQuery {
+        let (from, to) = match self.last_days {
+            Some(days) => (
+                today.checked_sub_days(Days::new(u64::from(days) - 1)),
+                Some(today),
+            ),
+            None => (self.from, self.to),
+        };
+        ListQuery {
+            cursor,
+            limit,
+            sort: self.sort.clone(),
+            direction: self.direction.clone(),
+            from: from.map(|from| from.to_string()),
+            to: to.map(|to| to.to_string()),
+            min_amount: self.min_amount.as_ref().map(BigDecimal::to_string),
+            max_amount: self.max_amount.as_ref().map(BigDecimal::to_string),
+            category: None,
+            tag: self.tag.clone(),
+        }
+    }
+
+    pub fn search(&self) -> Result<Option<TransactionQuery>, ValidationError> {
+        match &self.q {
+            Some(q) => Ok(Some(q.parse::<TransactionQuery>()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The columns of `transaction` the view shows.
+    pub fn project(&self, transaction: &Transaction) -> serde_json::Value {
+        let mut row = serde_json::to_value(transaction).unwrap_or_default();
+        if let (Some(fields), false) = (row.as_object_mut(), self.columns.is_empty()) {
+            fields.retain(|field, _| field == "id" || self.columns.contains(field));
+        }
+        row
+    }
+}
+
+/// A named transaction lookup a user can run again, optionally pinned to
+/// the dashboard.
+#[derive(Queryable, Associations, Serialize, Deserialize)]
+#[diesel(table_name = saved_views)]
+#[diesel(belongs_to(User))]
+pub struct SavedView {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub filters: serde_json::Value,
+    pub pinned: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Body of `POST /view` and `PUT /view/<id>`.
+#[derive(Insertable, Deserialize)]
+#[diesel(table_name = saved_views)]
+pub struct NewSavedView {
+    #[serde(skip_deserializing)]
+    pub user_id: i32,
+    pub name: String,
+    #[serde(default)]
+    pub filters: serde_json::Value,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl NewSavedView {
+    /// Checks the view and returns its filters; missing filters are none.
+    pub fn validate(&self) -> Result<ViewFilters, ValidationError> {
+        if self.name.trim().is_empty() || self.name.chars().count() > 100 {
+            return Err(ValidationError::new("name must be 1 to 100 characters long"));
+        }
+        let filters = match &self.filters {
+            serde_json::Value::Null => ViewFilters::default(),
+            filters => serde_json::from_value::<ViewFilters>(filters.clone()).map_err(|err| {
+                let mut error = ValidationError::new("invalid filters");
+                error.message = Some(err.to_string().into());
+                error
+            })?,
+        };
+        filters.validate()?;
+        Ok(filters)
+    }
+}
+
+/// The number, total and average amount of all transactions in a view.
+#[derive(Serialize)]
+pub struct ViewTotals {
+    pub count: i64,
+    pub total: BigDecimal,
+    pub average: BigDecimal,
+}
+
+/// The kinds of account. Credit cards and loans usually have a negative
+/// balance: what is owed.
+pub const ACCOUNT_KINDS: [&str; 5] = ["checking", "savings", "credit_card", "cash", "loan"];
+
+/// Most months of balance history of `GET /accounts`.
+pub const MAX_BALANCE_MONTHS: u32 = 120;
+
+/// Where money sits. Its balance is the opening balance plus the income
+/// paid into it and the transfers to it, minus the transactions paid from
+/// it and the transfers from it.
+#[derive(Queryable, Associations, Serialize, Deserialize, Clone)]
+#[diesel(table_name = accounts)]
+#[diesel(belongs_to(User))]
+pub struct Account {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub kind: String,
+    /// ISO 4217 code, e.g. `EUR`.
+    pub currency: String,
+    pub opening_balance: BigDecimal,
+    pub created_at: NaiveDateTime,
+}
+
+fn default_currency() -> String {
+    "USD".to_owned()
+}
+
+/// Body of `POST /account` and `PUT /account/<id>`.
+#[derive(Insertable, Deserialize)]
+#[diesel(table_name = accounts)]
+pub struct NewAccount {
+    #[serde(skip_deserializing)]
+    pub user_id: i32,
+    pub name: String,
+    pub kind: String,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    #[serde(default)]
+    pub opening_balance: BigDecimal,
+}
+
+impl NewAccount {
+    /// Checks the account, trimming its name and upper casing its currency.
+    pub fn validate(&mut self) -> Result<(), ValidationError> {
+        self.name = self.name.trim().to_owned();
+        self.currency = self.currency.trim().to_uppercase();
+        if self.name.is_empty() || self.name.chars().count() > 100 {
+            return Err(ValidationError::new("name must be 1 to 100 characters long"));
+        }
+        if !ACCOUNT_KINDS.contains(&self.kind.as_str()) {
+            return Err(ValidationError::new(
+                "kind must be checking, savings, credit_card, cash or loan",
+            ));
+        }
+        if self.currency.len() != 3 || !self.currency.chars().all(|ch| ch.is_ascii_uppercase()) {
+            return Err(ValidationError::new(
+                "currency must be a three letter code such as USD",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Money moved from one of the user's accounts to another, such as paying
+/// off a credit card from a checking account. A transfer is neither
+/// spending nor income.
+#[derive(Queryable, Associations, Serialize, Deserialize)]
+#[diesel(table_name = transfers)]
+#[diesel(belongs_to(User))]
+pub struct Transfer {
+    pub id: i32,
+    pub user_id: i32,
+    pub from_account_id: i32,
+    pub to_account_id: i32,
+    pub amount: BigDecimal,
+    pub date: NaiveDate,
+    pub description: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Body of `POST /transfer`.
+#[derive(Insertable, Deserialize)]
+#[diesel(table_name = transfers)]
+pub struct NewTransfer {
+    #[serde(skip_deserializing)]
+    pub user_id: i32,
+    pub from_account_id: i32,
+    pub to_account_id: i32,
+    pub amount: BigDecimal,
+    pub date: NaiveDate,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl NewTransfer {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_positive_amount(&self.amount)?;
+        if self.from_account_id == self.to_account_id {
+            return Err(ValidationError::new(
+                "money can only be transferred between two different accounts",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The balance of an account at the end of a day.
+#[derive(QueryableByName, Serialize)]
+pub struct Balance {
+    #[diesel(sql_type = Integer)]
+    #[serde(skip)]
+    pub account_id: i32,
+    #[diesel(sql_type = Date)]
+    pub date: NaiveDate,
+    #[diesel(sql_type = Numeric)]
+    pub balance: BigDecimal,
+}
+
+/// An account with its balance today and at the end of each of the months
+/// before, oldest first.
+#[derive(Serialize)]
+pub struct AccountBalances {
+    #[serde(flatten)]
+    pub account: Account,
+    pub balance: BigDecimal,
+    pub history: Vec<Balance>,
+}
+
+/// Today and the last day of each of the `months` months before it, oldest
+/// first.
+pub fn balance_dates(today: NaiveDate, months: u32) -> Vec<NaiveDate> {
+    let first_of_month = today.with_day(1).unwrap_or(today);
+    let mut dates: Vec<NaiveDate> = (1..=months)
+        .rev()
+        .filter_map(|back| first_of_month.checked_sub_months(Months::new(back - 1)))
+        .filter_map(|first| first.pred_opt())
+        .collect();
+    dates.push(today);
+    dates
+}
+
+/// Rows that can be listed with keyset pagination.
+pub trait Paginated {
+    fn cursor(&self, sort: SortField) -> Cursor;
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T: Paginated> Page<T> {
+    /// Builds a page from rows fetched with `limit + 1`; the extra row only
+    /// signals that another page exists.
+    pub fn from_rows(mut rows: Vec<T>, filter: &ListFilter) -> Page<T> {
+        let mut next_cursor = None;
+        if rows.len() as i64 > filter.limit {
+            rows.truncate(filter.limit as usize);
+            next_cursor = rows.last().map(|row| row.cursor(filter.sort).encode());
+        }
+        Page {
+            data: rows,
+            next_cursor,
+        }
+    }
+}
+
+impl Paginated for Transaction {
+    fn cursor(&self, sort: SortField) -> Cursor {
+        let value = match sort {
+            SortField::Date => CursorValue::Date(self.date),
+            SortField::Amount => CursorValue::Amount(self.amount.clone()),
+            SortField::Category => CursorValue::Text(self.category.clone()),
+        };
+        Cursor { id: self.id, value }
+    }
+}
+
+impl Paginated for Budget {
+    fn cursor(&self, sort: SortField) -> Cursor {
+        let value = match sort {
+            SortField::Date => CursorValue::Date(self.start_date),
+            SortField::Amount => CursorValue::Amount(self.amount.clone()),
+            SortField::Category => CursorValue::Text(self.category.clone()),
+        };
+        Cursor { id: self.id, value }
+    }
+}
+
+impl Paginated for Income {
+    fn cursor(&self, sort: SortField) -> Cursor {
+        let value = match sort {
+            SortField::Date => CursorValue::Date(self.date),
+            SortField::Amount => CursorValue::Amount(self.amount.clone()),
+            SortField::Category => CursorValue::Text(self.source.clone()),
+        };
+        Cursor { id: self.id, value }
+    }
+}
+
+impl Paginated for Goals {
+    fn cursor(&self, sort: SortField) -> Cursor {
+        let value = match sort {
+            SortField::Date => CursorValue::Date(self.deadline),
+            SortField::Amount => CursorValue::Amount(self.goal_amount.clone()),
+            SortField::Category => CursorValue::Text(self.goal_description.clone()),
+        };
+        Cursor { id: self.id, value }
+    }
+}
@@ -1,21 +1,99 @@
 use crate::model::*;
 use crate::schema::*;
+use crate::utils::classifier::{features, rank, CategoryCounts};
+use crate::utils::duplicates::{is_duplicate, DUPLICATE_WINDOW_DAYS};
+use crate::utils::payees::{best_match, normalize};
+use crate::utils::query::{Filter, Term, TransactionQuery};
+use crate::utils::rules::RuleSet;
 use diesel::prelude::*;
+use diesel::upsert::excluded;
+use bigdecimal::BigDecimal;
+use chrono::{Days, NaiveDate, NaiveDateTime};
+use diesel::sql_types::{Array, BigInt, Bool, Date, Integer, Nullable, Numeric, Text};
+use diesel::pg::Pg;
 use diesel::PgConnection;
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
+
+define_sql_function!(fn lower(x: Text) -> Text);
+
+/// A condition on transactions built at runtime, such as a term of the
+/// query language.
+type TransactionCondition = Box<dyn BoxableExpression<transactions::table, Pg, SqlType = Bool>>;
+
+/// A `LIKE` pattern matching text that contains `text`.
+fn contains_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// The condition that `$column` lies within the bounds `$lower` and
+/// `$upper`.
+macro_rules! within {
+    ($column:expr, $lower:expr, $upper:expr) => {{
+        let mut condition: TransactionCondition = Box::new(diesel::dsl::sql::<Bool>("TRUE"));
+        condition = match $lower {
+            Bound::Included(value) => Box::new(condition.and($column.ge(value))),
+            Bound::Excluded(value) => Box::new(condition.and($column.gt(value))),
+            Bound::Unbounded => condition,
+        };
+        match $upper {
+            Bound::Included(value) => Box::new(condition.and($column.le(value))),
+            Bound::Excluded(value) => Box::new(condition.and($column.lt(value))),
+            Bound::Unbounded => condition,
+        }
+    }};
+}
+
+/// Orders a boxed query by `$column` (with `$id` as tie breaker) and, when a
+/// cursor is given, only keeps rows that come after it in that order.
+macro_rules! keyset {
+    ($query:expr, $column:expr, $id:expr, $direction:expr, $cursor:expr) => {{
+        let mut query = $query;
+        if let Some((value, id)) = $cursor {
+            query = match $direction {
+                SortDirection::Asc => query.filter(
+                    $column
+                        .gt(value.clone())
+                        .or($column.eq(value).and($id.gt(id))),
+                ),
+                SortDirection::Desc => query.filter(
+                    $column
+                        .lt(value.clone())
+                        .or($column.eq(value).and($id.lt(id))),
+                ),
+            };
+        }
+        match $direction {
+            SortDirection::Asc => query.order(($column.asc(), $id.asc())),
+            SortDirection::Desc => query.order(($column.desc(), $id.desc())),
+        }
+    }};
+}
 
 pub struct UserRepository;
 
 impl UserRepository {
     pub fn create_record(c: &mut PgConnection, record: NewUser) -> QueryResult<User> {
-        diesel::insert_into(users::table)
-            .values(record)
-            .get_result(c)
+        c.transaction(|c| {
+            let user: User = diesel::insert_into(users::table)
+                .values(record)
+                .get_result(c)?;
+            CategoryRepository::seed_defaults(c, user.id)?;
+            Ok(user)
+        })
     }
     pub fn find_by_email(c: &mut PgConnection, email: &String) -> QueryResult<Option<User>> {
         diesel::QueryDsl::filter(users::table, users::email.eq(email))
             .get_result::<User>(c)
             .optional()
     }
+    pub fn find_user(c: &mut PgConnection, id: i32) -> QueryResult<Option<User>> {
+        users::table.find(id).get_result::<User>(c).optional()
+    }
     pub fn find_multiple_users(c: &mut PgConnection, limit: i64) -> QueryResult<Vec<User>> {
         users::table.limit(limit).load::<User>(c)
     }
@@ -38,6 +116,444 @@ impl UserRepository {
     }
 }
 
+#[derive(QueryableByName)]
+struct RowId {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+}
+
+#[derive(QueryableByName)]
+struct CategoryTotal {
+    #[diesel(sql_type = Integer)]
+    category_id: i32,
+    #[diesel(sql_type = Numeric)]
+    total: BigDecimal,
+}
+
+#[derive(QueryableByName)]
+struct Total {
+    #[diesel(sql_type = Numeric)]
+    total: BigDecimal,
+}
+
+pub struct CategoryRepository;
+
+impl CategoryRepository {
+    pub fn create_category(c: &mut PgConnection, record: NewCategory) -> QueryResult<Category> {
+        diesel::insert_into(categories::table)
+            .values(record)
+            .get_result(c)
+    }
+    pub fn seed_defaults(c: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+        let mut ids: HashMap<&str, i32> = HashMap::new();
+        for (name, parent) in DEFAULT_CATEGORIES {
+            let category = Self::create_category(
+                c,
+                NewCategory {
+                    user_id,
+                    name: name.to_string(),
+                    parent_id: parent.and_then(|parent| ids.get(parent).copied()),
+                },
+            )?;
+            ids.insert(name, category.id);
+        }
+        Ok(ids.len())
+    }
+    pub fn find_multiple_categories(
+        c: &mut PgConnection,
+        user_id: i32,
+    ) -> QueryResult<Vec<Category>> {
+        categories::table
+            .filter(categories::user_id.eq(user_id))
+            .order(categories::name.asc())
+            .load::<Category>(c)
+    }
+    pub fn find_category(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<Category>> {
+        categories::table
+            .find(id)
+            .filter(categories::user_id.eq(user_id))
+            .get_result::<Category>(c)
+            .optional()
+    }
+    /// Looks a category up by name, ignoring case.
+    pub fn find_by_name(
+        c: &mut PgConnection,
+        user_id: i32,
+        name: &str,
+    ) -> QueryResult<Option<Category>> {
+        categories::table
+            .filter(categories::user_id.eq(user_id))
+            .filter(lower(categories::name).eq(name.trim().to_lowercase()))
+            .get_result::<Category>(c)
+            .optional()
+    }
+    /// Resolves either a plain name or a path like `Utilities > Electricity`,
+    /// in which case every ancestor has to match as well.
+    pub fn find_by_path(
+        c: &mut PgConnection,
+        user_id: i32,
+        path: &str,
+    ) -> QueryResult<Option<Category>> {
+        let segments: Vec<&str> = path.split(CATEGORY_PATH_SEPARATOR).map(str::trim).collect();
+        let leaf = match segments.last() {
+            Some(name) => Self::find_by_name(c, user_id, name)?,
+            None => None,
+        };
+        let Some(category) = leaf else {
+            return Ok(None);
+        };
+
+        let mut parent_id = category.parent_id;
+        for name in segments.iter().rev().skip(1) {
+            let parent = match parent_id {
+                Some(parent_id) => Self::find_category(c, user_id, parent_id)?,
+                None => None,
+            };
+            match parent {
+                Some(parent) if parent.name.to_lowercase() == name.to_lowercase() => {
+                    parent_id = parent.parent_id
+                }
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(category))
+    }
+    /// Ids of a category and all of its descendants.
+    pub fn descendant_ids(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<Vec<i32>> {
+        diesel::sql_query(
+            "WITH RECURSIVE tree AS (
+                SELECT id FROM categories WHERE id = $1 AND user_id = $2
+                UNION
+                SELECT categories.id FROM categories JOIN tree ON categories.parent_id = tree.id
+            )
+            SELECT id FROM tree",
+        )
+        .bind::<Integer, _>(id)
+        .bind::<Integer, _>(user_id)
+        .load::<RowId>(c)
+        .map(|rows| rows.into_iter().map(|row| row.id).collect())
+    }
+    /// Renames or moves a category, updating the category name stored on its
+    /// transactions, budgets and recurring templates.
+    pub fn update_category(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+        update: UpdateCategory,
+    ) -> QueryResult<Option<Category>> {
+        let name = update.name.trim().to_owned();
+        c.transaction(|c| {
+            diesel::update(
+                categories::table
+                    .find(id)
+                    .filter(categories::user_id.eq(user_id)),
+            )
+            .set((
+                categories::name.eq(&name),
+                categories::parent_id.eq(update.parent_id),
+            ))
+            .execute(c)?;
+            diesel::update(transactions::table.filter(transactions::category_id.eq(id)))
+                .set(transactions::category.eq(&name))
+                .execute(c)?;
+            diesel::update(budgets::table.filter(budgets::category_id.eq(id)))
+                .set(budgets::category.eq(&name))
+                .execute(c)?;
+            diesel::update(
+                recurring_templates::table.filter(recurring_templates::category_id.eq(id)),
+            )
+            .set(recurring_templates::category.eq(&name))
+            .execute(c)?;
+            diesel::update(category_rules::table.filter(category_rules::category_id.eq(id)))
+                .set(category_rules::category.eq(&name))
+                .execute(c)?;
+            diesel::update(payees::table.filter(payees::category_id.eq(id)))
+                .set(payees::category.eq(&name))
+                .execute(c)?;
+            diesel::update(
+                transaction_splits::table.filter(transaction_splits::category_id.eq(id)),
+            )
+            .set(transaction_splits::category.eq(&name))
+            .execute(c)?;
+            Self::find_category(c, user_id, id)
+        })
+    }
+    /// Re-points the transactions, budgets, envelopes, recurring templates and
+    /// child categories of `source` to `target`, then deletes `source`.
+    pub fn merge_categories(
+        c: &mut PgConnection,
+        user_id: i32,
+        source: &Category,
+        target: &Category,
+    ) -> QueryResult<Option<Category>> {
+        c.transaction(|c| {
+            // lift the target out of the source's subtree first so re-parenting
+            // the source's children cannot create a cycle
+            if Self::descendant_ids(c, user_id, source.id)?.contains(&target.id) {
+                diesel::update(categories::table.find(target.id))
+                    .set(categories::parent_id.eq(source.parent_id))
+                    .execute(c)?;
+            }
+            diesel::update(
+                categories::table
+                    .filter(categories::user_id.eq(user_id))
+                    .filter(categories::parent_id.eq(source.id)),
+            )
+            .set(categories::parent_id.eq(target.id))
+            .execute(c)?;
+            diesel::update(
+                transactions::table
+                    .filter(transactions::user_id.eq(user_id))
+                    .filter(transactions::category_id.eq(source.id)),
+            )
+            .set((
+                transactions::category_id.eq(target.id),
+                transactions::category.eq(&target.name),
+            ))
+            .execute(c)?;
+            diesel::update(
+                budgets::table
+                    .filter(budgets::user_id.eq(user_id))
+                    .filter(budgets::category_id.eq(source.id)),
+            )
+            .set((
+                budgets::category_id.eq(target.id),
+                budgets::category.eq(&target.name),
+            ))
+            .execute(c)?;
+            diesel::update(
+                envelope_allocations::table
+                    .filter(envelope_allocations::user_id.eq(user_id))
+                    .filter(envelope_allocations::category_id.eq(source.id)),
+            )
+            .set(envelope_allocations::category_id.eq(target.id))
+            .execute(c)?;
+            diesel::update(
+                recurring_templates::table
+                    .filter(recurring_templates::user_id.eq(user_id))
+                    .filter(recurring_templates::category_id.eq(source.id)),
+            )
+            .set((
+                recurring_templates::category_id.eq(target.id),
+                recurring_templates::category.eq(&target.name),
+            ))
+            .execute(c)?;
+            diesel::update(
+                category_rules::table
+                    .filter(category_rules::user_id.eq(user_id))
+                    .filter(category_rules::category_id.eq(source.id)),
+            )
+            .set((
+                category_rules::category_id.eq(target.id),
+                category_rules::category.eq(&target.name),
+            ))
+            .execute(c)?;
+            diesel::update(
+                payees::table
+                    .filter(payees::user_id.eq(user_id))
+                    .filter(payees::category_id.eq(source.id)),
+            )
+            .set((
+                payees::category_id.eq(target.id),
+                payees::category.eq(&target.name),
+            ))
+            .execute(c)?;
+            diesel::update(
+                transaction_splits::table
+                    .filter(transaction_splits::user_id.eq(user_id))
+                    .filter(transaction_splits::category_id.eq(source.id)),
+            )
+            .set((
+                transaction_splits::category_id.eq(target.id),
+                transaction_splits::category.eq(&target.name),
+            ))
+            .execute(c)?;
+            ClassifierRepository::invalidate(c, user_id)?;
+            Self::delete_category(c, user_id, source.id)?;
+            Self::find_category(c, user_id, target.id)
+        })
+    }
+    pub fn delete_category(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(
+            categories::table
+                .find(id)
+                .filter(categories::user_id.eq(user_id)),
+        )
+        .execute(c)
+    }
+}
+
+/// First key of the advisory lock `EnvelopeRepository::assign` and
+/// `EnvelopeRepository::move_money` take on a user's envelopes; the second
+/// key is the user id.
+const ENVELOPE_LOCK: i32 = 1;
+
+pub struct EnvelopeRepository;
+
+impl EnvelopeRepository {
+    pub fn set_mode(
+        c: &mut PgConnection,
+        user_id: i32,
+        mode: &str,
+        envelope_start: Option<NaiveDate>,
+    ) -> QueryResult<usize> {
+        diesel::update(users::table.find(user_id))
+            .set((
+                users::budgeting_mode.eq(mode),
+                users::envelope_start.eq(envelope_start),
+            ))
+            .execute(c)
+    }
+    /// Every category's envelope for `month`, counting allocations and
+    /// spending from `start` (the month envelope budgeting began) onwards.
+    pub fn find_envelopes(
+        c: &mut PgConnection,
+        user_id: i32,
+        start: NaiveDate,
+        month: NaiveDate,
+    ) -> QueryResult<Vec<Envelope>> {
+        diesel::sql_query(
+            "WITH allocated AS (
+                SELECT category_id,
+                    SUM(amount) FILTER (WHERE month = $3) AS assigned,
+                    SUM(amount) AS total
+                FROM envelope_allocations
+                WHERE user_id = $1 AND month >= $2 AND month <= $3
+                GROUP BY category_id
+            ),
+            spending AS (
+                SELECT category_id,
+                    SUM(amount) FILTER (WHERE date >= $3) AS activity,
+                    SUM(amount) AS total
+                FROM transaction_lines
+                WHERE user_id = $1 AND date >= $2 AND date <= $4 AND category_id IS NOT NULL
+                GROUP BY category_id
+            )
+            SELECT categories.id AS category_id, categories.name AS category,
+                COALESCE(allocated.assigned, 0) AS assigned,
+                COALESCE(spending.activity, 0) AS activity,
+                COALESCE(allocated.total, 0) - COALESCE(spending.total, 0) AS available
+            FROM categories
+            LEFT JOIN allocated ON allocated.category_id = categories.id
+            LEFT JOIN spending ON spending.category_id = categories.id
+            WHERE categories.user_id = $1
+            ORDER BY categories.name",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Date, _>(start)
+        .bind::<Date, _>(month)
+        .bind::<Date, _>(month_end(month))
+        .load::<Envelope>(c)
+    }
+    pub fn find_unassigned(
+        c: &mut PgConnection,
+        user_id: i32,
+        start: NaiveDate,
+        month: NaiveDate,
+    ) -> QueryResult<UnassignedMoney> {
+        diesel::sql_query(
+            "SELECT $3 AS month, totals.income, totals.assigned,
+                totals.income - totals.assigned AS to_be_assigned
+            FROM (
+                SELECT
+                    (SELECT COALESCE(SUM(amount), 0) FROM income
+                        WHERE user_id = $1 AND date >= $2 AND date <= $4) AS income,
+                    (SELECT COALESCE(SUM(amount), 0) FROM envelope_allocations
+                        WHERE user_id = $1 AND month >= $2 AND month <= $3) AS assigned
+            ) AS totals",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Date, _>(start)
+        .bind::<Date, _>(month)
+        .bind::<Date, _>(month_end(month))
+        .get_result::<UnassignedMoney>(c)
+    }
+    /// Locks the user's envelopes until the surrounding transaction ends and
+    /// returns the money available in `category_id`'s envelope for `month`.
+    fn lock_available(
+        c: &mut PgConnection,
+        user_id: i32,
+        start: NaiveDate,
+        month: NaiveDate,
+        category_id: i32,
+    ) -> QueryResult<BigDecimal> {
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1, $2)")
+            .bind::<Integer, _>(ENVELOPE_LOCK)
+            .bind::<Integer, _>(user_id)
+            .execute(c)?;
+        Ok(Self::find_envelopes(c, user_id, start, month)?
+            .into_iter()
+            .find(|envelope| envelope.category_id == category_id)
+            .map(|envelope| envelope.available)
+            .unwrap_or_else(|| BigDecimal::from(0)))
+    }
+    /// Records an allocation. Returns `None` without writing anything when a
+    /// negative allocation takes out more than the envelope holds.
+    pub fn assign(
+        c: &mut PgConnection,
+        start: NaiveDate,
+        record: NewAllocation,
+    ) -> QueryResult<Option<Allocation>> {
+        c.transaction(|c| {
+            let available =
+                Self::lock_available(c, record.user_id, start, record.month, record.category_id)?;
+            if available + &record.amount < BigDecimal::from(0) {
+                return Ok(None);
+            }
+
+            diesel::insert_into(envelope_allocations::table)
+                .values(record)
+                .get_result(c)
+                .map(Some)
+        })
+    }
+    /// Moves `amount` between two envelopes as a pair of allocations. Returns
+    /// `None` without writing anything when the source envelope does not hold
+    /// enough money. Moves and assignments of one user run one at a time, so two
+    /// of them cannot both take the same money.
+    pub fn move_money(
+        c: &mut PgConnection,
+        user_id: i32,
+        start: NaiveDate,
+        month: NaiveDate,
+        from: &Category,
+        to: &Category,
+        amount: BigDecimal,
+    ) -> QueryResult<Option<Vec<Allocation>>> {
+        c.transaction(|c| {
+            let available = Self::lock_available(c, user_id, start, month, from.id)?;
+            if available < amount {
+                return Ok(None);
+            }
+
+            let moves = vec![
+                NewAllocation {
+                    user_id,
+                    category_id: from.id,
+                    month,
+                    amount: -amount.clone(),
+                    note: Some(format!("moved to {}", to.name)),
+                },
+                NewAllocation {
+                    user_id,
+                    category_id: to.id,
+                    month,
+                    amount,
+                    note: Some(format!("moved from {}", from.name)),
+                },
+            ];
+            diesel::insert_into(envelope_allocations::table)
+                .values(moves)
+                .get_results(c)
+                .map(Some)
+        })
+    }
+}
+
 pub struct BudgetRepository;
 
 impl BudgetRepository {
@@ -48,30 +564,331 @@ impl BudgetRepository {
     }
     pub fn find_multiple_budgets(
         c: &mut PgConnection,
-        limit: i64,
-    ) -> QueryResult<Option<Vec<Budget>>> {
-        budgets::table.limit(limit).load::<Budget>(c).optional()
+        user_id: i32,
+        filter: &ListFilter,
+    ) -> QueryResult<Page<Budget>> {
+        let mut query = budgets::table
+            .filter(budgets::user_id.eq(user_id))
+            .into_boxed();
+        // a budget matches a date range when its period overlaps it
+        if let Some(from) = filter.from {
+            query = query.filter(budgets::end_date.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(budgets::start_date.le(to));
+        }
+        if let Some(min_amount) = &filter.min_amount {
+            query = query.filter(budgets::amount.ge(min_amount.clone()));
+        }
+        if let Some(max_amount) = &filter.max_amount {
+            query = query.filter(budgets::amount.le(max_amount.clone()));
+        }
+        if let Some(category) = &filter.category {
+            query = query.filter(budgets::category.eq(category.clone()));
+        }
+
+        let cursor = filter.cursor.as_ref();
+        let query = match filter.sort {
+            SortField::Date => keyset!(
+                query,
+                budgets::start_date,
+                budgets::id,
+                filter.direction,
+                cursor.and_then(Cursor::date)
+            ),
+            SortField::Amount => keyset!(
+                query,
+                budgets::amount,
+                budgets::id,
+                filter.direction,
+                cursor.and_then(Cursor::amount)
+            ),
+            SortField::Category => keyset!(
+                query,
+                budgets::category,
+                budgets::id,
+                filter.direction,
+                cursor.and_then(Cursor::text)
+            ),
+        };
+        let rows = query.limit(filter.limit + 1).load::<Budget>(c)?;
+        Ok(Page::from_rows(rows, filter))
     }
-    pub fn find_budget(c: &mut PgConnection, id: i32) -> QueryResult<Option<Budget>> {
-        budgets::table.find(id).get_result::<Budget>(c).optional()
+    pub fn find_budget(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<Option<Budget>> {
+        budgets::table
+            .find(id)
+            .filter(budgets::user_id.eq(user_id))
+            .get_result::<Budget>(c)
+            .optional()
     }
     pub fn update_budget(
         c: &mut PgConnection,
+        user_id: i32,
         id: i32,
         update: UpdateBudget,
     ) -> QueryResult<Option<Budget>> {
-        diesel::update(budgets::table.find(id))
+        diesel::update(budgets::table.find(id).filter(budgets::user_id.eq(user_id)))
             .set((
                 budgets::amount.eq(update.amount.to_owned()),
                 budgets::category.eq(update.category.to_owned()),
+                budgets::category_id.eq(update.category_id),
+                budgets::recurrence.eq(update.recurrence.to_owned()),
+                budgets::rollover.eq(update.rollover.to_owned()),
+                budgets::alert_thresholds.eq(update.alert_thresholds.to_owned()),
                 budgets::start_date.eq(update.start_date.to_owned()),
                 budgets::end_date.eq(update.end_date.to_owned()),
             ))
             .execute(c)?;
-        Self::find_budget(c, id)
+        Self::find_budget(c, user_id, id)
+    }
+    /// Compares each budget (or only budget `id`) with the spending booked in
+    /// its period on its category and all of that category's descendants.
+    /// Burn rate and projection are based on the days elapsed so far.
+    pub fn find_status(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: Option<i32>,
+    ) -> QueryResult<Vec<BudgetStatus>> {
+        diesel::sql_query(
+            "WITH RECURSIVE budget_categories AS (
+                SELECT budgets.id AS budget_id, budgets.category_id
+                FROM budgets
+                WHERE budgets.user_id = $1 AND budgets.category_id IS NOT NULL
+                UNION
+                SELECT budget_categories.budget_id, categories.id
+                FROM categories
+                JOIN budget_categories ON categories.parent_id = budget_categories.category_id
+            ),
+            spending AS (
+                SELECT budget_categories.budget_id, SUM(transaction_lines.amount) AS spent
+                FROM budget_categories
+                JOIN budgets ON budgets.id = budget_categories.budget_id
+                JOIN transaction_lines
+                    ON transaction_lines.category_id = budget_categories.category_id
+                    AND transaction_lines.user_id = budgets.user_id
+                    AND transaction_lines.date BETWEEN budgets.start_date AND budgets.end_date
+                GROUP BY budget_categories.budget_id
+            ),
+            periods AS (
+                SELECT budgets.id, budgets.category, budgets.start_date, budgets.end_date,
+                    budgets.amount + budgets.rollover_amount AS budgeted,
+                    budgets.rollover_amount,
+                    COALESCE(spending.spent, 0) AS spent,
+                    budgets.end_date - budgets.start_date + 1 AS days_total,
+                    GREATEST(LEAST(CURRENT_DATE, budgets.end_date) - budgets.start_date + 1, 0)
+                        AS days_elapsed
+                FROM budgets
+                LEFT JOIN spending ON spending.budget_id = budgets.id
+                WHERE budgets.user_id = $1 AND ($2::INTEGER IS NULL OR budgets.id = $2)
+            )
+            SELECT id AS budget_id, category, start_date, end_date, budgeted, rollover_amount,
+                spent,
+                budgeted - spent AS remaining,
+                CASE WHEN budgeted > 0 THEN ROUND(spent * 100 / budgeted, 2) END AS percent_used,
+                CASE WHEN days_elapsed > 0 THEN ROUND(spent / days_elapsed, 2) ELSE 0 END
+                    AS daily_burn_rate,
+                CASE WHEN days_elapsed > 0 AND days_elapsed < days_total
+                    THEN ROUND(spent / days_elapsed * days_total, 2)
+                    ELSE spent
+                END AS projected_spend,
+                days_elapsed, days_total
+            FROM periods
+            ORDER BY start_date DESC, id DESC",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Nullable<Integer>, _>(id)
+        .load::<BudgetStatus>(c)
+    }
+    /// Creates the next period of every recurring budget whose current period
+    /// ended before `today`, carrying money over according to its rollover
+    /// policy. Runs until each recurring budget has a period covering `today`.
+    pub fn materialize_periods(
+        c: &mut PgConnection,
+        user_id: Option<i32>,
+        today: NaiveDate,
+    ) -> QueryResult<usize> {
+        let mut query = budgets::table
+            .filter(budgets::recurrence.is_not_null())
+            .filter(budgets::end_date.lt(today))
+            .into_boxed();
+        if let Some(user_id) = user_id {
+            query = query.filter(budgets::user_id.eq(user_id));
+        }
+        let ended = query.load::<Budget>(c)?;
+        let ended_ids: Vec<i32> = ended.iter().map(|budget| budget.id).collect();
+        let continued: Vec<i32> = budgets::table
+            .filter(budgets::previous_budget_id.eq_any(ended_ids))
+            .select(budgets::previous_budget_id.assume_not_null())
+            .load(c)?;
+
+        // only the periods created by the previous pass can have ended too
+        let mut pending: Vec<Budget> =
+            ended.into_iter().filter(|b| !continued.contains(&b.id)).collect();
+        let mut created = 0;
+        while !pending.is_empty() {
+            let mut next = Vec::new();
+            for budget in &pending {
+                if let Some(period) = Self::create_next_period(c, budget)? {
+                    created += 1;
+                    if period.end_date < today {
+                        next.push(period);
+                    }
+                }
+            }
+            pending = next;
+        }
+        Ok(created)
+    }
+    fn create_next_period(c: &mut PgConnection, budget: &Budget) -> QueryResult<Option<Budget>> {
+        let recurrence = budget.recurrence.as_deref().and_then(Recurrence::parse);
+        let period = recurrence.and_then(|recurrence| recurrence.next_period(budget.end_date));
+        let (Some(user_id), Some((start_date, end_date))) = (budget.user_id, period) else {
+            return Ok(None);
+        };
+        let policy = Rollover::parse(&budget.rollover).unwrap_or(Rollover::None);
+        let rollover_amount = match policy {
+            Rollover::None => BigDecimal::from(0),
+            _ => match Self::find_status(c, user_id, Some(budget.id))?.pop() {
+                Some(status) => policy.carry(&status.remaining),
+                None => BigDecimal::from(0),
+            },
+        };
+
+        diesel::insert_into(budgets::table)
+            .values(NewBudget {
+                user_id: budget.user_id,
+                category: budget.category.clone(),
+                amount: budget.amount.clone(),
+                start_date,
+                end_date,
+                category_id: budget.category_id,
+                recurrence: budget.recurrence.clone(),
+                rollover: budget.rollover.clone(),
+                rollover_amount,
+                previous_budget_id: Some(budget.id),
+                alert_thresholds: budget.alert_thresholds.clone(),
+            })
+            .on_conflict(budgets::previous_budget_id)
+            .do_nothing()
+            .get_result(c)
+            .optional()
     }
-    pub fn delete_budget(c: &mut PgConnection, id: i32) -> QueryResult<usize> {
-        diesel::delete(budgets::table.find(id)).execute(c)
+    pub fn delete_budget(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(budgets::table.find(id).filter(budgets::user_id.eq(user_id))).execute(c)
+    }
+}
+
+pub struct AlertRepository;
+
+impl AlertRepository {
+    /// Creates an alert for every threshold that the budgets covering
+    /// `transaction` have now reached. Budgets on an ancestor of the
+    /// transaction's category count as well. Thresholds that already fired
+    /// for a budget are skipped, so only the new alerts are returned. The
+    /// periods of recurring budgets up to `today` are created first.
+    pub fn check_thresholds(
+        c: &mut PgConnection,
+        user_id: i32,
+        transaction: &Transaction,
+        today: NaiveDate,
+    ) -> QueryResult<Vec<Alert>> {
+        BudgetRepository::materialize_periods(c, Some(user_id), today)?;
+        let budget_ids = diesel::sql_query(
+            "WITH RECURSIVE ancestors AS (
+                SELECT id, parent_id FROM categories
+                WHERE user_id = $1 AND id IN (
+                    SELECT category_id FROM transaction_lines WHERE transaction_id = $2
+                )
+                UNION
+                SELECT categories.id, categories.parent_id
+                FROM categories
+                JOIN ancestors ON categories.id = ancestors.parent_id
+            )
+            SELECT budgets.id
+            FROM budgets
+            JOIN ancestors ON budgets.category_id = ancestors.id
+            WHERE budgets.user_id = $1 AND $3 BETWEEN budgets.start_date AND budgets.end_date",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Integer, _>(transaction.id)
+        .bind::<Date, _>(transaction.date)
+        .load::<RowId>(c)?;
+
+        let mut created = Vec::new();
+        for budget_id in budget_ids.into_iter().map(|row| row.id) {
+            let Some(budget) = BudgetRepository::find_budget(c, user_id, budget_id)? else {
+                continue;
+            };
+            let Some(percent_used) = BudgetRepository::find_status(c, user_id, Some(budget_id))?
+                .pop()
+                .and_then(|status| status.percent_used)
+            else {
+                continue;
+            };
+            let reached: Vec<NewAlert> = budget
+                .alert_thresholds
+                .iter()
+                .flatten()
+                .filter(|threshold| percent_used >= BigDecimal::from(**threshold))
+                .map(|threshold| NewAlert {
+                    user_id,
+                    budget_id,
+                    transaction_id: Some(transaction.id),
+                    category: budget.category.clone(),
+                    threshold: *threshold,
+                    percent_used: percent_used.clone(),
+                })
+                .collect();
+            if reached.is_empty() {
+                continue;
+            }
+            created.extend(
+                diesel::insert_into(alerts::table)
+                    .values(reached)
+                    .on_conflict((alerts::budget_id, alerts::threshold))
+                    .do_nothing()
+                    .get_results::<Alert>(c)?,
+            );
+        }
+        Ok(created)
+    }
+    pub fn find_alerts(
+        c: &mut PgConnection,
+        user_id: i32,
+        unread_only: bool,
+    ) -> QueryResult<Vec<Alert>> {
+        let mut query = alerts::table
+            .filter(alerts::user_id.eq(user_id))
+            .order((alerts::created_at.desc(), alerts::id.desc()))
+            .into_boxed();
+        if unread_only {
+            query = query.filter(alerts::read_at.is_null());
+        }
+        query.load::<Alert>(c)
+    }
+    pub fn mark_read(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<Option<Alert>> {
+        diesel::update(
+            alerts::table
+                .find(id)
+                .filter(alerts::user_id.eq(user_id))
+                .filter(alerts::read_at.is_null()),
+        )
+        .set(alerts::read_at.eq(diesel::dsl::now))
+        .execute(c)?;
+        alerts::table
+            .find(id)
+            .filter(alerts::user_id.eq(user_id))
+            .get_result::<Alert>(c)
+            .optional()
+    }
+    pub fn mark_all_read(c: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+        diesel::update(
+            alerts::table
+                .filter(alerts::user_id.eq(user_id))
+                .filter(alerts::read_at.is_null()),
+        )
+        .set(alerts::read_at.eq(diesel::dsl::now))
+        .execute(c)
     }
 }
 
@@ -82,42 +899,2046 @@ impl TransactionsRepository {
         c: &mut PgConnection,
         record: NewTransaction,
     ) -> QueryResult<Transaction> {
-        diesel::insert_into(transactions::table)
+        let transaction: Transaction = diesel::insert_into(transactions::table)
             .values(record)
-            .get_result(c)
+            .get_result(c)?;
+        ClassifierRepository::learn(c, &transaction, 1)?;
+        Ok(transaction)
     }
+    /// A page of the user's transactions matching `filter` and, when given,
+    /// the query language `search`.
     pub fn find_multiple_transactions(
         c: &mut PgConnection,
-        limit: i64,
-    ) -> QueryResult<Option<Vec<Transaction>>> {
-        transactions::table
-            .limit(limit)
-            .load::<Transaction>(c)
-            .optional()
+        user_id: i32,
+        filter: &ListFilter,
+        search: Option<&TransactionQuery>,
+    ) -> QueryResult<Page<Transaction>> {
+        let query = Self::matching(c, user_id, filter, search, &[])?;
+        Self::page(c, query, filter)
+    }
+    /// A page of the transactions of a saved view, with the number and total
+    /// of all transactions in the view. With `categories`, only the split
+    /// lines on those categories count towards the total.
+    pub fn find_view_results(
+        c: &mut PgConnection,
+        user_id: i32,
+        filter: &ListFilter,
+        search: Option<&TransactionQuery>,
+        categories: &[String],
+    ) -> QueryResult<(Page<Transaction>, ViewTotals)> {
+        let (count, total) = Self::matching(c, user_id, filter, search, categories)?
+            .select((diesel::dsl::count_star(), diesel::dsl::sum(transactions::amount)))
+            .get_result::<(i64, Option<BigDecimal>)>(c)?;
+        let mut total = total.unwrap_or_default();
+        if !categories.is_empty() {
+            let mut category_ids = Vec::new();
+            for category in categories {
+                category_ids.extend(Self::category_ids(c, user_id, category)?);
+            }
+            let ids = Self::matching(c, user_id, filter, search, categories)?
+                .select(transactions::id)
+                .load::<i32>(c)?;
+            total = diesel::sql_query(
+                "SELECT COALESCE(SUM(amount), 0) AS total
+                FROM transaction_lines
+                WHERE user_id = $1 AND transaction_id = ANY($2) AND category_id = ANY($3)",
+            )
+            .bind::<Integer, _>(user_id)
+            .bind::<Array<Integer>, _>(ids)
+            .bind::<Array<Integer>, _>(category_ids)
+            .get_result::<Total>(c)?
+            .total;
+        }
+        let average = if count > 0 {
+            (&total / BigDecimal::from(count)).round(2)
+        } else {
+            BigDecimal::from(0)
+        };
+        let query = Self::matching(c, user_id, filter, search, categories)?;
+        let page = Self::page(c, query, filter)?;
+        Ok((
+            page,
+            ViewTotals {
+                count,
+                total,
+                average,
+            },
+        ))
+    }
+    /// The user's transactions matching `filter`, `search` and, unless it is
+    /// empty, one of `categories`, the cursor of `filter` aside.
+    fn matching(
+        c: &mut PgConnection,
+        user_id: i32,
+        filter: &ListFilter,
+        search: Option<&TransactionQuery>,
+        categories: &[String],
+    ) -> QueryResult<transactions::BoxedQuery<'static, Pg>> {
+        let mut query = transactions::table
+            .filter(transactions::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(from) = filter.from {
+            query = query.filter(transactions::date.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(transactions::date.le(to));
+        }
+        if let Some(min_amount) = &filter.min_amount {
+            query = query.filter(transactions::amount.ge(min_amount.clone()));
+        }
+        if let Some(max_amount) = &filter.max_amount {
+            query = query.filter(transactions::amount.le(max_amount.clone()));
+        }
+        if let Some(category) = &filter.category {
+            query = query.filter(Self::category_condition(c, user_id, category)?);
+        }
+        if let Some(tag) = &filter.tag {
+            query = query.filter(Self::tag_condition(user_id, tag));
+        }
+        for term in search.map_or(&[][..], |search| &search.terms) {
+            query = query.filter(Self::term_condition(c, user_id, term)?);
+        }
+        let mut any_category: Option<TransactionCondition> = None;
+        for category in categories {
+            let condition = Self::category_condition(c, user_id, category)?;
+            any_category = Some(match any_category {
+                Some(any_category) => Box::new(any_category.or(condition)),
+                None => condition,
+            });
+        }
+        if let Some(any_category) = any_category {
+            query = query.filter(any_category);
+        }
+        Ok(query)
+    }
+    /// Sorts `query` as `filter` asks and loads the page after its cursor.
+    fn page(
+        c: &mut PgConnection,
+        query: transactions::BoxedQuery<'static, Pg>,
+        filter: &ListFilter,
+    ) -> QueryResult<Page<Transaction>> {
+        let cursor = filter.cursor.as_ref();
+        let query = match filter.sort {
+            SortField::Date => keyset!(
+                query,
+                transactions::date,
+                transactions::id,
+                filter.direction,
+                cursor.and_then(Cursor::date)
+            ),
+            SortField::Amount => keyset!(
+                query,
+                transactions::amount,
+                transactions::id,
+                filter.direction,
+                cursor.and_then(Cursor::amount)
+            ),
+            SortField::Category => keyset!(
+                query,
+                transactions::category,
+                transactions::id,
+                filter.direction,
+                cursor.and_then(Cursor::text)
+            ),
+        };
+        let rows = query.limit(filter.limit + 1).load::<Transaction>(c)?;
+        Ok(Page::from_rows(rows, filter))
+    }
+    /// The ids of `category` and its subcategories, none when it does not
+    /// exist.
+    fn category_ids(c: &mut PgConnection, user_id: i32, category: &str) -> QueryResult<Vec<i32>> {
+        match CategoryRepository::find_by_path(c, user_id, category)? {
+            Some(category) => CategoryRepository::descendant_ids(c, user_id, category.id),
+            None => Ok(Vec::new()),
+        }
+    }
+    /// Transactions on `category` or one of its subcategories, or with a
+    /// split line there.
+    fn category_condition(
+        c: &mut PgConnection,
+        user_id: i32,
+        category: &str,
+    ) -> QueryResult<TransactionCondition> {
+        let ids = Self::category_ids(c, user_id, category)?;
+        let split = transaction_splits::table
+            .filter(transaction_splits::transaction_id.eq(transactions::id));
+        let split_ids = transaction_splits::table
+            .filter(transaction_splits::category_id.eq_any(ids.clone()))
+            .select(transaction_splits::transaction_id);
+        Ok(Box::new(
+            transactions::category_id
+                .is_not_null()
+                .and(transactions::category_id.assume_not_null().eq_any(ids))
+                .and(diesel::dsl::not(diesel::dsl::exists(split)))
+                .or(transactions::id.eq_any(split_ids)),
+        ))
+    }
+    fn tag_condition(user_id: i32, tag: &str) -> TransactionCondition {
+        let tagged = transaction_tags::table
+            .inner_join(tags::table)
+            .filter(tags::user_id.eq(user_id))
+            .filter(tags::name.eq(tag.to_owned()))
+            .select(transaction_tags::transaction_id);
+        Box::new(transactions::id.eq_any(tagged))
     }
-    pub fn find_transaction(c: &mut PgConnection, id: i32) -> QueryResult<Option<Transaction>> {
+    /// Compiles a term of the query language. Every condition is true or
+    /// false, never NULL, so that negating it keeps the rows it excluded.
+    fn term_condition(
+        c: &mut PgConnection,
+        user_id: i32,
+        term: &Term,
+    ) -> QueryResult<TransactionCondition> {
+        let condition: TransactionCondition = match &term.filter {
+            Filter::Category(category) => Self::category_condition(c, user_id, category)?,
+            Filter::Description(text) => Box::new(
+                transactions::description.is_not_null().and(
+                    transactions::description
+                        .assume_not_null()
+                        .ilike(contains_pattern(text)),
+                ),
+            ),
+            Filter::Payee(name) => {
+                let payee_ids = payees::table
+                    .filter(payees::user_id.eq(user_id))
+                    .filter(lower(payees::name).eq(name.to_lowercase()))
+                    .select(payees::id);
+                Box::new(
+                    transactions::payee_id
+                        .is_not_null()
+                        .and(transactions::payee_id.assume_not_null().eq_any(payee_ids)),
+                )
+            }
+            Filter::Tag(tag) => Self::tag_condition(user_id, &normalize_tag(tag)),
+            Filter::Amount(lower, upper) => {
+                within!(transactions::amount, lower.clone(), upper.clone())
+            }
+            Filter::Date(lower, upper) => within!(transactions::date, *lower, *upper),
+        };
+        Ok(if term.negated {
+            Box::new(diesel::dsl::not(condition))
+        } else {
+            condition
+        })
+    }
+    pub fn find_transaction(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<Transaction>> {
         transactions::table
             .find(id)
+            .filter(transactions::user_id.eq(user_id))
             .get_result::<Transaction>(c)
             .optional()
     }
     pub fn update_transaction(
         c: &mut PgConnection,
+        user_id: i32,
         id: i32,
         update: UpdateTransaction,
     ) -> QueryResult<Option<Transaction>> {
-        diesel::update(transactions::table.find(id))
+        let Some(previous) = Self::find_transaction(c, user_id, id)? else {
+            return Ok(None);
+        };
+        let payee_id = if update.description == previous.description {
+            previous.payee_id
+        } else {
+            match &update.description {
+                Some(description) => {
+                    let aliases = PayeeRepository::find_all_aliases(c, user_id)?;
+                    PayeeRepository::match_description(&aliases, description)
+                        .map(|alias| alias.payee_id)
+                }
+                None => None,
+            }
+        };
+        diesel::update(
+            transactions::table
+                .find(id)
+                .filter(transactions::user_id.eq(user_id)),
+        )
+        .set((
+            transactions::amount.eq(update.amount.to_owned()),
+            transactions::description.eq(update.description.to_owned()),
+            transactions::category.eq(update.category.to_owned()),
+            transactions::category_id.eq(update.category_id),
+            transactions::date.eq(update.date.to_owned()),
+            transactions::payee_id.eq(payee_id),
+            transactions::account_id.eq(update.account_id),
+        ))
+        .execute(c)?;
+        let updated = Self::find_transaction(c, user_id, id)?;
+        if let Some(transaction) = &updated {
+            ClassifierRepository::learn(c, &previous, -1)?;
+            ClassifierRepository::learn(c, transaction, 1)?;
+        }
+        Ok(updated)
+    }
+    /// Spending per category id between `from` and `to` (both optional),
+    /// counting the lines of split transactions.
+    pub fn totals_by_category(
+        c: &mut PgConnection,
+        user_id: i32,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> QueryResult<HashMap<i32, BigDecimal>> {
+        let rows = diesel::sql_query(
+            "SELECT category_id, SUM(amount) AS total
+            FROM transaction_lines
+            WHERE user_id = $1 AND category_id IS NOT NULL
+                AND ($2::DATE IS NULL OR date >= $2)
+                AND ($3::DATE IS NULL OR date <= $3)
+            GROUP BY category_id",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Nullable<Date>, _>(from)
+        .bind::<Nullable<Date>, _>(to)
+        .load::<CategoryTotal>(c)?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.category_id, row.total))
+            .collect())
+    }
+    /// Every transaction between `from` and `to` (both optional), oldest first.
+    pub fn find_in_range(
+        c: &mut PgConnection,
+        user_id: i32,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> QueryResult<Vec<Transaction>> {
+        let mut query = transactions::table
+            .filter(transactions::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(from) = from {
+            query = query.filter(transactions::date.ge(from));
+        }
+        if let Some(to) = to {
+            query = query.filter(transactions::date.le(to));
+        }
+        query
+            .order((transactions::date.asc(), transactions::id.asc()))
+            .load::<Transaction>(c)
+    }
+    /// Which of the bank ids in `external_ids` were already imported.
+    pub fn find_external_ids(
+        c: &mut PgConnection,
+        user_id: i32,
+        external_ids: &[String],
+    ) -> QueryResult<HashSet<String>> {
+        transactions::table
+            .filter(transactions::user_id.eq(user_id))
+            .filter(transactions::external_id.eq_any(external_ids))
+            .select(transactions::external_id.assume_not_null())
+            .load::<String>(c)
+            .map(|ids| ids.into_iter().collect())
+    }
+    /// Transactions other than `exclude` that look like duplicates of one of
+    /// `amount` on `date` described as `description`.
+    pub fn find_possible_duplicates(
+        c: &mut PgConnection,
+        user_id: i32,
+        amount: &BigDecimal,
+        date: NaiveDate,
+        description: Option<&str>,
+        exclude: Option<i32>,
+    ) -> QueryResult<Vec<Transaction>> {
+        let window = Days::new(DUPLICATE_WINDOW_DAYS);
+        let (Some(first), Some(last)) = (date.checked_sub_days(window), date.checked_add_days(window))
+        else {
+            return Ok(Vec::new());
+        };
+        let mut query = transactions::table
+            .filter(transactions::user_id.eq(user_id))
+            .filter(transactions::amount.eq(amount.clone()))
+            .filter(transactions::date.between(first, last))
+            .into_boxed();
+        if let Some(exclude) = exclude {
+            query = query.filter(transactions::id.ne(exclude));
+        }
+        let candidates = query
+            .order((transactions::date.asc(), transactions::id.asc()))
+            .load::<Transaction>(c)?;
+        Ok(candidates
+            .into_iter()
+            .filter(|candidate| {
+                is_duplicate(amount, date, description, candidate, DUPLICATE_WINDOW_DAYS)
+            })
+            .collect())
+    }
+    /// Deletes `duplicates` and keeps `keep`, which takes over their alerts,
+    /// recurring occurrences, attachments and tags, their bank id and
+    /// description when it has none, and the split lines of the first split
+    /// duplicate when it is not split itself.
+    pub fn merge_transactions(
+        c: &mut PgConnection,
+        user_id: i32,
+        keep: &Transaction,
+        duplicates: &[Transaction],
+    ) -> QueryResult<Option<Transaction>> {
+        let ids: Vec<i32> = duplicates.iter().map(|duplicate| duplicate.id).collect();
+        let external_id = keep.external_id.clone().or_else(|| {
+            duplicates
+                .iter()
+                .find_map(|duplicate| duplicate.external_id.clone())
+        });
+        let (description, payee_id) = match &keep.description {
+            Some(description) => (Some(description.clone()), keep.payee_id),
+            None => duplicates
+                .iter()
+                .find(|duplicate| duplicate.description.is_some())
+                .map_or((None, keep.payee_id), |duplicate| {
+                    (duplicate.description.clone(), duplicate.payee_id)
+                }),
+        };
+        c.transaction(|c| {
+            diesel::update(alerts::table.filter(alerts::transaction_id.eq_any(&ids)))
+                .set(alerts::transaction_id.eq(keep.id))
+                .execute(c)?;
+            diesel::update(
+                recurring_occurrences::table
+                    .filter(recurring_occurrences::transaction_id.eq_any(&ids)),
+            )
+            .set(recurring_occurrences::transaction_id.eq(keep.id))
+            .execute(c)?;
+            diesel::update(attachments::table.filter(attachments::transaction_id.eq_any(&ids)))
+                .set(attachments::transaction_id.eq(keep.id))
+                .execute(c)?;
+            diesel::sql_query(
+                "INSERT INTO transaction_tags (transaction_id, tag_id)
+                SELECT $1, tag_id FROM transaction_tags WHERE transaction_id = ANY($2)
+                ON CONFLICT DO NOTHING",
+            )
+            .bind::<Integer, _>(keep.id)
+            .bind::<Array<Integer>, _>(&ids)
+            .execute(c)?;
+            if Self::find_splits(c, user_id, keep.id)?.is_empty() {
+                let split = transaction_splits::table
+                    .filter(transaction_splits::transaction_id.eq_any(&ids))
+                    .order(transaction_splits::transaction_id.asc())
+                    .select(transaction_splits::transaction_id)
+                    .first::<i32>(c)
+                    .optional()?;
+                if let Some(split) = split {
+                    diesel::update(
+                        transaction_splits::table
+                            .filter(transaction_splits::transaction_id.eq(split)),
+                    )
+                    .set(transaction_splits::transaction_id.eq(keep.id))
+                    .execute(c)?;
+                }
+            }
+            // the bank id can only move once its row is gone
+            diesel::delete(
+                transactions::table
+                    .filter(transactions::user_id.eq(user_id))
+                    .filter(transactions::id.eq_any(&ids)),
+            )
+            .execute(c)?;
+            diesel::update(transactions::table.find(keep.id))
+                .set((
+                    transactions::external_id.eq(external_id),
+                    transactions::description.eq(description),
+                    transactions::payee_id.eq(payee_id),
+                ))
+                .execute(c)?;
+            let merged = Self::find_transaction(c, user_id, keep.id)?;
+            for duplicate in duplicates {
+                ClassifierRepository::learn(c, duplicate, -1)?;
+            }
+            if let Some(merged) = &merged {
+                ClassifierRepository::learn(c, keep, -1)?;
+                ClassifierRepository::learn(c, merged, 1)?;
+            }
+            Ok(merged)
+        })
+    }
+    pub fn find_splits(
+        c: &mut PgConnection,
+        user_id: i32,
+        transaction_id: i32,
+    ) -> QueryResult<Vec<TransactionSplit>> {
+        transaction_splits::table
+            .filter(transaction_splits::user_id.eq(user_id))
+            .filter(transaction_splits::transaction_id.eq(transaction_id))
+            .order(transaction_splits::id.asc())
+            .load::<TransactionSplit>(c)
+    }
+    /// Replaces the split lines of a transaction.
+    pub fn set_splits(
+        c: &mut PgConnection,
+        user_id: i32,
+        transaction_id: i32,
+        splits: Vec<NewTransactionSplit>,
+    ) -> QueryResult<Vec<TransactionSplit>> {
+        c.transaction(|c| {
+            diesel::delete(
+                transaction_splits::table
+                    .filter(transaction_splits::user_id.eq(user_id))
+                    .filter(transaction_splits::transaction_id.eq(transaction_id)),
+            )
+            .execute(c)?;
+            diesel::insert_into(transaction_splits::table)
+                .values(splits)
+                .execute(c)?;
+            Self::find_splits(c, user_id, transaction_id)
+        })
+    }
+    pub fn delete_transaction(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        let Some(transaction) = Self::find_transaction(c, user_id, id)? else {
+            return Ok(0);
+        };
+        let deleted = diesel::delete(
+            transactions::table
+                .find(id)
+                .filter(transactions::user_id.eq(user_id)),
+        )
+        .execute(c)?;
+        ClassifierRepository::learn(c, &transaction, -1)?;
+        Ok(deleted)
+    }
+    /// Ids of the user's transactions matching `search`, oldest first, at
+    /// most `limit` of them.
+    pub fn find_matching_ids(
+        c: &mut PgConnection,
+        user_id: i32,
+        search: &TransactionQuery,
+        limit: i64,
+    ) -> QueryResult<Vec<i32>> {
+        let filter = ListFilter {
+            limit,
+            sort: SortField::Date,
+            direction: SortDirection::Asc,
+            cursor: None,
+            from: None,
+            to: None,
+            min_amount: None,
+            max_amount: None,
+            category: None,
+            tag: None,
+        };
+        Self::matching(c, user_id, &filter, Some(search), &[])?
+            .select(transactions::id)
+            .order((transactions::date.asc(), transactions::id.asc()))
+            .limit(limit)
+            .load::<i32>(c)
+    }
+    /// Applies `operation` to the transactions `ids` in one database
+    /// transaction, which is rolled back unless it succeeds on all of them.
+    pub fn apply_bulk(
+        c: &mut PgConnection,
+        user_id: i32,
+        ids: &[i32],
+        operation: &BulkOperation,
+    ) -> QueryResult<BulkReport> {
+        let mut rows = Vec::new();
+        let mut attachments = Vec::new();
+        let result = c.transaction(|c| {
+            for id in ids {
+                rows.push(Self::apply_bulk_row(c, user_id, *id, operation, &mut attachments)?);
+            }
+            if rows.iter().any(|row| row.error.is_some()) {
+                return Err(diesel::result::Error::RollbackTransaction);
+            }
+            Ok(())
+        });
+        let committed = match result {
+            Ok(()) => true,
+            Err(diesel::result::Error::RollbackTransaction) => false,
+            Err(err) => return Err(err),
+        };
+        let count = |status: &str| rows.iter().filter(|row| row.status == status).count();
+        Ok(BulkReport {
+            committed,
+            matched: ids.len(),
+            updated: count("updated"),
+            deleted: count("deleted"),
+            failed: count("failed"),
+            attachments: if committed { attachments } else { Vec::new() },
+            rows,
+        })
+    }
+    fn apply_bulk_row(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+        operation: &BulkOperation,
+        attachments: &mut Vec<Attachment>,
+    ) -> QueryResult<BulkRowReport> {
+        let Some(transaction) = Self::find_transaction(c, user_id, id)? else {
+            return Ok(BulkRowReport::failed(id, "transaction not found"));
+        };
+        let mut update = UpdateTransaction {
+            amount: transaction.amount.clone(),
+            category: transaction.category.clone(),
+            description: transaction.description.clone(),
+            date: transaction.date,
+            category_id: transaction.category_id,
+            account_id: transaction.account_id,
+        };
+        match operation {
+            BulkOperation::SetCategory {
+                category,
+                category_id,
+            } => {
+                if !Self::find_splits(c, user_id, id)?.is_empty() {
+                    return Ok(BulkRowReport::failed(
+                        id,
+                        "the transaction is split across categories",
+                    ));
+                }
+                update.category = category.clone();
+                update.category_id = *category_id;
+            }
+            BulkOperation::AppendDescription { text } => {
+                update.description = Some(match &transaction.description {
+                    Some(description) => format!("{} {}", description, text.trim()),
+                    None => text.trim().to_owned(),
+                });
+            }
+            BulkOperation::ShiftDate { days } => {
+                let shifted = if *days < 0 {
+                    transaction.date.checked_sub_days(Days::new(days.unsigned_abs()))
+                } else {
+                    transaction.date.checked_add_days(Days::new(days.unsigned_abs()))
+                };
+                let Some(date) = shifted else {
+                    return Ok(BulkRowReport::failed(id, "the date would be out of range"));
+                };
+                update.date = date;
+            }
+            BulkOperation::Delete => {
+                attachments.extend(AttachmentRepository::find_attachments(c, user_id, id)?);
+                Self::delete_transaction(c, user_id, id)?;
+                return Ok(BulkRowReport {
+                    id,
+                    status: "deleted",
+                    transaction: None,
+                    error: None,
+                });
+            }
+        }
+        Ok(BulkRowReport {
+            id,
+            status: "updated",
+            transaction: Self::update_transaction(c, user_id, id, update)?,
+            error: None,
+        })
+    }
+}
+
+pub struct ClassifierRepository;
+
+impl ClassifierRepository {
+    fn is_trained(c: &mut PgConnection, user_id: i32) -> QueryResult<bool> {
+        let trained_at = users::table
+            .find(user_id)
+            .select(users::classifier_trained_at)
+            .get_result::<Option<NaiveDateTime>>(c)
+            .optional()?;
+        Ok(trained_at.flatten().is_some())
+    }
+    /// Adds a categorized transaction to what its user's classifier learned
+    /// (`weight` 1) or takes it back out (`weight` -1). Does nothing before
+    /// the classifier is first trained, as training reads the whole history.
+    pub fn learn(c: &mut PgConnection, transaction: &Transaction, weight: i32) -> QueryResult<()> {
+        let (Some(user_id), Some(category_id)) = (transaction.user_id, transaction.category_id)
+        else {
+            return Ok(());
+        };
+        if !Self::is_trained(c, user_id)? {
+            return Ok(());
+        }
+
+        let features = features(transaction.description.as_deref(), &transaction.amount);
+        let tokens: i32 = features.values().sum();
+        diesel::insert_into(classifier_categories::table)
+            .values((
+                classifier_categories::user_id.eq(user_id),
+                classifier_categories::category_id.eq(category_id),
+                classifier_categories::documents.eq(weight),
+                classifier_categories::tokens.eq(tokens * weight),
+            ))
+            .on_conflict((classifier_categories::user_id, classifier_categories::category_id))
+            .do_update()
             .set((
-                transactions::amount.eq(update.amount.to_owned()),
-                transactions::description.eq(update.description.to_owned()),
-                transactions::category.eq(update.category.to_owned()),
-                transactions::date.eq(update.date.to_owned()),
+                classifier_categories::documents.eq(
+                    classifier_categories::documents + excluded(classifier_categories::documents),
+                ),
+                classifier_categories::tokens
+                    .eq(classifier_categories::tokens + excluded(classifier_categories::tokens)),
             ))
             .execute(c)?;
-        Self::find_transaction(c, id)
+        let rows: Vec<_> = features
+            .into_iter()
+            .map(|(token, count)| {
+                (
+                    classifier_tokens::user_id.eq(user_id),
+                    classifier_tokens::category_id.eq(category_id),
+                    classifier_tokens::token.eq(token),
+                    classifier_tokens::count.eq(count * weight),
+                )
+            })
+            .collect();
+        diesel::insert_into(classifier_tokens::table)
+            .values(&rows)
+            .on_conflict((
+                classifier_tokens::user_id,
+                classifier_tokens::token,
+                classifier_tokens::category_id,
+            ))
+            .do_update()
+            .set(
+                classifier_tokens::count
+                    .eq(classifier_tokens::count + excluded(classifier_tokens::count)),
+            )
+            .execute(c)?;
+
+        if weight < 0 {
+            diesel::delete(
+                classifier_tokens::table
+                    .filter(classifier_tokens::user_id.eq(user_id))
+                    .filter(classifier_tokens::category_id.eq(category_id))
+                    .filter(classifier_tokens::count.le(0)),
+            )
+            .execute(c)?;
+            diesel::delete(
+                classifier_categories::table
+                    .filter(classifier_categories::user_id.eq(user_id))
+                    .filter(classifier_categories::category_id.eq(category_id))
+                    .filter(classifier_categories::documents.le(0)),
+            )
+            .execute(c)?;
+        }
+        Ok(())
+    }
+    /// Relearns a user's classifier from all of their categorized transactions.
+    pub fn train(c: &mut PgConnection, user_id: i32) -> QueryResult<()> {
+        c.transaction(|c| {
+            diesel::delete(classifier_tokens::table.filter(classifier_tokens::user_id.eq(user_id)))
+                .execute(c)?;
+            diesel::delete(
+                classifier_categories::table.filter(classifier_categories::user_id.eq(user_id)),
+            )
+            .execute(c)?;
+
+            let transactions = transactions::table
+                .filter(transactions::user_id.eq(user_id))
+                .filter(transactions::category_id.is_not_null())
+                .load::<Transaction>(c)?;
+            let mut categories: HashMap<i32, (i32, i32)> = HashMap::new();
+            let mut tokens: HashMap<(i32, String), i32> = HashMap::new();
+            for transaction in transactions {
+                let Some(category_id) = transaction.category_id else {
+                    continue;
+                };
+                let features = features(transaction.description.as_deref(), &transaction.amount);
+                let (documents, total) = categories.entry(category_id).or_default();
+                *documents += 1;
+                for (token, count) in features {
+                    *total += count;
+                    *tokens.entry((category_id, token)).or_default() += count;
+                }
+            }
+
+            let rows: Vec<_> = categories
+                .into_iter()
+                .map(|(category_id, (documents, total))| {
+                    (
+                        classifier_categories::user_id.eq(user_id),
+                        classifier_categories::category_id.eq(category_id),
+                        classifier_categories::documents.eq(documents),
+                        classifier_categories::tokens.eq(total),
+                    )
+                })
+                .collect();
+            // stay below the bind parameter limit of a single statement
+            for chunk in rows.chunks(10_000) {
+                diesel::insert_into(classifier_categories::table)
+                    .values(chunk)
+                    .execute(c)?;
+            }
+            let rows: Vec<_> = tokens
+                .into_iter()
+                .map(|((category_id, token), count)| {
+                    (
+                        classifier_tokens::user_id.eq(user_id),
+                        classifier_tokens::category_id.eq(category_id),
+                        classifier_tokens::token.eq(token),
+                        classifier_tokens::count.eq(count),
+                    )
+                })
+                .collect();
+            for chunk in rows.chunks(10_000) {
+                diesel::insert_into(classifier_tokens::table)
+                    .values(chunk)
+                    .execute(c)?;
+            }
+
+            diesel::update(users::table.find(user_id))
+                .set(users::classifier_trained_at.eq(diesel::dsl::now))
+                .execute(c)?;
+            Ok(())
+        })
+    }
+    /// Has the user's classifier retrained on the next suggestion, after a
+    /// change that recategorizes many transactions at once.
+    pub fn invalidate(c: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+        diesel::update(users::table.find(user_id))
+            .set(users::classifier_trained_at.eq(None::<NaiveDateTime>))
+            .execute(c)
+    }
+    /// The categories most likely for `transaction`, leaving the transaction
+    /// itself out of what the classifier learned. Trains the classifier
+    /// first when needed.
+    pub fn suggest(
+        c: &mut PgConnection,
+        user_id: i32,
+        transaction: &Transaction,
+        limit: usize,
+    ) -> QueryResult<Vec<CategorySuggestion>> {
+        let features = features(transaction.description.as_deref(), &transaction.amount);
+        Self::suggest_for(c, user_id, &features, transaction.category_id, limit)
+    }
+    /// The categories most likely for a transaction that is not stored yet.
+    pub fn suggest_new(
+        c: &mut PgConnection,
+        user_id: i32,
+        transaction: &NewTransaction,
+        limit: usize,
+    ) -> QueryResult<Vec<CategorySuggestion>> {
+        let features = features(transaction.description.as_deref(), &transaction.amount);
+        Self::suggest_for(c, user_id, &features, None, limit)
+    }
+    /// The categories most likely for `features`, leaving a transaction
+    /// learned in category `learned` out.
+    fn suggest_for(
+        c: &mut PgConnection,
+        user_id: i32,
+        features: &HashMap<String, i32>,
+        learned: Option<i32>,
+        limit: usize,
+    ) -> QueryResult<Vec<CategorySuggestion>> {
+        if !Self::is_trained(c, user_id)? {
+            Self::train(c, user_id)?;
+        }
+
+        let mut categories: Vec<CategoryCounts> = classifier_categories::table
+            .filter(classifier_categories::user_id.eq(user_id))
+            .select((
+                classifier_categories::category_id,
+                classifier_categories::documents,
+                classifier_categories::tokens,
+            ))
+            .load::<(i32, i32, i32)>(c)?
+            .into_iter()
+            .map(|(category_id, documents, tokens)| CategoryCounts {
+                category_id,
+                documents,
+                tokens,
+                features: HashMap::new(),
+            })
+            .collect();
+        let counts = classifier_tokens::table
+            .filter(classifier_tokens::user_id.eq(user_id))
+            .filter(classifier_tokens::token.eq_any(features.keys()))
+            .select((
+                classifier_tokens::category_id,
+                classifier_tokens::token,
+                classifier_tokens::count,
+            ))
+            .load::<(i32, String, i32)>(c)?;
+        for (category_id, token, count) in counts {
+            if let Some(category) = categories
+                .iter_mut()
+                .find(|category| category.category_id == category_id)
+            {
+                category.features.insert(token, count);
+            }
+        }
+        if let Some(category) = categories
+            .iter_mut()
+            .find(|category| Some(category.category_id) == learned)
+        {
+            category.remove(features);
+        }
+        let vocabulary: i64 = classifier_tokens::table
+            .filter(classifier_tokens::user_id.eq(user_id))
+            .select(diesel::dsl::count_distinct(classifier_tokens::token))
+            .get_result(c)?;
+
+        let ranked: Vec<(i32, f64)> = rank(&categories, features, vocabulary)
+            .into_iter()
+            .take(limit)
+            .collect();
+        let ids: Vec<i32> = ranked.iter().map(|(category_id, _)| *category_id).collect();
+        let names: HashMap<i32, String> = categories::table
+            .filter(categories::user_id.eq(user_id))
+            .filter(categories::id.eq_any(ids))
+            .select((categories::id, categories::name))
+            .load::<(i32, String)>(c)?
+            .into_iter()
+            .collect();
+        Ok(ranked
+            .into_iter()
+            .filter_map(|(category_id, confidence)| {
+                Some(CategorySuggestion {
+                    category_id,
+                    category: names.get(&category_id)?.clone(),
+                    confidence: (confidence * 1000.0).round() / 1000.0,
+                })
+            })
+            .collect())
+    }
+}
+
+pub struct PayeeRepository;
+
+impl PayeeRepository {
+    /// Creates a payee together with an alias for its name.
+    pub fn create_payee(c: &mut PgConnection, record: NewPayee) -> QueryResult<Payee> {
+        c.transaction(|c| {
+            let payee: Payee = diesel::insert_into(payees::table)
+                .values(record)
+                .get_result(c)?;
+            if let Some(alias) = normalize(&payee.name) {
+                Self::insert_alias(c, payee.user_id, payee.id, &alias, false)?;
+            }
+            Self::relink(c, payee.user_id)?;
+            Ok(payee)
+        })
     }
-    pub fn delete_transaction(c: &mut PgConnection, id: i32) -> QueryResult<usize> {
-        diesel::delete(transactions::table.find(id)).execute(c)
+    pub fn find_payees(c: &mut PgConnection, user_id: i32) -> QueryResult<Vec<Payee>> {
+        payees::table
+            .filter(payees::user_id.eq(user_id))
+            .order((payees::name.asc(), payees::id.asc()))
+            .load::<Payee>(c)
+    }
+    pub fn find_payee(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<Option<Payee>> {
+        payees::table
+            .find(id)
+            .filter(payees::user_id.eq(user_id))
+            .get_result::<Payee>(c)
+            .optional()
+    }
+    pub fn find_aliases(
+        c: &mut PgConnection,
+        user_id: i32,
+        payee_id: i32,
+    ) -> QueryResult<Vec<PayeeAlias>> {
+        payee_aliases::table
+            .filter(payee_aliases::user_id.eq(user_id))
+            .filter(payee_aliases::payee_id.eq(payee_id))
+            .order(payee_aliases::alias.asc())
+            .load::<PayeeAlias>(c)
+    }
+    /// Renames a payee or changes its default category. The new name becomes
+    /// an alias unless another payee already has it.
+    pub fn update_payee(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+        record: NewPayee,
+    ) -> QueryResult<Option<Payee>> {
+        c.transaction(|c| {
+            diesel::update(payees::table.find(id).filter(payees::user_id.eq(user_id)))
+                .set((
+                    payees::name.eq(&record.name),
+                    payees::category.eq(&record.category),
+                    payees::category_id.eq(record.category_id),
+                ))
+                .execute(c)?;
+            if let Some(alias) = normalize(&record.name) {
+                Self::insert_alias(c, user_id, id, &alias, false)?;
+                Self::relink(c, user_id)?;
+            }
+            Self::find_payee(c, user_id, id)
+        })
+    }
+    /// Adds `alias`, already normalized, to a payee, taking it from the payee
+    /// that had it before, and moves the transactions it matches over.
+    pub fn add_alias(
+        c: &mut PgConnection,
+        user_id: i32,
+        payee_id: i32,
+        alias: &str,
+    ) -> QueryResult<PayeeAlias> {
+        c.transaction(|c| {
+            let alias = Self::insert_alias(c, user_id, payee_id, alias, true)?;
+            Self::relink(c, user_id)?;
+            alias.ok_or(diesel::result::Error::NotFound)
+        })
+    }
+    /// Inserts an alias, moving it when `take` is set and leaving it with
+    /// its payee otherwise.
+    fn insert_alias(
+        c: &mut PgConnection,
+        user_id: i32,
+        payee_id: i32,
+        alias: &str,
+        take: bool,
+    ) -> QueryResult<Option<PayeeAlias>> {
+        let insert = diesel::insert_into(payee_aliases::table).values((
+            payee_aliases::user_id.eq(user_id),
+            payee_aliases::payee_id.eq(payee_id),
+            payee_aliases::alias.eq(alias),
+        ));
+        let conflict = insert.on_conflict((payee_aliases::user_id, payee_aliases::alias));
+        if take {
+            conflict
+                .do_update()
+                .set(payee_aliases::payee_id.eq(payee_id))
+                .get_result::<PayeeAlias>(c)
+                .optional()
+        } else {
+            conflict
+                .do_nothing()
+                .get_result::<PayeeAlias>(c)
+                .optional()
+        }
+    }
+    pub fn delete_alias(
+        c: &mut PgConnection,
+        user_id: i32,
+        payee_id: i32,
+        id: i32,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            payee_aliases::table
+                .find(id)
+                .filter(payee_aliases::user_id.eq(user_id))
+                .filter(payee_aliases::payee_id.eq(payee_id)),
+        )
+        .execute(c)
+    }
+    /// Points every transaction whose description belongs to an alias at
+    /// that alias's payee. Transactions no alias matches keep their payee.
+    fn relink(c: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+        let aliases = Self::find_all_aliases(c, user_id)?;
+        let transactions = transactions::table
+            .filter(transactions::user_id.eq(user_id))
+            .filter(transactions::description.is_not_null())
+            .select((
+                transactions::id,
+                transactions::description,
+                transactions::payee_id,
+            ))
+            .load::<(i32, Option<String>, Option<i32>)>(c)?;
+
+        let mut moves: HashMap<i32, Vec<i32>> = HashMap::new();
+        for (id, description, payee_id) in transactions {
+            let Some(key) = description.as_deref().and_then(normalize) else {
+                continue;
+            };
+            if let Some(alias) = best_match(&key, &aliases, |alias| &alias.alias) {
+                if payee_id != Some(alias.payee_id) {
+                    moves.entry(alias.payee_id).or_default().push(id);
+                }
+            }
+        }
+        let mut moved = 0;
+        for (payee_id, ids) in moves {
+            moved += diesel::update(transactions::table.filter(transactions::id.eq_any(ids)))
+                .set(transactions::payee_id.eq(payee_id))
+                .execute(c)?;
+        }
+        Ok(moved)
+    }
+    /// Every alias of the user's payees, to match many descriptions against
+    /// without loading them each time.
+    pub fn find_all_aliases(c: &mut PgConnection, user_id: i32) -> QueryResult<Vec<PayeeAlias>> {
+        payee_aliases::table
+            .filter(payee_aliases::user_id.eq(user_id))
+            .load::<PayeeAlias>(c)
+    }
+    /// The alias among `aliases` a description belongs to, if any.
+    pub fn match_description<'a>(
+        aliases: &'a [PayeeAlias],
+        description: &str,
+    ) -> Option<&'a PayeeAlias> {
+        let key = normalize(description)?;
+        best_match(&key, aliases, |alias| &alias.alias)
+    }
+    /// The payee a description belongs to among the payees of `aliases`, if
+    /// any.
+    pub fn find_for_description(
+        c: &mut PgConnection,
+        user_id: i32,
+        aliases: &[PayeeAlias],
+        description: &str,
+    ) -> QueryResult<Option<Payee>> {
+        match Self::match_description(aliases, description) {
+            Some(alias) => Self::find_payee(c, user_id, alias.payee_id),
+            None => Ok(None),
+        }
+    }
+    /// Spending at a payee between `from` and `to` (both optional), in total
+    /// and per month.
+    pub fn summary(
+        c: &mut PgConnection,
+        user_id: i32,
+        payee: Payee,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> QueryResult<PayeeSummary> {
+        let aliases = Self::find_aliases(c, user_id, payee.id)?;
+        let mut query = transactions::table
+            .filter(transactions::user_id.eq(user_id))
+            .filter(transactions::payee_id.eq(payee.id))
+            .into_boxed();
+        if let Some(from) = from {
+            query = query.filter(transactions::date.ge(from));
+        }
+        if let Some(to) = to {
+            query = query.filter(transactions::date.le(to));
+        }
+        let transactions = query
+            .order((transactions::date.asc(), transactions::id.asc()))
+            .load::<Transaction>(c)?;
+
+        let mut months: Vec<MonthlySpending> = Vec::new();
+        let mut total = BigDecimal::from(0);
+        for transaction in &transactions {
+            total += &transaction.amount;
+            let month = transaction.date.format("%Y-%m").to_string();
+            match months.last_mut() {
+                Some(spending) if spending.month == month => {
+                    spending.count += 1;
+                    spending.total += &transaction.amount;
+                }
+                _ => months.push(MonthlySpending {
+                    month,
+                    count: 1,
+                    total: transaction.amount.clone(),
+                }),
+            }
+        }
+        let count = transactions.len() as i64;
+        let average = if count == 0 {
+            BigDecimal::from(0)
+        } else {
+            (&total / BigDecimal::from(count)).round(2)
+        };
+        Ok(PayeeSummary {
+            payee,
+            aliases,
+            count,
+            total,
+            average,
+            first_date: transactions.first().map(|transaction| transaction.date),
+            last_date: transactions.last().map(|transaction| transaction.date),
+            months,
+        })
+    }
+    pub fn delete_payee(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(payees::table.find(id).filter(payees::user_id.eq(user_id))).execute(c)
+    }
+}
+
+/// What a set of tags is attached to.
+#[derive(Clone, Copy)]
+pub enum Tagged {
+    Transaction(i32),
+    Income(i32),
+    Goal(i32),
+}
+
+pub struct TagRepository;
+
+impl TagRepository {
+    pub fn create_tag(c: &mut PgConnection, record: NewTag) -> QueryResult<Tag> {
+        diesel::insert_into(tags::table)
+            .values(record)
+            .get_result(c)
+    }
+    pub fn find_tags(c: &mut PgConnection, user_id: i32) -> QueryResult<Vec<Tag>> {
+        tags::table
+            .filter(tags::user_id.eq(user_id))
+            .order(tags::name.asc())
+            .load::<Tag>(c)
+    }
+    pub fn find_tag(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<Option<Tag>> {
+        tags::table
+            .find(id)
+            .filter(tags::user_id.eq(user_id))
+            .get_result::<Tag>(c)
+            .optional()
+    }
+    pub fn rename_tag(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+        name: &str,
+    ) -> QueryResult<Option<Tag>> {
+        diesel::update(tags::table.find(id).filter(tags::user_id.eq(user_id)))
+            .set(tags::name.eq(name))
+            .get_result::<Tag>(c)
+            .optional()
+    }
+    pub fn delete_tag(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(tags::table.find(id).filter(tags::user_id.eq(user_id))).execute(c)
+    }
+    /// The tags of a transaction, income or goal, by name.
+    pub fn find_tagged(c: &mut PgConnection, user_id: i32, tagged: Tagged) -> QueryResult<Vec<Tag>> {
+        let query = tags::table
+            .filter(tags::user_id.eq(user_id))
+            .order(tags::name.asc())
+            .into_boxed();
+        let query = match tagged {
+            Tagged::Transaction(id) => query.filter(
+                tags::id.eq_any(
+                    transaction_tags::table
+                        .filter(transaction_tags::transaction_id.eq(id))
+                        .select(transaction_tags::tag_id),
+                ),
+            ),
+            Tagged::Income(id) => query.filter(
+                tags::id.eq_any(
+                    income_tags::table
+                        .filter(income_tags::income_id.eq(id))
+                        .select(income_tags::tag_id),
+                ),
+            ),
+            Tagged::Goal(id) => query.filter(
+                tags::id.eq_any(
+                    goal_tags::table
+                        .filter(goal_tags::goal_id.eq(id))
+                        .select(goal_tags::tag_id),
+                ),
+            ),
+        };
+        query.load::<Tag>(c)
+    }
+    /// Replaces the tags of a transaction, income or goal, creating the
+    /// tags the user does not have yet.
+    pub fn set_tags(
+        c: &mut PgConnection,
+        user_id: i32,
+        tagged: Tagged,
+        names: &[String],
+    ) -> QueryResult<Vec<Tag>> {
+        c.transaction(|c| {
+            let new_tags: Vec<_> = names
+                .iter()
+                .map(|name| (tags::user_id.eq(user_id), tags::name.eq(name)))
+                .collect();
+            diesel::insert_into(tags::table)
+                .values(&new_tags)
+                .on_conflict((tags::user_id, tags::name))
+                .do_nothing()
+                .execute(c)?;
+            let ids: Vec<i32> = tags::table
+                .filter(tags::user_id.eq(user_id))
+                .filter(tags::name.eq_any(names))
+                .select(tags::id)
+                .load(c)?;
+
+            match tagged {
+                Tagged::Transaction(id) => {
+                    diesel::delete(
+                        transaction_tags::table.filter(transaction_tags::transaction_id.eq(id)),
+                    )
+                    .execute(c)?;
+                    let rows: Vec<_> = ids
+                        .iter()
+                        .map(|tag_id| {
+                            (
+                                transaction_tags::transaction_id.eq(id),
+                                transaction_tags::tag_id.eq(tag_id),
+                            )
+                        })
+                        .collect();
+                    diesel::insert_into(transaction_tags::table)
+                        .values(&rows)
+                        .execute(c)?;
+                }
+                Tagged::Income(id) => {
+                    diesel::delete(income_tags::table.filter(income_tags::income_id.eq(id)))
+                        .execute(c)?;
+                    let rows: Vec<_> = ids
+                        .iter()
+                        .map(|tag_id| (income_tags::income_id.eq(id), income_tags::tag_id.eq(tag_id)))
+                        .collect();
+                    diesel::insert_into(income_tags::table)
+                        .values(&rows)
+                        .execute(c)?;
+                }
+                Tagged::Goal(id) => {
+                    diesel::delete(goal_tags::table.filter(goal_tags::goal_id.eq(id)))
+                        .execute(c)?;
+                    let rows: Vec<_> = ids
+                        .iter()
+                        .map(|tag_id| (goal_tags::goal_id.eq(id), goal_tags::tag_id.eq(tag_id)))
+                        .collect();
+                    diesel::insert_into(goal_tags::table)
+                        .values(&rows)
+                        .execute(c)?;
+                }
+            }
+            Self::find_tagged(c, user_id, tagged)
+        })
+    }
+    /// Spending and income of every tag between `from` and `to` (both
+    /// optional), with the spending per category.
+    pub fn report(
+        c: &mut PgConnection,
+        user_id: i32,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> QueryResult<Vec<TagReport>> {
+        let totals = diesel::sql_query(
+            "WITH spending AS (
+                SELECT transaction_tags.tag_id, COUNT(*) AS transactions,
+                    SUM(transactions.amount) AS spent
+                FROM transaction_tags
+                JOIN transactions ON transactions.id = transaction_tags.transaction_id
+                WHERE ($2::DATE IS NULL OR transactions.date >= $2)
+                    AND ($3::DATE IS NULL OR transactions.date <= $3)
+                GROUP BY transaction_tags.tag_id
+            ),
+            earnings AS (
+                SELECT income_tags.tag_id, SUM(income.amount) AS income
+                FROM income_tags
+                JOIN income ON income.id = income_tags.income_id
+                WHERE ($2::DATE IS NULL OR income.date >= $2)
+                    AND ($3::DATE IS NULL OR income.date <= $3)
+                GROUP BY income_tags.tag_id
+            )
+            SELECT tags.id AS tag_id, tags.name AS tag,
+                COALESCE(spending.transactions, 0) AS transactions,
+                COALESCE(spending.spent, 0) AS spent,
+                COALESCE(earnings.income, 0) AS income
+            FROM tags
+            LEFT JOIN spending ON spending.tag_id = tags.id
+            LEFT JOIN earnings ON earnings.tag_id = tags.id
+            WHERE tags.user_id = $1
+            ORDER BY tags.name",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Nullable<Date>, _>(from)
+        .bind::<Nullable<Date>, _>(to)
+        .load::<TagTotals>(c)?;
+        let categories = diesel::sql_query(
+            "SELECT transaction_tags.tag_id, transaction_lines.category_id,
+                transaction_lines.category, SUM(transaction_lines.amount) AS spent
+            FROM transaction_tags
+            JOIN transaction_lines
+                ON transaction_lines.transaction_id = transaction_tags.transaction_id
+            WHERE transaction_lines.user_id = $1
+                AND ($2::DATE IS NULL OR transaction_lines.date >= $2)
+                AND ($3::DATE IS NULL OR transaction_lines.date <= $3)
+            GROUP BY transaction_tags.tag_id, transaction_lines.category_id,
+                transaction_lines.category
+            ORDER BY spent DESC, transaction_lines.category",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Nullable<Date>, _>(from)
+        .bind::<Nullable<Date>, _>(to)
+        .load::<TagCategorySpending>(c)?;
+
+        let mut by_tag: HashMap<i32, Vec<TagCategorySpending>> = HashMap::new();
+        for spending in categories {
+            by_tag.entry(spending.tag_id).or_default().push(spending);
+        }
+        Ok(totals
+            .into_iter()
+            .map(|totals| TagReport {
+                net: &totals.income - &totals.spent,
+                categories: by_tag.remove(&totals.tag_id).unwrap_or_default(),
+                tag_id: totals.tag_id,
+                tag: totals.tag,
+                transactions: totals.transactions,
+                spent: totals.spent,
+                income: totals.income,
+            })
+            .collect())
+    }
+}
+
+pub struct SearchRepository;
+
+impl SearchRepository {
+    /// The user's transactions and income matching `filter`, best match
+    /// first. Excluded words are left out of the ranking, which would
+    /// otherwise rank every result near zero. Snippets are HTML: the matches
+    /// are wrapped in `<mark>` and the rest of the text is escaped.
+    pub fn search(
+        c: &mut PgConnection,
+        user_id: i32,
+        filter: &SearchFilter,
+    ) -> QueryResult<Vec<SearchResult>> {
+        diesel::sql_query(
+            "SELECT * FROM (
+                SELECT 'transaction' AS kind, id, date, amount, category, description,
+                    ts_rank(search_vector, querytree(query)::TSQUERY) AS rank,
+                    ts_headline('english', html_escape(CONCAT_WS(' ', description, category)),
+                        query, 'StartSel=<mark>, StopSel=</mark>') AS snippet
+                FROM transactions, to_tsquery('english', $2) AS query
+                WHERE $3 AND user_id = $1 AND search_vector @@ query
+                    AND ($5::DATE IS NULL OR date >= $5)
+                    AND ($6::DATE IS NULL OR date <= $6)
+                    AND ($7::NUMERIC IS NULL OR amount >= $7)
+                    AND ($8::NUMERIC IS NULL OR amount <= $8)
+                UNION ALL
+                SELECT 'income', id, date, amount, NULL, source,
+                    ts_rank(search_vector, querytree(query)::TSQUERY),
+                    ts_headline('english', html_escape(source), query,
+                        'StartSel=<mark>, StopSel=</mark>')
+                FROM income, to_tsquery('english', $2) AS query
+                WHERE $4 AND user_id = $1 AND search_vector @@ query
+                    AND ($5::DATE IS NULL OR date >= $5)
+                    AND ($6::DATE IS NULL OR date <= $6)
+                    AND ($7::NUMERIC IS NULL OR amount >= $7)
+                    AND ($8::NUMERIC IS NULL OR amount <= $8)
+            ) AS results
+            ORDER BY rank DESC, date DESC, id DESC
+            LIMIT $9",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Text, _>(&filter.query)
+        .bind::<Bool, _>(filter.transactions)
+        .bind::<Bool, _>(filter.income)
+        .bind::<Nullable<Date>, _>(filter.from)
+        .bind::<Nullable<Date>, _>(filter.to)
+        .bind::<Nullable<Numeric>, _>(&filter.min_amount)
+        .bind::<Nullable<Numeric>, _>(&filter.max_amount)
+        .bind::<BigInt, _>(filter.limit)
+        .load::<SearchResult>(c)
+    }
+}
+
+pub struct SavedViewRepository;
+
+impl SavedViewRepository {
+    pub fn create_view(c: &mut PgConnection, record: NewSavedView) -> QueryResult<SavedView> {
+        diesel::insert_into(saved_views::table)
+            .values(record)
+            .get_result(c)
+    }
+    /// The user's views, pinned ones first, or only the pinned ones.
+    pub fn find_views(
+        c: &mut PgConnection,
+        user_id: i32,
+        pinned_only: bool,
+    ) -> QueryResult<Vec<SavedView>> {
+        let mut query = saved_views::table
+            .filter(saved_views::user_id.eq(user_id))
+            .into_boxed();
+        if pinned_only {
+            query = query.filter(saved_views::pinned.eq(true));
+        }
+        query
+            .order((saved_views::pinned.desc(), saved_views::name.asc()))
+            .load::<SavedView>(c)
+    }
+    pub fn find_view(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<SavedView>> {
+        saved_views::table
+            .find(id)
+            .filter(saved_views::user_id.eq(user_id))
+            .get_result::<SavedView>(c)
+            .optional()
+    }
+    pub fn update_view(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+        record: NewSavedView,
+    ) -> QueryResult<Option<SavedView>> {
+        diesel::update(
+            saved_views::table
+                .find(id)
+                .filter(saved_views::user_id.eq(user_id)),
+        )
+        .set((
+            saved_views::name.eq(record.name),
+            saved_views::filters.eq(record.filters),
+            saved_views::pinned.eq(record.pinned),
+            saved_views::updated_at.eq(diesel::dsl::now),
+        ))
+        .get_result::<SavedView>(c)
+        .optional()
+    }
+    pub fn delete_view(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(
+            saved_views::table
+                .find(id)
+                .filter(saved_views::user_id.eq(user_id)),
+        )
+        .execute(c)
+    }
+}
+
+pub struct AttachmentRepository;
+
+impl AttachmentRepository {
+    pub fn create_attachment(
+        c: &mut PgConnection,
+        new_attachment: NewAttachment,
+    ) -> QueryResult<Attachment> {
+        diesel::insert_into(attachments::table)
+            .values(new_attachment)
+            .get_result(c)
+    }
+    pub fn find_attachments(
+        c: &mut PgConnection,
+        user_id: i32,
+        transaction_id: i32,
+    ) -> QueryResult<Vec<Attachment>> {
+        attachments::table
+            .filter(attachments::user_id.eq(user_id))
+            .filter(attachments::transaction_id.eq(transaction_id))
+            .order(attachments::id.asc())
+            .load::<Attachment>(c)
+    }
+    pub fn find_attachment(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<Attachment>> {
+        attachments::table
+            .find(id)
+            .filter(attachments::user_id.eq(user_id))
+            .first::<Attachment>(c)
+            .optional()
+    }
+    /// The attachment of a transaction with this content, if it has one.
+    pub fn find_by_hash(
+        c: &mut PgConnection,
+        user_id: i32,
+        transaction_id: i32,
+        sha256: &str,
+    ) -> QueryResult<Option<Attachment>> {
+        attachments::table
+            .filter(attachments::user_id.eq(user_id))
+            .filter(attachments::transaction_id.eq(transaction_id))
+            .filter(attachments::sha256.eq(sha256))
+            .first::<Attachment>(c)
+            .optional()
+    }
+    /// Whether any attachment, of any user, still has this content.
+    pub fn is_stored(c: &mut PgConnection, sha256: &str) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(
+            attachments::table.filter(attachments::sha256.eq(sha256)),
+        ))
+        .get_result(c)
+    }
+    pub fn delete_attachment(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<Attachment>> {
+        diesel::delete(
+            attachments::table
+                .find(id)
+                .filter(attachments::user_id.eq(user_id)),
+        )
+        .get_result::<Attachment>(c)
+        .optional()
+    }
+}
+
+pub struct RecurringRepository;
+
+impl RecurringRepository {
+    pub fn create_template(
+        c: &mut PgConnection,
+        record: NewRecurringTemplate,
+    ) -> QueryResult<RecurringTemplate> {
+        diesel::insert_into(recurring_templates::table)
+            .values(record)
+            .get_result(c)
+    }
+    pub fn find_templates(c: &mut PgConnection, user_id: i32) -> QueryResult<Vec<RecurringTemplate>> {
+        recurring_templates::table
+            .filter(recurring_templates::user_id.eq(user_id))
+            .order(recurring_templates::id)
+            .load::<RecurringTemplate>(c)
+    }
+    pub fn find_template(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<RecurringTemplate>> {
+        recurring_templates::table
+            .find(id)
+            .filter(recurring_templates::user_id.eq(user_id))
+            .get_result::<RecurringTemplate>(c)
+            .optional()
+    }
+    pub fn delete_template(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(
+            recurring_templates::table
+                .find(id)
+                .filter(recurring_templates::user_id.eq(user_id)),
+        )
+        .execute(c)
+    }
+    /// The dates of every template of the user between `from` and `to`, with
+    /// skips and edits applied, ordered by the date they will be posted on.
+    pub fn find_upcoming(
+        c: &mut PgConnection,
+        user_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> QueryResult<Vec<UpcomingOccurrence>> {
+        let templates = Self::find_templates(c, user_id)?;
+        let template_ids: Vec<i32> = templates.iter().map(|template| template.id).collect();
+        let occurrences: HashMap<(i32, NaiveDate), RecurringOccurrence> =
+            recurring_occurrences::table
+                .filter(recurring_occurrences::template_id.eq_any(template_ids))
+                .filter(recurring_occurrences::occurrence_date.between(from, to))
+                .load::<RecurringOccurrence>(c)?
+                .into_iter()
+                .map(|occurrence| ((occurrence.template_id, occurrence.occurrence_date), occurrence))
+                .collect();
+
+        let mut upcoming = Vec::new();
+        for template in &templates {
+            for occurrence_date in template.occurrences(from, to) {
+                let occurrence = occurrences.get(&(template.id, occurrence_date));
+                upcoming.push(UpcomingOccurrence {
+                    template_id: template.id,
+                    kind: template.kind.clone(),
+                    occurrence_date,
+                    date: occurrence
+                        .and_then(|occurrence| occurrence.date)
+                        .unwrap_or(occurrence_date),
+                    amount: occurrence
+                        .and_then(|occurrence| occurrence.amount.clone())
+                        .unwrap_or_else(|| template.amount.clone()),
+                    category: template.category.clone(),
+                    description: occurrence
+                        .and_then(|occurrence| occurrence.description.clone())
+                        .or_else(|| template.description.clone()),
+                    status: occurrence
+                        .map(|occurrence| occurrence.status.clone())
+                        .unwrap_or_else(|| OCCURRENCE_PENDING.to_owned()),
+                });
+            }
+        }
+        upcoming.sort_by_key(|occurrence| (occurrence.date, occurrence.template_id));
+        Ok(upcoming)
+    }
+    /// Skips or edits a single date of `template`. Returns `None` when that
+    /// date has already been posted.
+    pub fn change_occurrence(
+        c: &mut PgConnection,
+        template: &RecurringTemplate,
+        occurrence_date: NaiveDate,
+        status: &str,
+        edit: EditOccurrence,
+    ) -> QueryResult<Option<RecurringOccurrence>> {
+        c.transaction(|c| {
+            let existing = recurring_occurrences::table
+                .filter(recurring_occurrences::template_id.eq(template.id))
+                .filter(recurring_occurrences::occurrence_date.eq(occurrence_date))
+                .for_update()
+                .get_result::<RecurringOccurrence>(c)
+                .optional()?;
+            if existing
+                .as_ref()
+                .is_some_and(|occurrence| occurrence.status == OCCURRENCE_POSTED)
+            {
+                return Ok(None);
+            }
+
+            diesel::insert_into(recurring_occurrences::table)
+                .values(NewRecurringOccurrence {
+                    template_id: template.id,
+                    occurrence_date,
+                    status: status.to_owned(),
+                    amount: edit.amount,
+                    description: edit.description,
+                    date: edit.date,
+                })
+                .on_conflict((
+                    recurring_occurrences::template_id,
+                    recurring_occurrences::occurrence_date,
+                ))
+                .do_update()
+                .set((
+                    recurring_occurrences::status.eq(excluded(recurring_occurrences::status)),
+                    recurring_occurrences::amount.eq(excluded(recurring_occurrences::amount)),
+                    recurring_occurrences::description
+                        .eq(excluded(recurring_occurrences::description)),
+                    recurring_occurrences::date.eq(excluded(recurring_occurrences::date)),
+                ))
+                .get_result(c)
+                .map(Some)
+        })
+    }
+    /// Posts every date of every template (or only the user's) that is due
+    /// on `today` and has not been posted or skipped yet. Each date is
+    /// claimed in the same database transaction that posts it, so running
+    /// this concurrently or repeatedly never posts a date twice. A template
+    /// that fails is logged and left for the next run, so that it does not
+    /// hold up the others.
+    pub fn post_due(
+        c: &mut PgConnection,
+        user_id: Option<i32>,
+        today: NaiveDate,
+    ) -> QueryResult<usize> {
+        let mut query = recurring_templates::table
+            .filter(recurring_templates::start_date.le(today))
+            .into_boxed();
+        if let Some(user_id) = user_id {
+            query = query.filter(recurring_templates::user_id.eq(user_id));
+        }
+        let templates = query.load::<RecurringTemplate>(c)?;
+
+        let mut posted = 0;
+        for template in &templates {
+            match Self::post_template(c, template, today) {
+                Ok(count) => posted += count,
+                Err(error) => {
+                    log::error!("posting recurring template {} failed: {}", template.id, error)
+                }
+            }
+        }
+        Ok(posted)
+    }
+    /// Posts the dates of `template` that are due on `today`, oldest first.
+    fn post_template(
+        c: &mut PgConnection,
+        template: &RecurringTemplate,
+        today: NaiveDate,
+    ) -> QueryResult<usize> {
+        let occurrences: HashMap<NaiveDate, RecurringOccurrence> = recurring_occurrences::table
+            .filter(recurring_occurrences::template_id.eq(template.id))
+            .load::<RecurringOccurrence>(c)?
+            .into_iter()
+            .map(|occurrence| (occurrence.occurrence_date, occurrence))
+            .collect();
+
+        let mut due: Vec<NaiveDate> = template
+            .occurrences(template.start_date, today)
+            .into_iter()
+            .filter(|date| !occurrences.contains_key(date))
+            .collect();
+        // Edited dates are posted on their new date, which may be later
+        // or earlier than the scheduled one.
+        due.extend(
+            occurrences
+                .values()
+                .filter(|occurrence| occurrence.status == OCCURRENCE_PENDING)
+                .filter(|occurrence| occurrence.date.unwrap_or(occurrence.occurrence_date) <= today)
+                .map(|occurrence| occurrence.occurrence_date),
+        );
+        due.sort();
+        if due.is_empty() {
+            return Ok(0);
+        }
+
+        let aliases = PayeeRepository::find_all_aliases(c, template.user_id)?;
+        let mut posted = 0;
+        for occurrence_date in due {
+            if Self::post_occurrence(c, template, &aliases, occurrence_date, today)? {
+                posted += 1;
+            }
+        }
+        Ok(posted)
+    }
+    fn post_occurrence(
+        c: &mut PgConnection,
+        template: &RecurringTemplate,
+        aliases: &[PayeeAlias],
+        occurrence_date: NaiveDate,
+        today: NaiveDate,
+    ) -> QueryResult<bool> {
+        c.transaction(|c| {
+            let claimed = match diesel::insert_into(recurring_occurrences::table)
+                .values(NewRecurringOccurrence {
+                    template_id: template.id,
+                    occurrence_date,
+                    status: OCCURRENCE_POSTED.to_owned(),
+                    amount: None,
+                    description: None,
+                    date: None,
+                })
+                .on_conflict_do_nothing()
+                .get_result::<RecurringOccurrence>(c)
+                .optional()?
+            {
+                Some(occurrence) => Some(occurrence),
+                None => diesel::update(
+                    recurring_occurrences::table
+                        .filter(recurring_occurrences::template_id.eq(template.id))
+                        .filter(recurring_occurrences::occurrence_date.eq(occurrence_date))
+                        .filter(recurring_occurrences::status.eq(OCCURRENCE_PENDING)),
+                )
+                .set(recurring_occurrences::status.eq(OCCURRENCE_POSTED))
+                .get_result::<RecurringOccurrence>(c)
+                .optional()?,
+            };
+            let Some(occurrence) = claimed else {
+                return Ok(false);
+            };
+
+            let amount = occurrence.amount.unwrap_or_else(|| template.amount.clone());
+            let date = occurrence.date.unwrap_or(occurrence_date);
+            if template.kind == TEMPLATE_INCOME {
+                let income = IncomeRepository::create_income(
+                    c,
+                    NewIncome {
+                        user_id: Some(template.user_id),
+                        amount,
+                        source: template.category.clone(),
+                        date,
+                        external_id: None,
+                        account_id: template.account_id,
+                    },
+                )?;
+                diesel::update(recurring_occurrences::table.find(occurrence.id))
+                    .set(recurring_occurrences::income_id.eq(income.id))
+                    .execute(c)?;
+            } else {
+                let description = occurrence
+                    .description
+                    .or_else(|| template.description.clone());
+                let payee_id = description
+                    .as_deref()
+                    .and_then(|description| {
+                        PayeeRepository::match_description(aliases, description)
+                    })
+                    .map(|alias| alias.payee_id);
+                let transaction = TransactionsRepository::create_transaction(
+                    c,
+                    NewTransaction {
+                        user_id: Some(template.user_id),
+                        amount,
+                        category: template.category.clone(),
+                        description,
+                        date,
+                        category_id: template.category_id,
+                        external_id: None,
+                        payee_id,
+                        account_id: template.account_id,
+                    },
+                )?;
+                AlertRepository::check_thresholds(
+                    c,
+                    template.user_id,
+                    &transaction,
+                    today,
+                )?;
+                diesel::update(recurring_occurrences::table.find(occurrence.id))
+                    .set(recurring_occurrences::transaction_id.eq(transaction.id))
+                    .execute(c)?;
+            }
+            Ok(true)
+        })
+    }
+}
+
+pub struct ImportProfileRepository;
+
+impl ImportProfileRepository {
+    pub fn create_profile(
+        c: &mut PgConnection,
+        record: NewImportProfile,
+    ) -> QueryResult<ImportProfile> {
+        diesel::insert_into(import_profiles::table)
+            .values(record)
+            .get_result(c)
+    }
+    pub fn find_profiles(c: &mut PgConnection, user_id: i32) -> QueryResult<Vec<ImportProfile>> {
+        import_profiles::table
+            .filter(import_profiles::user_id.eq(user_id))
+            .order(import_profiles::name)
+            .load::<ImportProfile>(c)
+    }
+    pub fn find_profile(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<ImportProfile>> {
+        import_profiles::table
+            .find(id)
+            .filter(import_profiles::user_id.eq(user_id))
+            .get_result::<ImportProfile>(c)
+            .optional()
+    }
+    pub fn find_profile_by_name(
+        c: &mut PgConnection,
+        user_id: i32,
+        name: &str,
+    ) -> QueryResult<Option<ImportProfile>> {
+        import_profiles::table
+            .filter(import_profiles::user_id.eq(user_id))
+            .filter(import_profiles::name.eq(name))
+            .get_result::<ImportProfile>(c)
+            .optional()
+    }
+    pub fn delete_profile(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(
+            import_profiles::table
+                .find(id)
+                .filter(import_profiles::user_id.eq(user_id)),
+        )
+        .execute(c)
+    }
+}
+
+pub struct RuleRepository;
+
+impl RuleRepository {
+    /// Adds a rule after the user's existing rules.
+    pub fn create_rule(c: &mut PgConnection, mut record: NewCategoryRule) -> QueryResult<CategoryRule> {
+        c.transaction(|c| {
+            let last = category_rules::table
+                .filter(category_rules::user_id.eq(record.user_id))
+                .select(diesel::dsl::max(category_rules::position))
+                .first::<Option<i32>>(c)?;
+            record.position = last.map_or(0, |last| last + 1);
+            diesel::insert_into(category_rules::table)
+                .values(record)
+                .get_result(c)
+        })
+    }
+    pub fn find_rules(c: &mut PgConnection, user_id: i32) -> QueryResult<Vec<CategoryRule>> {
+        category_rules::table
+            .filter(category_rules::user_id.eq(user_id))
+            .order((category_rules::position.asc(), category_rules::id.asc()))
+            .load::<CategoryRule>(c)
+    }
+    pub fn find_rule(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<CategoryRule>> {
+        category_rules::table
+            .find(id)
+            .filter(category_rules::user_id.eq(user_id))
+            .get_result::<CategoryRule>(c)
+            .optional()
+    }
+    /// The user's rules ready to run, in the user's match mode.
+    pub fn find_rule_set(c: &mut PgConnection, user_id: i32) -> QueryResult<RuleSet> {
+        let mode = users::table
+            .find(user_id)
+            .select(users::rule_match_mode)
+            .first::<String>(c)?;
+        Ok(RuleSet::new(Self::find_rules(c, user_id)?, &mode))
+    }
+    pub fn set_match_mode(c: &mut PgConnection, user_id: i32, mode: &str) -> QueryResult<usize> {
+        diesel::update(users::table.find(user_id))
+            .set(users::rule_match_mode.eq(mode))
+            .execute(c)
+    }
+    /// Gives the rules in `ids` the positions of their index.
+    pub fn reorder(c: &mut PgConnection, user_id: i32, ids: &[i32]) -> QueryResult<Vec<CategoryRule>> {
+        c.transaction(|c| {
+            for (position, id) in ids.iter().enumerate() {
+                diesel::update(
+                    category_rules::table
+                        .find(id)
+                        .filter(category_rules::user_id.eq(user_id)),
+                )
+                .set(category_rules::position.eq(position as i32))
+                .execute(c)?;
+            }
+            Self::find_rules(c, user_id)
+        })
+    }
+    /// What running the rules on the transactions between `from` and `to`
+    /// (both optional) would change. Split transactions are left alone, as
+    /// their categories are on the splits.
+    pub fn preview_changes(
+        c: &mut PgConnection,
+        user_id: i32,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> QueryResult<Vec<RuleChange>> {
+        let rules = Self::find_rule_set(c, user_id)?;
+        let transactions = TransactionsRepository::find_in_range(c, user_id, from, to)?;
+        let split: HashSet<i32> = transaction_splits::table
+            .filter(transaction_splits::user_id.eq(user_id))
+            .select(transaction_splits::transaction_id)
+            .distinct()
+            .load::<i32>(c)?
+            .into_iter()
+            .collect();
+        Ok(transactions
+            .into_iter()
+            .filter(|transaction| !split.contains(&transaction.id))
+            .filter_map(|transaction| {
+                let outcome = rules.evaluate(
+                    &transaction.amount,
+                    transaction.date,
+                    transaction.description.as_deref(),
+                );
+                let (new_category, new_category_id) = match outcome.category {
+                    Some((category, id)) => (category, Some(id)),
+                    None => (transaction.category.clone(), transaction.category_id),
+                };
+                let new_description = outcome.description.or(transaction.description.clone());
+                if new_category_id == transaction.category_id
+                    && new_description == transaction.description
+                {
+                    return None;
+                }
+                Some(RuleChange {
+                    transaction_id: transaction.id,
+                    rule_ids: outcome.rule_ids,
+                    category: transaction.category,
+                    new_category,
+                    new_category_id,
+                    description: transaction.description,
+                    new_description,
+                })
+            })
+            .collect())
+    }
+    /// Makes the `changes`, moving a transaction whose description changes
+    /// to the payee of its new description.
+    pub fn apply_changes(
+        c: &mut PgConnection,
+        user_id: i32,
+        changes: &[RuleChange],
+    ) -> QueryResult<usize> {
+        c.transaction(|c| {
+            let aliases = PayeeRepository::find_all_aliases(c, user_id)?;
+            for change in changes {
+                let target = transactions::table
+                    .find(change.transaction_id)
+                    .filter(transactions::user_id.eq(user_id));
+                diesel::update(target)
+                    .set((
+                        transactions::category.eq(&change.new_category),
+                        transactions::category_id.eq(change.new_category_id),
+                        transactions::description.eq(&change.new_description),
+                    ))
+                    .execute(c)?;
+                if change.new_description == change.description {
+                    continue;
+                }
+                let payee_id = change
+                    .new_description
+                    .as_deref()
+                    .and_then(|description| {
+                        PayeeRepository::match_description(&aliases, description)
+                    })
+                    .map(|alias| alias.payee_id);
+                diesel::update(target)
+                    .set(transactions::payee_id.eq(payee_id))
+                    .execute(c)?;
+            }
+            if !changes.is_empty() {
+                ClassifierRepository::invalidate(c, user_id)?;
+            }
+            Ok(changes.len())
+        })
+    }
+    pub fn delete_rule(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(
+            category_rules::table
+                .find(id)
+                .filter(category_rules::user_id.eq(user_id)),
+        )
+        .execute(c)
     }
 }
 
@@ -128,31 +2949,124 @@ impl IncomeRepository {
             .values(record)
             .get_result(c)
     }
+    /// Incomes have no category, so `category` filters and sorts on `source`.
     pub fn find_multiple_income(
         c: &mut PgConnection,
-        limit: i64,
-    ) -> QueryResult<Option<Vec<Income>>> {
-        income::table.limit(limit).load::<Income>(c).optional()
+        user_id: i32,
+        filter: &ListFilter,
+    ) -> QueryResult<Page<Income>> {
+        let mut query = income::table
+            .filter(income::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(from) = filter.from {
+            query = query.filter(income::date.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(income::date.le(to));
+        }
+        if let Some(min_amount) = &filter.min_amount {
+            query = query.filter(income::amount.ge(min_amount.clone()));
+        }
+        if let Some(max_amount) = &filter.max_amount {
+            query = query.filter(income::amount.le(max_amount.clone()));
+        }
+        if let Some(source) = &filter.category {
+            query = query.filter(income::source.eq(source.clone()));
+        }
+        if let Some(tag) = &filter.tag {
+            let tagged = income_tags::table
+                .inner_join(tags::table)
+                .filter(tags::user_id.eq(user_id))
+                .filter(tags::name.eq(tag.clone()))
+                .select(income_tags::income_id);
+            query = query.filter(income::id.eq_any(tagged));
+        }
+
+        let cursor = filter.cursor.as_ref();
+        let query = match filter.sort {
+            SortField::Date => keyset!(
+                query,
+                income::date,
+                income::id,
+                filter.direction,
+                cursor.and_then(Cursor::date)
+            ),
+            SortField::Amount => keyset!(
+                query,
+                income::amount,
+                income::id,
+                filter.direction,
+                cursor.and_then(Cursor::amount)
+            ),
+            SortField::Category => keyset!(
+                query,
+                income::source,
+                income::id,
+                filter.direction,
+                cursor.and_then(Cursor::text)
+            ),
+        };
+        let rows = query.limit(filter.limit + 1).load::<Income>(c)?;
+        Ok(Page::from_rows(rows, filter))
     }
-    pub fn find_income(c: &mut PgConnection, id: i32) -> QueryResult<Option<Income>> {
-        income::table.find(id).get_result::<Income>(c).optional()
+    /// Every income between `from` and `to` (both optional), oldest first.
+    pub fn find_in_range(
+        c: &mut PgConnection,
+        user_id: i32,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> QueryResult<Vec<Income>> {
+        let mut query = income::table
+            .filter(income::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(from) = from {
+            query = query.filter(income::date.ge(from));
+        }
+        if let Some(to) = to {
+            query = query.filter(income::date.le(to));
+        }
+        query
+            .order((income::date.asc(), income::id.asc()))
+            .load::<Income>(c)
     }
-    pub fn delete_income(c: &mut PgConnection, id: i32) -> QueryResult<usize> {
-        diesel::delete(income::table.find(id)).execute(c)
+    /// Which of the bank ids in `external_ids` were already imported.
+    pub fn find_external_ids(
+        c: &mut PgConnection,
+        user_id: i32,
+        external_ids: &[String],
+    ) -> QueryResult<HashSet<String>> {
+        income::table
+            .filter(income::user_id.eq(user_id))
+            .filter(income::external_id.eq_any(external_ids))
+            .select(income::external_id.assume_not_null())
+            .load::<String>(c)
+            .map(|ids| ids.into_iter().collect())
+    }
+    pub fn find_income(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<Option<Income>> {
+        income::table
+            .find(id)
+            .filter(income::user_id.eq(user_id))
+            .get_result::<Income>(c)
+            .optional()
+    }
+    pub fn delete_income(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(income::table.find(id).filter(income::user_id.eq(user_id))).execute(c)
     }
     pub fn update_income(
         c: &mut PgConnection,
+        user_id: i32,
         id: i32,
         update: NewIncome    
     )-> QueryResult<Option<Income>>{
-        diesel::update(income::table.find(id))
+        diesel::update(income::table.find(id).filter(income::user_id.eq(user_id)))
             .set((
                 income::amount.eq(update.amount),
                 income::source.eq(update.source),
                 income::date.eq(update.date),
+                income::account_id.eq(update.account_id),
             ))
             .execute(c)?;
-        Self::find_income(c, id)
+        Self::find_income(c, user_id, id)
     }
 }
 
@@ -163,24 +3077,84 @@ impl GoalsRepository {
             .values(record)
             .get_result(c)
     }
+    /// Goals are listed by `deadline` and `goal_amount`; `category` filters
+    /// and sorts on `goal_description`.
     pub fn find_multiple_goals(
         c: &mut PgConnection,
-        limit: i64,
-    ) -> QueryResult<Option<Vec<Goals>>> {
-        goals::table.limit(limit).load::<Goals>(c).optional()
+        user_id: i32,
+        filter: &ListFilter,
+    ) -> QueryResult<Page<Goals>> {
+        let mut query = goals::table
+            .filter(goals::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(from) = filter.from {
+            query = query.filter(goals::deadline.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(goals::deadline.le(to));
+        }
+        if let Some(min_amount) = &filter.min_amount {
+            query = query.filter(goals::goal_amount.ge(min_amount.clone()));
+        }
+        if let Some(max_amount) = &filter.max_amount {
+            query = query.filter(goals::goal_amount.le(max_amount.clone()));
+        }
+        if let Some(description) = &filter.category {
+            query = query.filter(goals::goal_description.eq(description.clone()));
+        }
+        if let Some(tag) = &filter.tag {
+            let tagged = goal_tags::table
+                .inner_join(tags::table)
+                .filter(tags::user_id.eq(user_id))
+                .filter(tags::name.eq(tag.clone()))
+                .select(goal_tags::goal_id);
+            query = query.filter(goals::id.eq_any(tagged));
+        }
+
+        let cursor = filter.cursor.as_ref();
+        let query = match filter.sort {
+            SortField::Date => keyset!(
+                query,
+                goals::deadline,
+                goals::id,
+                filter.direction,
+                cursor.and_then(Cursor::date)
+            ),
+            SortField::Amount => keyset!(
+                query,
+                goals::goal_amount,
+                goals::id,
+                filter.direction,
+                cursor.and_then(Cursor::amount)
+            ),
+            SortField::Category => keyset!(
+                query,
+                goals::goal_description,
+                goals::id,
+                filter.direction,
+                cursor.and_then(Cursor::text)
+            ),
+        };
+        let rows = query.limit(filter.limit + 1).load::<Goals>(c)?;
+        Ok(Page::from_rows(rows, filter))
     }
-    pub fn find_goal(c: &mut PgConnection, id: i32) -> QueryResult<Option<Goals>> {
-        goals::table.find(id).get_result::<Goals>(c).optional()
+    pub fn find_goal(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<Option<Goals>> {
+        goals::table
+            .find(id)
+            .filter(goals::user_id.eq(user_id))
+            .get_result::<Goals>(c)
+            .optional()
     }
-    pub fn delete_goal(c: &mut PgConnection, id: i32) -> QueryResult<usize> {
-        diesel::delete(goals::table.find(id)).execute(c)
+    pub fn delete_goal(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(goals::table.find(id).filter(goals::user_id.eq(user_id))).execute(c)
     }
     pub fn update_goal(
         c: &mut PgConnection,
+        user_id: i32,
         id: i32,
         record: NewGoal,
     )-> QueryResult<Option<Goals>>{
-        diesel::update(goals::table.find(id))
+        diesel::update(goals::table.find(id).filter(goals::user_id.eq(user_id)))
             .set((
                 goals::goal_amount.eq(record.goal_amount),
                 goals::goal_description.eq(record.goal_description),
@@ -188,6 +3162,131 @@ impl GoalsRepository {
                 goals::saving.eq(record.saving),
             ))
             .execute(c)?;
-        Self::find_goal(c, id)
+        Self::find_goal(c, user_id, id)
+    }
+}
+
+pub struct AccountRepository;
+impl AccountRepository {
+    pub fn create_account(c: &mut PgConnection, record: NewAccount) -> QueryResult<Account> {
+        diesel::insert_into(accounts::table)
+            .values(record)
+            .get_result(c)
+    }
+    pub fn find_accounts(c: &mut PgConnection, user_id: i32) -> QueryResult<Vec<Account>> {
+        accounts::table
+            .filter(accounts::user_id.eq(user_id))
+            .order((accounts::name.asc(), accounts::id.asc()))
+            .load::<Account>(c)
+    }
+    pub fn find_account(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<Account>> {
+        accounts::table
+            .find(id)
+            .filter(accounts::user_id.eq(user_id))
+            .first::<Account>(c)
+            .optional()
+    }
+    pub fn find_account_by_name(
+        c: &mut PgConnection,
+        user_id: i32,
+        name: &str,
+    ) -> QueryResult<Option<Account>> {
+        accounts::table
+            .filter(accounts::user_id.eq(user_id))
+            .filter(accounts::name.eq(name.trim()))
+            .first::<Account>(c)
+            .optional()
+    }
+    pub fn update_account(
+        c: &mut PgConnection,
+        user_id: i32,
+        id: i32,
+        update: NewAccount,
+    ) -> QueryResult<Option<Account>> {
+        diesel::update(accounts::table.find(id).filter(accounts::user_id.eq(user_id)))
+            .set((
+                accounts::name.eq(update.name),
+                accounts::kind.eq(update.kind),
+                accounts::currency.eq(update.currency),
+                accounts::opening_balance.eq(update.opening_balance),
+            ))
+            .get_result::<Account>(c)
+            .optional()
+    }
+    /// Deletes an account; its transactions and income are kept, without
+    /// an account.
+    pub fn delete_account(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(accounts::table.find(id).filter(accounts::user_id.eq(user_id))).execute(c)
+    }
+    /// The balance of each of the user's accounts at the end of each of
+    /// `dates`, by account and then date. Income and transfers to an account
+    /// raise its balance; transactions and transfers from it lower it.
+    pub fn find_balances(
+        c: &mut PgConnection,
+        user_id: i32,
+        dates: &[NaiveDate],
+    ) -> QueryResult<Vec<Balance>> {
+        diesel::sql_query(
+            "SELECT accounts.id AS account_id, day AS date,
+                 accounts.opening_balance
+                 + COALESCE((SELECT SUM(income.amount) FROM income
+                     WHERE income.account_id = accounts.id
+                       AND income.user_id = accounts.user_id
+                       AND income.date <= day), 0)
+                 - COALESCE((SELECT SUM(transactions.amount) FROM transactions
+                     WHERE transactions.account_id = accounts.id
+                       AND transactions.user_id = accounts.user_id
+                       AND transactions.date <= day), 0)
+                 + COALESCE((SELECT SUM(transfers.amount) FROM transfers
+                     WHERE transfers.to_account_id = accounts.id
+                       AND transfers.date <= day), 0)
+                 - COALESCE((SELECT SUM(transfers.amount) FROM transfers
+                     WHERE transfers.from_account_id = accounts.id
+                       AND transfers.date <= day), 0) AS balance
+             FROM accounts CROSS JOIN UNNEST($2) AS days (day)
+             WHERE accounts.user_id = $1
+             ORDER BY accounts.id, day",
+        )
+        .bind::<Integer, _>(user_id)
+        .bind::<Array<Date>, _>(dates)
+        .load::<Balance>(c)
+    }
+}
+
+pub struct TransferRepository;
+impl TransferRepository {
+    pub fn create_transfer(c: &mut PgConnection, record: NewTransfer) -> QueryResult<Transfer> {
+        diesel::insert_into(transfers::table)
+            .values(record)
+            .get_result(c)
+    }
+    /// The user's transfers, newest first, only those from or to
+    /// `account_id` when given.
+    pub fn find_transfers(
+        c: &mut PgConnection,
+        user_id: i32,
+        account_id: Option<i32>,
+    ) -> QueryResult<Vec<Transfer>> {
+        let mut query = transfers::table
+            .filter(transfers::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(account_id) = account_id {
+            query = query.filter(
+                transfers::from_account_id
+                    .eq(account_id)
+                    .or(transfers::to_account_id.eq(account_id)),
+            );
+        }
+        query
+            .order((transfers::date.desc(), transfers::id.desc()))
+            .load::<Transfer>(c)
+    }
+    pub fn delete_transfer(c: &mut PgConnection, user_id: i32, id: i32) -> QueryResult<usize> {
+        diesel::delete(transfers::table.find(id).filter(transfers::user_id.eq(user_id)))
+            .execute(c)
     }
 }
@@ -0,0 +1,201 @@
+use crate::model::{balance_dates, AccountBalances, Balance, NewAccount, MAX_BALANCE_MONTHS};
+use crate::repositories::AccountRepository;
+use crate::{AuthenticatedUser, DBConnection};
+use chrono::Local;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::PgConnection;
+use rocket::{
+    delete, get,
+    http::Status,
+    post, put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Months of balance history of `GET /accounts` when none are asked for.
+const DEFAULT_BALANCE_MONTHS: u32 = 12;
+
+fn write_error(err: DieselError) -> Custom<Value> {
+    match err {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Custom(
+            Status::Conflict,
+            json!({"error": "an account with this name already exists"}),
+        ),
+        _ => Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        ),
+    }
+}
+
+/// Checks that `account_id`, when given, is one of the user's accounts.
+pub(crate) fn check_account(
+    c: &mut PgConnection,
+    user_id: i32,
+    account_id: Option<i32>,
+) -> Result<(), Custom<Value>> {
+    let Some(account_id) = account_id else {
+        return Ok(());
+    };
+    match AccountRepository::find_account(c, user_id, account_id) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(Custom(
+            Status::BadRequest,
+            json!({"error": format!("unknown account: {}", account_id)}),
+        )),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    }
+}
+
+#[post("/account", format = "json", data = "<new_account>")]
+pub async fn create_account(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_account: Json<NewAccount>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut account = new_account.into_inner();
+    account.user_id = auth.id;
+    if let Err(error) = account.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(
+        move |c| match AccountRepository::create_account(c, account) {
+            Ok(account) => Ok(Custom(Status::Created, json!({"message": account}))),
+            Err(err) => Err(write_error(err)),
+        },
+    )
+    .await
+}
+
+/// The user's accounts with their balance today and at the end of each of
+/// the last `months` months.
+#[get("/accounts?<months>")]
+pub async fn view_accounts(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    months: Option<u32>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let months = months.unwrap_or(DEFAULT_BALANCE_MONTHS);
+    if months > MAX_BALANCE_MONTHS {
+        return Err(Custom(
+            Status::BadRequest,
+            json!({"error": format!("months must be at most {}", MAX_BALANCE_MONTHS)}),
+        ));
+    }
+    let dates = balance_dates(Local::now().date_naive(), months);
+
+    db.run(move |c| {
+        let found = AccountRepository::find_accounts(c, auth.id).and_then(|accounts| {
+            let balances = AccountRepository::find_balances(c, auth.id, &dates)?;
+            Ok((accounts, balances))
+        });
+        let Ok((accounts, balances)) = found else {
+            return Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            ));
+        };
+
+        let mut histories: HashMap<i32, Vec<Balance>> = HashMap::new();
+        for balance in balances {
+            histories
+                .entry(balance.account_id)
+                .or_default()
+                .push(balance);
+        }
+        let accounts: Vec<AccountBalances> = accounts
+            .into_iter()
+            .map(|account| {
+                let mut history = histories.remove(&account.id).unwrap_or_default();
+                // the last date is today
+                let balance = history
+                    .pop()
+                    .map(|today| today.balance)
+                    .unwrap_or_else(|| account.opening_balance.clone());
+                AccountBalances {
+                    account,
+                    balance,
+                    history,
+                }
+            })
+            .collect();
+        Ok(Custom(Status::Ok, json!(accounts)))
+    })
+    .await
+}
+
+#[get("/account/<id>")]
+pub async fn view_account(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match AccountRepository::find_account(c, auth.id, id) {
+            Ok(Some(account)) => Ok(Custom(Status::Ok, json!(account))),
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error":"Account not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[put("/account/<id>", format = "json", data = "<account>")]
+pub async fn update_account(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    account: Json<NewAccount>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut account = account.into_inner();
+    account.user_id = auth.id;
+    if let Err(error) = account.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(
+        move |c| match AccountRepository::update_account(c, auth.id, id, account) {
+            Ok(Some(account)) => Ok(Custom(Status::Ok, json!({"message": account}))),
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error":"Account not found"}),
+            )),
+            Err(err) => Err(write_error(err)),
+        },
+    )
+    .await
+}
+
+#[delete("/account/<id>")]
+pub async fn delete_account(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match AccountRepository::delete_account(c, auth.id, id) {
+            Ok(0) => Err(Custom(
+                Status::NotFound,
+                json!({"error":"Account not found"}),
+            )),
+            Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Account deleted"}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
@@ -0,0 +1,59 @@
+use crate::repositories::AlertRepository;
+use crate::{AuthenticatedUser, DBConnection};
+use rocket::{
+    get,
+    http::Status,
+    put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Value},
+};
+
+#[get("/alerts?<unread>")]
+pub async fn view_alerts(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    unread: Option<bool>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match AlertRepository::find_alerts(c, auth.id, unread.unwrap_or(false)) {
+            Ok(alerts) => Ok(Custom(Status::Ok, json!(alerts))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[put("/alert/<id>/read")]
+pub async fn mark_alert_read(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match AlertRepository::mark_read(c, auth.id, id) {
+        Ok(Some(alert)) => Ok(Custom(Status::Ok, json!({"message": alert}))),
+        Ok(None) => Err(Custom(Status::NotFound, json!({"error": "Alert not found"}))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[put("/alerts/read")]
+pub async fn mark_alerts_read(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match AlertRepository::mark_all_read(c, auth.id) {
+        Ok(count) => Ok(Custom(Status::Ok, json!({"message": {"marked_read": count}}))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
@@ -0,0 +1,272 @@
+use crate::model::{Attachment, NewAttachment};
+use crate::repositories::{AttachmentRepository, TransactionsRepository};
+use crate::utils::attachments::{file_name, sha256, sniff, AttachmentStore, MAX_ATTACHMENT_SIZE};
+use crate::{AuthenticatedUser, DBConnection};
+use diesel::{PgConnection, QueryResult};
+use rocket::form::{Errors, Form};
+use rocket::fs::TempFile;
+use rocket::http::{ContentType, Header};
+use rocket::response::{self, Responder, Response};
+use rocket::tokio::fs::File;
+use rocket::tokio::io::AsyncReadExt;
+use rocket::{
+    delete, get, http::Status, post, response::status::Custom, serde::json::serde_json::json,
+    FromForm, Request, State,
+};
+use serde_json::Value;
+
+/// Body of `POST /transaction/<id>/attachments`: a `multipart/form-data`
+/// form with the file in its `file` field.
+#[derive(FromForm)]
+pub struct AttachmentUpload<'r> {
+    file: TempFile<'r>,
+}
+
+/// An attachment's file, streamed back with the type it was stored with.
+pub struct AttachmentFile {
+    file: File,
+    content_type: ContentType,
+    file_name: String,
+}
+
+impl<'r> Responder<'r, 'static> for AttachmentFile {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(self.content_type)
+            .header(Header::new(
+                "Content-Disposition",
+                format!("inline; filename=\"{}\"", self.file_name),
+            ))
+            .streamed_body(self.file)
+            .ok()
+    }
+}
+
+/// Removes the files of deleted attachments that no other attachment
+/// shares.
+pub(crate) fn remove_files(
+    c: &mut PgConnection,
+    store: &AttachmentStore,
+    attachments: &[Attachment],
+) -> QueryResult<()> {
+    for attachment in attachments {
+        if !AttachmentRepository::is_stored(c, &attachment.sha256)? {
+            if let Err(err) = store.remove(&attachment.sha256) {
+                log::error!("attachment {} was not removed: {}", attachment.sha256, err);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[post("/transaction/<id>/attachments", data = "<upload>")]
+pub async fn upload_attachment(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    store: &State<AttachmentStore>,
+    id: i32,
+    upload: Result<Form<AttachmentUpload<'_>>, Errors<'_>>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let too_large = || {
+        Custom(
+            Status::PayloadTooLarge,
+            json!({"error": format!("files over {} MiB cannot be attached", MAX_ATTACHMENT_SIZE)}),
+        )
+    };
+    let upload = match upload {
+        Ok(upload) => upload.into_inner(),
+        Err(errors) if errors.status() == Status::PayloadTooLarge => return Err(too_large()),
+        Err(_) => {
+            return Err(Custom(
+                Status::BadRequest,
+                json!({"error": "send the file as multipart/form-data in a `file` field"}),
+            ))
+        }
+    };
+    if upload.file.len() > MAX_ATTACHMENT_SIZE * 1024 * 1024 {
+        return Err(too_large());
+    }
+
+    let mut content = Vec::new();
+    let read = match upload.file.open().await {
+        Ok(mut file) => file.read_to_end(&mut content).await,
+        Err(err) => Err(err),
+    };
+    if read.is_err() {
+        return Err(Custom(
+            Status::BadRequest,
+            json!({"error": "the file could not be read"}),
+        ));
+    }
+    let Some(content_type) = sniff(&content) else {
+        return Err(Custom(
+            Status::UnsupportedMediaType,
+            json!({"error": "only images and PDF files can be attached"}),
+        ));
+    };
+    let name = file_name(
+        upload
+            .file
+            .raw_name()
+            .map(|name| name.dangerous_unsafe_unsanitized_raw().as_str()),
+    );
+    let sha256 = sha256(&content);
+    let store = store.inner().clone();
+
+    db.run(move |c| {
+        match TransactionsRepository::find_transaction(c, auth.id, id) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return Err(Custom(
+                    Status::NotFound,
+                    json!({"error": "transaction not found"}),
+                ))
+            }
+            Err(_) => {
+                return Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                ))
+            }
+        }
+        match AttachmentRepository::find_by_hash(c, auth.id, id, &sha256) {
+            Ok(Some(attachment)) => return Ok(Custom(Status::Ok, json!({"message": attachment}))),
+            Ok(None) => {}
+            Err(_) => {
+                return Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                ))
+            }
+        }
+        if let Err(err) = store.store(&sha256, &content) {
+            log::error!("attachment {} was not stored: {}", sha256, err);
+            return Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            ));
+        }
+
+        let attachment = NewAttachment {
+            user_id: auth.id,
+            transaction_id: id,
+            file_name: name,
+            content_type: content_type.to_owned(),
+            size: content.len() as i64,
+            sha256,
+        };
+        match AttachmentRepository::create_attachment(c, attachment) {
+            Ok(attachment) => Ok(Custom(Status::Created, json!({"message": attachment}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[get("/transaction/<id>/attachments")]
+pub async fn view_attachments(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match TransactionsRepository::find_transaction(c, auth.id, id) {
+            Ok(Some(_)) => match AttachmentRepository::find_attachments(c, auth.id, id) {
+                Ok(attachments) => Ok(Custom(Status::Ok, json!(attachments))),
+                Err(_) => Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                )),
+            },
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error": "transaction not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[get("/attachment/<id>")]
+pub async fn download_attachment(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    store: &State<AttachmentStore>,
+    id: i32,
+) -> Result<AttachmentFile, Custom<Value>> {
+    let attachment = db
+        .run(move |c| AttachmentRepository::find_attachment(c, auth.id, id))
+        .await;
+    let attachment = match attachment {
+        Ok(Some(attachment)) => attachment,
+        Ok(None) => {
+            return Err(Custom(
+                Status::NotFound,
+                json!({"error": "attachment not found"}),
+            ))
+        }
+        Err(_) => {
+            return Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            ))
+        }
+    };
+
+    match File::open(store.path(&attachment.sha256)).await {
+        Ok(file) => Ok(AttachmentFile {
+            file,
+            content_type: ContentType::parse_flexible(&attachment.content_type)
+                .unwrap_or(ContentType::Binary),
+            file_name: attachment.file_name,
+        }),
+        Err(err) => {
+            log::error!(
+                "attachment {} could not be read: {}",
+                attachment.sha256,
+                err
+            );
+            Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            ))
+        }
+    }
+}
+
+#[delete("/attachment/<id>")]
+pub async fn delete_attachment(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    store: &State<AttachmentStore>,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let store = store.inner().clone();
+    db.run(move |c| {
+        let deleted = AttachmentRepository::delete_attachment(c, auth.id, id).and_then(|deleted| {
+            if let Some(attachment) = &deleted {
+                remove_files(c, &store, std::slice::from_ref(attachment))?;
+            }
+            Ok(deleted)
+        });
+        match deleted {
+            Ok(Some(_)) => Ok(Custom(Status::Ok, json!({"message": "attachment deleted"}))),
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error": "attachment not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
@@ -1,5 +1,6 @@
-use crate::model::{NewBudget, UpdateBudget};
+use crate::model::{ListQuery, NewBudget, UpdateBudget};
 use crate::repositories::BudgetRepository;
+use crate::routes::category::resolve_category;
 use crate::{AuthenticatedUser, DBConnection};
 use rocket::{
     delete, get,
@@ -8,8 +9,25 @@ use rocket::{
     response::status::Custom,
     serde::json::{serde_json::json, Json},
 };
+use chrono::Local;
 use serde_json::Value;
 
+/// Makes sure every recurring budget of the user has a period covering today
+/// before budgets are read.
+async fn materialize_periods(db: &DBConnection, user_id: i32) -> Result<(), Custom<Value>> {
+    db.run(move |c| {
+        BudgetRepository::materialize_periods(c, Some(user_id), Local::now().date_naive())
+    })
+    .await
+    .map(|_| ())
+    .map_err(|_| {
+        Custom(
+            Status::InternalServerError,
+            json!({"error":"Something went wrong"}),
+        )
+    })
+}
+
 #[post("/budget", format = "json", data = "<new_budget>")]
 pub async fn create_budget(
     db: DBConnection,
@@ -21,9 +39,15 @@ pub async fn create_budget(
 
     match budget.validate() {
         Ok(()) => {
-            db.run(move |c| match BudgetRepository::create_budget(c, budget) {
-                Ok(budget_res) => Ok(Custom(Status::Created, json!({"message":budget_res}))),
-                Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
+            db.run(move |c| {
+                let category = resolve_category(c, auth.id, &budget.category)?;
+                budget.category_id = Some(category.id);
+                budget.category = category.name;
+
+                match BudgetRepository::create_budget(c, budget) {
+                    Ok(budget_res) => Ok(Custom(Status::Created, json!({"message":budget_res}))),
+                    Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
+                }
             })
             .await
         }
@@ -37,10 +61,12 @@ pub async fn create_budget(
 #[get("/budget/<id>")]
 pub async fn view_budget(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     id: i32,
 ) -> Result<Value, Custom<Value>> {
-    db.run(move |c| match BudgetRepository::find_budget(c, id) {
+    materialize_periods(&db, auth.id).await?;
+
+    db.run(move |c| match BudgetRepository::find_budget(c, auth.id, id) {
         Ok(Some(budget)) => Ok(json!(budget)),
         Ok(None) => Err(Custom(
             Status::NotFound,
@@ -54,18 +80,66 @@ pub async fn view_budget(
     .await
 }
 
-#[get("/budget")]
+#[get("/budget?<query..>")]
 pub async fn view_budgets(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
+    query: ListQuery,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let filter = match query.parse() {
+        Ok(filter) => filter,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+    materialize_periods(&db, auth.id).await?;
+
+    db.run(
+        move |c| match BudgetRepository::find_multiple_budgets(c, auth.id, &filter) {
+            Ok(page) => Ok(Custom(Status::Ok, json!(page))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[get("/budget/<id>/status")]
+pub async fn view_budget_status(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
 ) -> Result<Custom<Value>, Custom<Value>> {
+    materialize_periods(&db, auth.id).await?;
+
     db.run(
-        move |c| match BudgetRepository::find_multiple_budgets(c, 100) {
-            Ok(Some(budgets)) => Ok(Custom(Status::Ok, json!(budgets))),
-            Ok(None) => Err(Custom(
-                Status::NotFound,
-                json!({"error": "Budget not found"}),
+        move |c| match BudgetRepository::find_status(c, auth.id, Some(id)) {
+            Ok(mut status) => match status.pop() {
+                Some(status) => Ok(Custom(Status::Ok, json!(status))),
+                None => Err(Custom(
+                    Status::NotFound,
+                    json!({"error": "Budget not found"}),
+                )),
+            },
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"Something went wrong"}),
             )),
+        },
+    )
+    .await
+}
+
+#[get("/budgets/status")]
+pub async fn view_budgets_status(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+) -> Result<Custom<Value>, Custom<Value>> {
+    materialize_periods(&db, auth.id).await?;
+
+    db.run(
+        move |c| match BudgetRepository::find_status(c, auth.id, None) {
+            Ok(statuses) => Ok(Custom(Status::Ok, json!(statuses))),
             Err(_) => Err(Custom(
                 Status::InternalServerError,
                 json!({"error":"something went wrong"}),
@@ -79,15 +153,23 @@ pub async fn view_budgets(
 pub async fn update_budget(
     db: DBConnection,
     id: i32,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     budget: Json<UpdateBudget>,
 ) -> Result<Custom<Value>, Custom<Value>> {
-    db.run(move |c| match BudgetRepository::find_budget(c, id) {
+    let mut budget = budget.into_inner();
+
+    db.run(move |c| match BudgetRepository::find_budget(c, auth.id, id) {
         Ok(Some(_)) => match budget.validate() {
-            Ok(()) => match BudgetRepository::update_budget(c, id, budget.into_inner()) {
-                Ok(budget_res) => Ok(Custom(Status::Ok, json!({"message":budget_res}))),
-                Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
-            },
+            Ok(()) => {
+                let category = resolve_category(c, auth.id, &budget.category)?;
+                budget.category_id = Some(category.id);
+                budget.category = category.name;
+
+                match BudgetRepository::update_budget(c, auth.id, id, budget) {
+                    Ok(budget_res) => Ok(Custom(Status::Ok, json!({"message":budget_res}))),
+                    Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
+                }
+            }
             Err(errors) => Err(Custom(Status::BadRequest, json!({"error":errors}))),
         },
         Ok(None) => Err(Custom(
@@ -105,11 +187,11 @@ pub async fn update_budget(
 #[delete("/budget/<id>")]
 pub async fn delete_budget(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     id: i32,
 ) -> Result<Value, Custom<Value>> {
-    db.run(move |c| match BudgetRepository::find_budget(c, id) {
-        Ok(Some(_)) => match BudgetRepository::delete_budget(c, id) {
+    db.run(move |c| match BudgetRepository::find_budget(c, auth.id, id) {
+        Ok(Some(_)) => match BudgetRepository::delete_budget(c, auth.id, id) {
             Ok(_) => Ok(json!({"message":"budget deleted successfully"})),
             Err(err) => {
                 eprintln!("Error fetching budget: {:?}", err);
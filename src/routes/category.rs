@@ -0,0 +1,282 @@
+use crate::model::{
+    Category, CategoryNode, DateRangeQuery, MergeCategory, NewCategory, UpdateCategory,
+};
+use crate::repositories::{CategoryRepository, TransactionsRepository};
+use crate::{AuthenticatedUser, DBConnection};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::PgConnection;
+use rocket::{
+    delete, get,
+    http::Status,
+    post, put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+/// Resolves the category name (or `Parent > Child` path) sent with a
+/// transaction or budget to one of the user's categories.
+pub(crate) fn resolve_category(
+    c: &mut PgConnection,
+    user_id: i32,
+    name: &str,
+) -> Result<Category, Custom<Value>> {
+    match CategoryRepository::find_by_path(c, user_id, name) {
+        Ok(Some(category)) => Ok(category),
+        Ok(None) => Err(Custom(
+            Status::BadRequest,
+            json!({"error": format!("unknown category: {}", name)}),
+        )),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    }
+}
+
+/// Checks that `parent_id` is one of the user's categories and, when moving
+/// the existing category `id`, that it is not the category itself or one of
+/// its descendants.
+fn check_parent(
+    c: &mut PgConnection,
+    user_id: i32,
+    id: Option<i32>,
+    parent_id: Option<i32>,
+) -> Result<(), Custom<Value>> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+    let server_error = |_| {
+        Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )
+    };
+    match CategoryRepository::find_category(c, user_id, parent_id).map_err(server_error)? {
+        Some(_) => {}
+        None => {
+            return Err(Custom(
+                Status::BadRequest,
+                json!({"error": "parent category not found"}),
+            ))
+        }
+    }
+    if let Some(id) = id {
+        let descendants = CategoryRepository::descendant_ids(c, user_id, id).map_err(server_error)?;
+        if descendants.contains(&parent_id) {
+            return Err(Custom(
+                Status::BadRequest,
+                json!({"error": "a category cannot be moved below itself"}),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn write_error(err: DieselError) -> Custom<Value> {
+    match err {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Custom(
+            Status::Conflict,
+            json!({"error": "a category with this name already exists"}),
+        ),
+        DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => Custom(
+            Status::Conflict,
+            json!({"error": "category is still in use or has subcategories, merge it into another category instead"}),
+        ),
+        _ => Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        ),
+    }
+}
+
+#[post("/category", format = "json", data = "<new_category>")]
+pub async fn create_category(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_category: Json<NewCategory>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut category = new_category.into_inner();
+    category.user_id = auth.id;
+    category.name = category.name.trim().to_owned();
+
+    match category.validate() {
+        Ok(()) => {
+            db.run(move |c| {
+                check_parent(c, auth.id, None, category.parent_id)?;
+
+                match CategoryRepository::create_category(c, category) {
+                    Ok(category_res) => Ok(Custom(Status::Created, json!({"message": category_res}))),
+                    Err(err) => Err(write_error(err)),
+                }
+            })
+            .await
+        }
+        Err(error) => Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    }
+}
+
+#[get("/categories")]
+pub async fn view_categories(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match CategoryRepository::find_multiple_categories(c, auth.id) {
+            Ok(categories) => Ok(Custom(Status::Ok, json!(categories))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[get("/categories/tree?<range..>")]
+pub async fn view_category_tree(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    range: DateRangeQuery,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let (from, to) = match range.parse() {
+        Ok(range) => range,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(move |c| {
+        let categories = CategoryRepository::find_multiple_categories(c, auth.id);
+        let totals = TransactionsRepository::totals_by_category(c, auth.id, from, to);
+        match (categories, totals) {
+            (Ok(categories), Ok(totals)) => Ok(Custom(
+                Status::Ok,
+                json!(CategoryNode::build_tree(categories, &totals)),
+            )),
+            _ => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[get("/category/<id>")]
+pub async fn view_category(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match CategoryRepository::find_category(c, auth.id, id) {
+            Ok(Some(category)) => Ok(Custom(Status::Ok, json!(category))),
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error": "Category not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[put("/category/<id>", format = "json", data = "<category>")]
+pub async fn update_category(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    category: Json<UpdateCategory>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let category = category.into_inner();
+
+    db.run(
+        move |c| match CategoryRepository::find_category(c, auth.id, id) {
+            Ok(Some(_)) => match category.validate() {
+                Ok(()) => {
+                    check_parent(c, auth.id, Some(id), category.parent_id)?;
+
+                    match CategoryRepository::update_category(c, auth.id, id, category) {
+                        Ok(category_res) => Ok(Custom(Status::Ok, json!({"message": category_res}))),
+                        Err(err) => Err(write_error(err)),
+                    }
+                }
+                Err(errors) => Err(Custom(Status::BadRequest, json!({"error": errors}))),
+            },
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error": "Category not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[post("/category/<id>/merge", format = "json", data = "<merge>")]
+pub async fn merge_category(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    merge: Json<MergeCategory>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let merge = merge.into_inner();
+    if merge.into == id {
+        return Err(Custom(
+            Status::BadRequest,
+            json!({"error": "a category cannot be merged into itself"}),
+        ));
+    }
+
+    db.run(move |c| {
+        let source = CategoryRepository::find_category(c, auth.id, id);
+        let target = CategoryRepository::find_category(c, auth.id, merge.into);
+        match (source, target) {
+            (Ok(Some(source)), Ok(Some(target))) => {
+                match CategoryRepository::merge_categories(c, auth.id, &source, &target) {
+                    Ok(merged) => Ok(Custom(Status::Ok, json!({"message": merged}))),
+                    Err(err) => Err(write_error(err)),
+                }
+            }
+            (Ok(_), Ok(_)) => Err(Custom(
+                Status::NotFound,
+                json!({"error": "Category not found"}),
+            )),
+            _ => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[delete("/category/<id>")]
+pub async fn delete_category(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match CategoryRepository::find_category(c, auth.id, id) {
+            Ok(Some(_)) => match CategoryRepository::delete_category(c, auth.id, id) {
+                Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Category deleted"}))),
+                Err(err) => Err(write_error(err)),
+            },
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error": "Category not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
@@ -0,0 +1,239 @@
+use crate::model::{
+    parse_month, AssignMoney, BudgetingMode, MoveMoney, NewAllocation, ENVELOPE_MODE,
+};
+use crate::repositories::{CategoryRepository, EnvelopeRepository, UserRepository};
+use crate::{AuthenticatedUser, DBConnection};
+use chrono::{Datelike, Local, NaiveDate};
+use diesel::PgConnection;
+use rocket::{
+    get,
+    http::Status,
+    post, put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+fn current_month() -> NaiveDate {
+    Local::now().date_naive().with_day(1).unwrap_or_default()
+}
+
+fn month_or_current(month: Option<String>) -> Result<NaiveDate, Custom<Value>> {
+    match month {
+        Some(month) => {
+            parse_month(&month).map_err(|error| Custom(Status::BadRequest, json!({"errors": error})))
+        }
+        None => Ok(current_month()),
+    }
+}
+
+/// Returns the first month counted by envelope budgeting, or an error when
+/// the user has not switched to envelope budgeting.
+fn envelope_start(c: &mut PgConnection, user_id: i32) -> Result<NaiveDate, Custom<Value>> {
+    match UserRepository::find_user(c, user_id) {
+        Ok(Some(user)) if user.budgeting_mode == ENVELOPE_MODE => {
+            Ok(user.envelope_start.unwrap_or_else(current_month))
+        }
+        Ok(Some(_)) => Err(Custom(
+            Status::Conflict,
+            json!({"error": "envelope budgeting is not enabled"}),
+        )),
+        Ok(None) => Err(Custom(Status::NotFound, json!({"error": "User not found"}))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    }
+}
+
+#[put("/envelopes/mode", format = "json", data = "<mode>")]
+pub async fn set_budgeting_mode(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    mode: Json<BudgetingMode>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mode = mode.into_inner();
+    if let Err(error) = mode.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+    let start = match mode.mode.as_str() {
+        ENVELOPE_MODE => Some(month_or_current(mode.start_month)?),
+        _ => None,
+    };
+
+    db.run(
+        move |c| match EnvelopeRepository::set_mode(c, auth.id, &mode.mode, start) {
+            Ok(_) => Ok(Custom(
+                Status::Ok,
+                json!({"message": {"mode": mode.mode, "envelope_start": start}}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[get("/envelopes?<month>")]
+pub async fn view_envelopes(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    month: Option<String>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let month = month_or_current(month)?;
+
+    db.run(move |c| {
+        let start = envelope_start(c, auth.id)?;
+        let envelopes = EnvelopeRepository::find_envelopes(c, auth.id, start, month);
+        let unassigned = EnvelopeRepository::find_unassigned(c, auth.id, start, month);
+        match (envelopes, unassigned) {
+            (Ok(envelopes), Ok(unassigned)) => Ok(Custom(
+                Status::Ok,
+                json!({
+                    "month": month,
+                    "to_be_assigned": unassigned.to_be_assigned,
+                    "envelopes": envelopes,
+                }),
+            )),
+            _ => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[get("/envelopes/unassigned?<month>")]
+pub async fn view_unassigned(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    month: Option<String>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let month = month_or_current(month)?;
+
+    db.run(move |c| {
+        let start = envelope_start(c, auth.id)?;
+        match EnvelopeRepository::find_unassigned(c, auth.id, start, month) {
+            Ok(unassigned) => Ok(Custom(Status::Ok, json!(unassigned))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[post("/envelopes/assign", format = "json", data = "<assign>")]
+pub async fn assign_money(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    assign: Json<AssignMoney>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let assign = assign.into_inner();
+    let month = match assign.validate() {
+        Ok(month) => month,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(move |c| {
+        let start = envelope_start(c, auth.id)?;
+        if month < start {
+            return Err(Custom(
+                Status::BadRequest,
+                json!({"error": "month is before envelope budgeting started"}),
+            ));
+        }
+        match CategoryRepository::find_category(c, auth.id, assign.category_id) {
+            Ok(Some(category)) => {
+                let allocation = NewAllocation {
+                    user_id: auth.id,
+                    category_id: category.id,
+                    month,
+                    amount: assign.amount,
+                    note: None,
+                };
+                match EnvelopeRepository::assign(c, start, allocation) {
+                    Ok(Some(allocation)) => {
+                        Ok(Custom(Status::Created, json!({"message": allocation})))
+                    }
+                    Ok(None) => Err(Custom(
+                        Status::BadRequest,
+                        json!({"error": "not enough money in the envelope"}),
+                    )),
+                    Err(_) => Err(Custom(
+                        Status::InternalServerError,
+                        json!({"error":"something went wrong"}),
+                    )),
+                }
+            }
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error": "Category not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[post("/envelopes/move", format = "json", data = "<transfer>")]
+pub async fn move_money(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    transfer: Json<MoveMoney>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let transfer = transfer.into_inner();
+    let month = match transfer.validate() {
+        Ok(month) => month,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(move |c| {
+        let start = envelope_start(c, auth.id)?;
+        if month < start {
+            return Err(Custom(
+                Status::BadRequest,
+                json!({"error": "month is before envelope budgeting started"}),
+            ));
+        }
+        let from = CategoryRepository::find_category(c, auth.id, transfer.from_category_id);
+        let to = CategoryRepository::find_category(c, auth.id, transfer.to_category_id);
+        match (from, to) {
+            (Ok(Some(from)), Ok(Some(to))) => match EnvelopeRepository::move_money(
+                c,
+                auth.id,
+                start,
+                month,
+                &from,
+                &to,
+                transfer.amount,
+            ) {
+                Ok(Some(allocations)) => Ok(Custom(Status::Ok, json!({"message": allocations}))),
+                Ok(None) => Err(Custom(
+                    Status::BadRequest,
+                    json!({"error": "not enough money in the source envelope"}),
+                )),
+                Err(_) => Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                )),
+            },
+            (Ok(_), Ok(_)) => Err(Custom(
+                Status::NotFound,
+                json!({"error": "Category not found"}),
+            )),
+            _ => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
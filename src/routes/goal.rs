@@ -1,4 +1,4 @@
-use crate::model::NewGoal;
+use crate::model::{ListQuery, NewGoal};
 use crate::repositories::GoalsRepository;
 use crate::{AuthenticatedUser, DBConnection};
 use rocket::{delete, get};
@@ -31,16 +31,24 @@ pub async fn create_goal(
     }
 }
 
-#[get("/goals")]
+#[get("/goals?<query..>")]
 pub async fn view_goals(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
+    query: ListQuery,
 ) -> Result<Custom<Value>, Custom<Value>> {
+    let filter = match query.parse() {
+        Ok(filter) => filter,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
     db.run(
-        move |c| match GoalsRepository::find_multiple_goals(c, 100) {
-            Ok(Some(goals)) => Ok(Custom(Status::Ok, json!(goals))),
-            Ok(None) => Ok(Custom(Status::NotFound, json!("Not found"))),
-            Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
+        move |c| match GoalsRepository::find_multiple_goals(c, auth.id, &filter) {
+            Ok(page) => Ok(Custom(Status::Ok, json!(page))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
         },
     )
     .await
@@ -49,10 +57,10 @@ pub async fn view_goals(
 #[get("/goal/<id>")]
 pub async fn view_goal(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     id: i32
 )-> Result<Custom<Value>, Custom<Value>>{
-    db.run(move |c| match GoalsRepository::find_goal(c, id) {
+    db.run(move |c| match GoalsRepository::find_goal(c, auth.id, id) {
         Ok(Some(goal))=> Ok(Custom(Status::Ok, json!(goal))),
         Ok(None)=> Err(Custom(Status::NotFound, json!({"error":"goal not found"}))),
         Err(_)=> Err(Custom(Status::InternalServerError, json!({"error":"something went wrong"})))
@@ -64,13 +72,13 @@ pub async fn update_goal(
     db: DBConnection,
     id: i32,
     goal: Json<NewGoal>,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
 ) -> Result<Custom<Value>, Custom<Value>> {
     let goal = goal.into_inner();
 
-    db.run(move |c| match GoalsRepository::find_goal(c, id) {
+    db.run(move |c| match GoalsRepository::find_goal(c, auth.id, id) {
         Ok(Some(_)) => match goal.validate() {
-            Ok(()) => match GoalsRepository::update_goal(c, id, goal) {
+            Ok(()) => match GoalsRepository::update_goal(c, auth.id, id, goal) {
                 Ok(goal_res) => Ok(Custom(Status::Ok, json!({"message": goal_res}))),
                 Err(_) => Err(Custom(Status::InternalServerError, json!({"error": "something went wrong"})))
             },
@@ -92,10 +100,10 @@ pub async fn update_goal(
 pub async fn delete_goal(
     db: DBConnection,
     id: i32,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     ) -> Result<Custom<Value>, Custom<Value>> {
-        db.run(move |c| match GoalsRepository::find_goal(c, id) {
-            Ok(Some(_)) => match GoalsRepository::delete_goal(c, id) {
+        db.run(move |c| match GoalsRepository::find_goal(c, auth.id, id) {
+            Ok(Some(_)) => match GoalsRepository::delete_goal(c, auth.id, id) {
                 Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Goal deleted"}))),
                 Err(_) => Err(Custom(Status::InternalServerError, json!({"error":"something went wrong"}))),
             },
@@ -0,0 +1,348 @@
+use crate::import::{self, camt, csv, mt940, ofx, qif};
+use crate::model::{DateRangeQuery, NewImportProfile};
+use crate::repositories::{
+    CategoryRepository, ImportProfileRepository, IncomeRepository, TransactionsRepository,
+};
+use crate::routes::account::check_account;
+use crate::{AuthenticatedUser, DBConnection};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use rocket::data::{Data, ToByteUnit};
+use rocket::{
+    delete, get,
+    http::{ContentType, Status},
+    post,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+/// Category of imported transactions when the file does not name one.
+const DEFAULT_IMPORT_CATEGORY: &str = "Other";
+
+/// Largest file accepted by the import routes.
+const MAX_IMPORT_SIZE: u64 = 10;
+
+/// Reads an uploaded file, refusing files over `MAX_IMPORT_SIZE` MiB.
+async fn read_upload(data: Data<'_>) -> Result<Vec<u8>, Custom<Value>> {
+    match data.open(MAX_IMPORT_SIZE.mebibytes()).into_bytes().await {
+        Ok(bytes) if bytes.is_complete() => Ok(bytes.into_inner()),
+        Ok(_) => Err(Custom(
+            Status::PayloadTooLarge,
+            json!({"error": format!("files over {} MiB cannot be imported", MAX_IMPORT_SIZE)}),
+        )),
+        Err(_) => Err(Custom(
+            Status::BadRequest,
+            json!({"error": "the file could not be read"}),
+        )),
+    }
+}
+
+/// Answers with the import report: `201 Created` once rows were stored,
+/// `200 OK` for a dry run and `400 Bad Request` when invalid rows kept the
+/// import from being committed.
+fn import_response(report: import::ImportReport) -> Custom<Value> {
+    if report.committed {
+        Custom(Status::Created, json!({"message": report}))
+    } else if report.dry_run || report.failed == 0 {
+        Custom(Status::Ok, json!({"message": report}))
+    } else {
+        Custom(Status::BadRequest, json!({"errors": report}))
+    }
+}
+
+/// Imports the parsed rows of a file that needs no import profile, parsed
+/// with `category` as the default category, onto `account`, when given, and
+/// rows in unknown categories into `category` when `fallback` is set.
+async fn import_rows(
+    db: DBConnection,
+    user_id: i32,
+    rows: Vec<import::ParsedRow>,
+    account: Option<i32>,
+    category: String,
+    fallback: bool,
+    dry_run: bool,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| {
+        check_account(c, user_id, account)?;
+        let fallback_category = fallback.then_some(category.as_str());
+        match import::import_transactions(
+            c,
+            user_id,
+            rows,
+            account,
+            &category,
+            fallback_category,
+            dry_run,
+        ) {
+            Ok(report) => Ok(import_response(report)),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[post("/import/profile", format = "json", data = "<new_profile>")]
+pub async fn create_profile(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_profile: Json<NewImportProfile>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut profile = new_profile.into_inner();
+    profile.user_id = auth.id;
+    profile.name = profile.name.trim().to_owned();
+    if let Err(error) = profile.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(
+        move |c| match ImportProfileRepository::create_profile(c, profile) {
+            Ok(profile) => Ok(Custom(Status::Created, json!({"message": profile}))),
+            Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => Err(Custom(
+                Status::Conflict,
+                json!({"error": "a profile with this name already exists"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[get("/import/profiles")]
+pub async fn view_profiles(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match ImportProfileRepository::find_profiles(c, auth.id) {
+            Ok(profiles) => Ok(Custom(Status::Ok, json!(profiles))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[delete("/import/profile/<id>")]
+pub async fn delete_profile(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match ImportProfileRepository::delete_profile(c, auth.id, id) {
+            Ok(0) => Err(Custom(
+                Status::NotFound,
+                json!({"error":"Import profile not found"}),
+            )),
+            Ok(_) => Ok(Custom(
+                Status::Ok,
+                json!({"message": "Import profile deleted"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[post("/transactions/import?<profile>&<account>&<dry_run>", data = "<file>")]
+pub async fn import_csv(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    profile: i32,
+    account: Option<i32>,
+    dry_run: Option<bool>,
+    file: Data<'_>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let file = read_upload(file).await?;
+
+    db.run(move |c| {
+        let profile = match ImportProfileRepository::find_profile(c, auth.id, profile) {
+            Ok(Some(profile)) => profile,
+            Ok(None) => {
+                return Err(Custom(
+                    Status::NotFound,
+                    json!({"error":"Import profile not found"}),
+                ))
+            }
+            Err(_) => {
+                return Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                ))
+            }
+        };
+        let rows = csv::parse(&profile, &file)
+            .map_err(|error| Custom(Status::BadRequest, json!({"error": error.to_string()})))?;
+        check_account(c, auth.id, account)?;
+
+        match import::import_transactions(
+            c,
+            auth.id,
+            rows,
+            account,
+            &profile.default_category,
+            None,
+            dry_run.unwrap_or(false),
+        ) {
+            Ok(report) => Ok(import_response(report)),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[post(
+    "/transactions/import/ofx?<category>&<account>&<dry_run>",
+    data = "<file>"
+)]
+pub async fn import_ofx(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    category: Option<String>,
+    account: Option<i32>,
+    dry_run: Option<bool>,
+    file: Data<'_>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let file = read_upload(file).await?;
+    let category = category.unwrap_or_else(|| DEFAULT_IMPORT_CATEGORY.to_owned());
+    let rows = ofx::parse(&file, &category)
+        .map_err(|error| Custom(Status::BadRequest, json!({"error": error.to_string()})))?;
+    import_rows(
+        db,
+        auth.id,
+        rows,
+        account,
+        category,
+        false,
+        dry_run.unwrap_or(false),
+    )
+    .await
+}
+
+#[post(
+    "/transactions/import/qif?<category>&<day_first>&<account>&<dry_run>",
+    data = "<file>"
+)]
+pub async fn import_qif(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    category: Option<String>,
+    day_first: Option<bool>,
+    account: Option<i32>,
+    dry_run: Option<bool>,
+    file: Data<'_>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let file = read_upload(file).await?;
+    let category = category.unwrap_or_else(|| DEFAULT_IMPORT_CATEGORY.to_owned());
+    let rows = qif::parse(&file, &category, day_first.unwrap_or(false))
+        .map_err(|error| Custom(Status::BadRequest, json!({"error": error.to_string()})))?;
+    import_rows(
+        db,
+        auth.id,
+        rows,
+        account,
+        category,
+        true,
+        dry_run.unwrap_or(false),
+    )
+    .await
+}
+
+#[post(
+    "/transactions/import/camt?<category>&<account>&<dry_run>",
+    data = "<file>"
+)]
+pub async fn import_camt(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    category: Option<String>,
+    account: Option<i32>,
+    dry_run: Option<bool>,
+    file: Data<'_>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let file = read_upload(file).await?;
+    let category = category.unwrap_or_else(|| DEFAULT_IMPORT_CATEGORY.to_owned());
+    let rows = camt::parse(&file, &category)
+        .map_err(|error| Custom(Status::BadRequest, json!({"error": error.to_string()})))?;
+    import_rows(
+        db,
+        auth.id,
+        rows,
+        account,
+        category,
+        false,
+        dry_run.unwrap_or(false),
+    )
+    .await
+}
+
+#[post(
+    "/transactions/import/mt940?<category>&<account>&<dry_run>",
+    data = "<file>"
+)]
+pub async fn import_mt940(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    category: Option<String>,
+    account: Option<i32>,
+    dry_run: Option<bool>,
+    file: Data<'_>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let file = read_upload(file).await?;
+    let category = category.unwrap_or_else(|| DEFAULT_IMPORT_CATEGORY.to_owned());
+    let rows = mt940::parse(&file, &category)
+        .map_err(|error| Custom(Status::BadRequest, json!({"error": error.to_string()})))?;
+    import_rows(
+        db,
+        auth.id,
+        rows,
+        account,
+        category,
+        false,
+        dry_run.unwrap_or(false),
+    )
+    .await
+}
+
+#[get("/transactions/export/qif?<range..>")]
+pub async fn export_qif(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    range: DateRangeQuery,
+) -> Result<(ContentType, String), Custom<Value>> {
+    let (from, to) = match range.parse() {
+        Ok(range) => range,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(move |c| {
+        let transactions = TransactionsRepository::find_in_range(c, auth.id, from, to);
+        let income = IncomeRepository::find_in_range(c, auth.id, from, to);
+        let categories = CategoryRepository::find_multiple_categories(c, auth.id);
+        match (transactions, income, categories) {
+            (Ok(transactions), Ok(income), Ok(categories)) => Ok((
+                ContentType::new("application", "qif"),
+                qif::write(&transactions, &income, &categories),
+            )),
+            _ => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
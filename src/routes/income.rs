@@ -1,5 +1,6 @@
-use crate::model::NewIncome;
+use crate::model::{ListQuery, NewIncome};
 use crate::repositories::IncomeRepository;
+use crate::routes::account::check_account;
 use crate::{AuthenticatedUser, DBConnection};
 use rocket::{delete, get, put};
 use rocket::{
@@ -20,9 +21,12 @@ pub async fn create_income(
 
     match income.validate() {
         Ok(()) => {
-            db.run(move |c| match IncomeRepository::create_income(c, income) {
-                Ok(income_res) => Ok(Custom(Status::Created, json!({"message":income_res}))),
-                Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
+            db.run(move |c| {
+                check_account(c, auth.id, income.account_id)?;
+                match IncomeRepository::create_income(c, income) {
+                    Ok(income_res) => Ok(Custom(Status::Created, json!({"message":income_res}))),
+                    Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
+                }
             })
             .await
         }
@@ -30,16 +34,24 @@ pub async fn create_income(
     }
 }
 
-#[get("/incomes")]
+#[get("/incomes?<query..>")]
 pub async fn view_incomes(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
+    query: ListQuery,
 ) -> Result<Custom<Value>, Custom<Value>> {
+    let filter = match query.parse() {
+        Ok(filter) => filter,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
     db.run(
-        move |c| match IncomeRepository::find_multiple_income(c, 100) {
-            Ok(Some(incomes)) => Ok(Custom(Status::Ok, json!(incomes))),
-            Ok(None) => Ok(Custom(Status::NotFound, json!("Not found"))),
-            Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
+        move |c| match IncomeRepository::find_multiple_income(c, auth.id, &filter) {
+            Ok(page) => Ok(Custom(Status::Ok, json!(page))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
         },
     )
     .await
@@ -48,10 +60,10 @@ pub async fn view_incomes(
 #[get("/income/<id>")]
 pub async fn view_income(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     id: i32
 )-> Result<Custom<Value>, Custom<Value>>{
-    db.run(move |c| match IncomeRepository::find_income(c, id) {
+    db.run(move |c| match IncomeRepository::find_income(c, auth.id, id) {
         Ok(Some(income))=> Ok(Custom(Status::Ok, json!(income))),
         Ok(None)=> Err(Custom(Status::NotFound, json!({"error":"income not found"}))),
         Err(_)=> Err(Custom(Status::InternalServerError, json!({"error":"something went wrong"})))
@@ -63,15 +75,18 @@ pub async fn update_income(
     db: DBConnection,
     id: i32,
     income: Json<NewIncome>,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
 ) ->Result<Custom<Value>, Custom<Value>>{
     let income = income.into_inner();
 
-    db.run(move |c| match IncomeRepository::find_income(c, id){
+    db.run(move |c| match IncomeRepository::find_income(c, auth.id, id){
         Ok(Some(_))=> match  income.validate() {
-            Ok(())=> match  IncomeRepository::update_income(c, id, income) {
-                Ok(income_res)=> Ok(Custom(Status::Ok, json!({"message":income_res}))),
-                Err(_)=> Err(Custom(Status::InternalServerError, json!({"error":"something went wrong"})))
+            Ok(())=> {
+                check_account(c, auth.id, income.account_id)?;
+                match IncomeRepository::update_income(c, auth.id, id, income) {
+                    Ok(income_res)=> Ok(Custom(Status::Ok, json!({"message":income_res}))),
+                    Err(_)=> Err(Custom(Status::InternalServerError, json!({"error":"something went wrong"})))
+                }
             },
             Err(errors) => Err(Custom(Status::BadRequest, json!({"error":errors}))),
         }
@@ -91,10 +106,10 @@ pub async fn update_income(
 pub async fn delete_income(
     db: DBConnection,
     id: i32,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     ) -> Result<Custom<Value>, Custom<Value>> {
-        db.run(move |c| match IncomeRepository::find_income(c, id) {
-            Ok(Some(_)) => match IncomeRepository::delete_income(c, id) {
+        db.run(move |c| match IncomeRepository::find_income(c, auth.id, id) {
+            Ok(Some(_)) => match IncomeRepository::delete_income(c, auth.id, id) {
                 Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Income deleted"}))),
                 Err(_) => Err(Custom(Status::InternalServerError, json!({"error":"something went wrong"}))),
             },
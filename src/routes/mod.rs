@@ -1,5 +1,18 @@
+pub mod account;
+pub mod alert;
+pub mod attachment;
 pub mod budget;
+pub mod category;
+pub mod envelope;
 pub mod goal;
+pub mod import;
 pub mod income;
+pub mod payee;
+pub mod recurring;
+pub mod rule;
+pub mod search;
+pub mod tag;
 pub mod transactions;
+pub mod transfer;
 pub mod user;
+pub mod view;
@@ -0,0 +1,225 @@
+use crate::model::{DateRangeQuery, NewPayee, NewPayeeAlias};
+use crate::repositories::PayeeRepository;
+use crate::routes::category::resolve_category;
+use crate::utils::payees::normalize;
+use crate::{AuthenticatedUser, DBConnection};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::PgConnection;
+use rocket::{
+    delete, get,
+    http::Status,
+    post, put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+fn write_error(err: DieselError) -> Custom<Value> {
+    match err {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Custom(
+            Status::Conflict,
+            json!({"error": "a payee with this name already exists"}),
+        ),
+        _ => Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        ),
+    }
+}
+
+/// Trims the name and resolves the default category of a payee.
+fn check_payee(
+    c: &mut PgConnection,
+    user_id: i32,
+    payee: &mut NewPayee,
+) -> Result<(), Custom<Value>> {
+    payee.user_id = user_id;
+    payee.name = payee.name.trim().to_owned();
+    payee.category_id = None;
+    if let Err(error) = payee.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+    if let Some(category) = &payee.category {
+        let category = resolve_category(c, user_id, category)?;
+        payee.category_id = Some(category.id);
+        payee.category = Some(category.name);
+    }
+    Ok(())
+}
+
+#[post("/payee", format = "json", data = "<new_payee>")]
+pub async fn create_payee(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_payee: Json<NewPayee>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut payee = new_payee.into_inner();
+
+    db.run(move |c| {
+        check_payee(c, auth.id, &mut payee)?;
+        match PayeeRepository::create_payee(c, payee) {
+            Ok(payee) => Ok(Custom(Status::Created, json!({"message": payee}))),
+            Err(err) => Err(write_error(err)),
+        }
+    })
+    .await
+}
+
+#[get("/payees")]
+pub async fn view_payees(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match PayeeRepository::find_payees(c, auth.id) {
+        Ok(payees) => Ok(Custom(Status::Ok, json!(payees))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[get("/payee/<id>")]
+pub async fn view_payee(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| {
+        let found = PayeeRepository::find_payee(c, auth.id, id).and_then(|payee| match payee {
+            Some(payee) => {
+                PayeeRepository::find_aliases(c, auth.id, id).map(|aliases| Some((payee, aliases)))
+            }
+            None => Ok(None),
+        });
+        match found {
+            Ok(Some((payee, aliases))) => Ok(Custom(
+                Status::Ok,
+                json!({"payee": payee, "aliases": aliases}),
+            )),
+            Ok(None) => Err(Custom(Status::NotFound, json!({"error":"Payee not found"}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[get("/payees/<id>/summary?<range..>")]
+pub async fn payee_summary(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    range: DateRangeQuery,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let (from, to) = match range.parse() {
+        Ok(range) => range,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(move |c| {
+        let summary = PayeeRepository::find_payee(c, auth.id, id).and_then(|payee| match payee {
+            Some(payee) => PayeeRepository::summary(c, auth.id, payee, from, to).map(Some),
+            None => Ok(None),
+        });
+        match summary {
+            Ok(Some(summary)) => Ok(Custom(Status::Ok, json!(summary))),
+            Ok(None) => Err(Custom(Status::NotFound, json!({"error":"Payee not found"}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[put("/payee/<id>", format = "json", data = "<payee>")]
+pub async fn update_payee(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    payee: Json<NewPayee>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut payee = payee.into_inner();
+
+    db.run(move |c| {
+        check_payee(c, auth.id, &mut payee)?;
+        match PayeeRepository::update_payee(c, auth.id, id, payee) {
+            Ok(Some(payee)) => Ok(Custom(Status::Ok, json!({"message": payee}))),
+            Ok(None) => Err(Custom(Status::NotFound, json!({"error":"Payee not found"}))),
+            Err(err) => Err(write_error(err)),
+        }
+    })
+    .await
+}
+
+#[post("/payee/<id>/alias", format = "json", data = "<alias>")]
+pub async fn add_alias(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    alias: Json<NewPayeeAlias>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let Some(alias) = normalize(&alias.alias) else {
+        return Err(Custom(
+            Status::BadRequest,
+            json!({"error": "alias must contain a name"}),
+        ));
+    };
+
+    db.run(move |c| match PayeeRepository::find_payee(c, auth.id, id) {
+        Ok(Some(_)) => match PayeeRepository::add_alias(c, auth.id, id, &alias) {
+            Ok(alias) => Ok(Custom(Status::Created, json!({"message": alias}))),
+            Err(err) => Err(write_error(err)),
+        },
+        Ok(None) => Err(Custom(Status::NotFound, json!({"error":"Payee not found"}))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[delete("/payee/<id>/alias/<alias_id>")]
+pub async fn delete_alias(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    alias_id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match PayeeRepository::delete_alias(c, auth.id, id, alias_id) {
+            Ok(0) => Err(Custom(Status::NotFound, json!({"error":"Alias not found"}))),
+            Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Alias deleted"}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[delete("/payee/<id>")]
+pub async fn delete_payee(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match PayeeRepository::delete_payee(c, auth.id, id) {
+            Ok(0) => Err(Custom(Status::NotFound, json!({"error":"Payee not found"}))),
+            Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Payee deleted"}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
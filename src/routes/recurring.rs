@@ -0,0 +1,225 @@
+use crate::model::{
+    EditOccurrence, NewRecurringTemplate, RecurringTemplate, OCCURRENCE_PENDING,
+    OCCURRENCE_SKIPPED, TEMPLATE_TRANSACTION,
+};
+use crate::repositories::RecurringRepository;
+use crate::routes::account::check_account;
+use crate::routes::category::resolve_category;
+use crate::{AuthenticatedUser, DBConnection};
+use chrono::{Days, Local, NaiveDate};
+use diesel::PgConnection;
+use rocket::{
+    delete, get,
+    http::Status,
+    post, put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+const DEFAULT_UPCOMING_DAYS: u64 = 30;
+const MAX_UPCOMING_DAYS: u64 = 366;
+
+fn find_template(
+    c: &mut PgConnection,
+    user_id: i32,
+    id: i32,
+) -> Result<RecurringTemplate, Custom<Value>> {
+    match RecurringRepository::find_template(c, user_id, id) {
+        Ok(Some(template)) => Ok(template),
+        Ok(None) => Err(Custom(
+            Status::NotFound,
+            json!({"error":"Recurring template not found"}),
+        )),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    }
+}
+
+/// Parses the `<date>` segment of an occurrence route and checks that the
+/// template is scheduled on it.
+fn occurrence_date(template: &RecurringTemplate, date: &str) -> Result<NaiveDate, Custom<Value>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+        Custom(
+            Status::BadRequest,
+            json!({"error": "date must look like 2024-01-31"}),
+        )
+    })?;
+    if template.occurrences(date, date).contains(&date) {
+        Ok(date)
+    } else {
+        Err(Custom(
+            Status::NotFound,
+            json!({"error": "the template is not scheduled on this date"}),
+        ))
+    }
+}
+
+#[post("/recurring", format = "json", data = "<new_template>")]
+pub async fn create_template(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_template: Json<NewRecurringTemplate>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut template = new_template.into_inner();
+    template.user_id = auth.id;
+    template.category_id = None;
+    if let Err(error) = template.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(move |c| {
+        if template.kind == TEMPLATE_TRANSACTION {
+            let category = resolve_category(c, auth.id, &template.category)?;
+            template.category_id = Some(category.id);
+            template.category = category.name;
+        }
+        check_account(c, auth.id, template.account_id)?;
+
+        let created = RecurringRepository::create_template(c, template).and_then(|template| {
+            // post what is already due instead of waiting for the next run
+            RecurringRepository::post_due(c, Some(auth.id), Local::now().date_naive())?;
+            Ok(template)
+        });
+        match created {
+            Ok(template) => Ok(Custom(Status::Created, json!({"message": template}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[get("/recurring")]
+pub async fn view_templates(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match RecurringRepository::find_templates(c, auth.id) {
+            Ok(templates) => Ok(Custom(Status::Ok, json!(templates))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[get("/recurring/<id>")]
+pub async fn view_template(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| {
+        let template = find_template(c, auth.id, id)?;
+        Ok(Custom(Status::Ok, json!(template)))
+    })
+    .await
+}
+
+#[get("/recurring/upcoming?<days>")]
+pub async fn view_upcoming(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    days: Option<u64>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let days = days.unwrap_or(DEFAULT_UPCOMING_DAYS).min(MAX_UPCOMING_DAYS);
+    let from = Local::now().date_naive();
+    let to = from.checked_add_days(Days::new(days)).unwrap_or(from);
+
+    db.run(
+        move |c| match RecurringRepository::find_upcoming(c, auth.id, from, to) {
+            Ok(upcoming) => Ok(Custom(Status::Ok, json!(upcoming))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[put("/recurring/<id>/occurrence/<date>/skip")]
+pub async fn skip_occurrence(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    date: String,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let skip = EditOccurrence {
+        amount: None,
+        description: None,
+        date: None,
+    };
+    change_occurrence(db, auth.id, id, date, OCCURRENCE_SKIPPED, skip).await
+}
+
+#[put("/recurring/<id>/occurrence/<date>", format = "json", data = "<edit>")]
+pub async fn edit_occurrence(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    date: String,
+    edit: Json<EditOccurrence>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let edit = edit.into_inner();
+    if let Err(error) = edit.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+    change_occurrence(db, auth.id, id, date, OCCURRENCE_PENDING, edit).await
+}
+
+async fn change_occurrence(
+    db: DBConnection,
+    user_id: i32,
+    id: i32,
+    date: String,
+    status: &'static str,
+    edit: EditOccurrence,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| {
+        let template = find_template(c, user_id, id)?;
+        let date = occurrence_date(&template, &date)?;
+        match RecurringRepository::change_occurrence(c, &template, date, status, edit) {
+            Ok(Some(occurrence)) => Ok(Custom(Status::Ok, json!({"message": occurrence}))),
+            Ok(None) => Err(Custom(
+                Status::Conflict,
+                json!({"error": "this occurrence has already been posted"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[delete("/recurring/<id>")]
+pub async fn delete_template(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| {
+        find_template(c, auth.id, id)?;
+        match RecurringRepository::delete_template(c, auth.id, id) {
+            Ok(_) => Ok(Custom(
+                Status::Ok,
+                json!({"message": "Recurring template deleted"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
@@ -0,0 +1,206 @@
+use crate::model::{DateRangeQuery, NewCategoryRule, RuleMatchMode, RuleOrder};
+use crate::repositories::RuleRepository;
+use crate::routes::category::resolve_category;
+use crate::{AuthenticatedUser, DBConnection};
+use diesel::{Connection, QueryResult};
+use rocket::{
+    delete, get,
+    http::Status,
+    post, put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+#[post("/rule", format = "json", data = "<new_rule>")]
+pub async fn create_rule(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_rule: Json<NewCategoryRule>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut rule = new_rule.into_inner();
+    rule.user_id = auth.id;
+    rule.category_id = None;
+    if let Err(error) = rule.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(move |c| {
+        if let Some(category) = &rule.category {
+            let category = resolve_category(c, auth.id, category)?;
+            rule.category_id = Some(category.id);
+            rule.category = Some(category.name);
+        }
+
+        match RuleRepository::create_rule(c, rule) {
+            Ok(rule) => Ok(Custom(Status::Created, json!({"message": rule}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[get("/rules")]
+pub async fn view_rules(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match RuleRepository::find_rules(c, auth.id) {
+        Ok(rules) => Ok(Custom(Status::Ok, json!(rules))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[get("/rule/<id>")]
+pub async fn view_rule(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match RuleRepository::find_rule(c, auth.id, id) {
+        Ok(Some(rule)) => Ok(Custom(Status::Ok, json!(rule))),
+        Ok(None) => Err(Custom(Status::NotFound, json!({"error":"Rule not found"}))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[put("/rules/mode", format = "json", data = "<mode>")]
+pub async fn set_match_mode(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    mode: Json<RuleMatchMode>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mode = mode.into_inner();
+    if let Err(error) = mode.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(
+        move |c| match RuleRepository::set_match_mode(c, auth.id, &mode.mode) {
+            Ok(_) => Ok(Custom(Status::Ok, json!({"message": mode}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[put("/rules/order", format = "json", data = "<order>")]
+pub async fn reorder_rules(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    order: Json<RuleOrder>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let order = order.into_inner();
+
+    db.run(move |c| {
+        let mut ids: Vec<i32> = match RuleRepository::find_rules(c, auth.id) {
+            Ok(rules) => rules.iter().map(|rule| rule.id).collect(),
+            Err(_) => {
+                return Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                ))
+            }
+        };
+        let mut requested = order.ids.clone();
+        ids.sort_unstable();
+        requested.sort_unstable();
+        if ids != requested {
+            return Err(Custom(
+                Status::BadRequest,
+                json!({"error": "ids must list each of your rules once"}),
+            ));
+        }
+
+        match RuleRepository::reorder(c, auth.id, &order.ids) {
+            Ok(rules) => Ok(Custom(Status::Ok, json!({"message": rules}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[get("/rules/preview?<range..>")]
+pub async fn preview_rules(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    range: DateRangeQuery,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let (from, to) = match range.parse() {
+        Ok(range) => range,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(
+        move |c| match RuleRepository::preview_changes(c, auth.id, from, to) {
+            Ok(changes) => Ok(Custom(Status::Ok, json!(changes))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[post("/rules/apply?<range..>")]
+pub async fn apply_rules(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    range: DateRangeQuery,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let (from, to) = match range.parse() {
+        Ok(range) => range,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(move |c| {
+        let applied = c.transaction(|c| {
+            let changes = RuleRepository::preview_changes(c, auth.id, from, to)?;
+            RuleRepository::apply_changes(c, auth.id, &changes)?;
+            QueryResult::Ok(changes)
+        });
+        match applied {
+            Ok(changes) => Ok(Custom(Status::Ok, json!({"message": changes}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[delete("/rule/<id>")]
+pub async fn delete_rule(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match RuleRepository::delete_rule(c, auth.id, id) {
+        Ok(0) => Err(Custom(Status::NotFound, json!({"error":"Rule not found"}))),
+        Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Rule deleted"}))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
@@ -0,0 +1,28 @@
+use crate::model::SearchQuery;
+use crate::repositories::SearchRepository;
+use crate::{AuthenticatedUser, DBConnection};
+use rocket::{get, http::Status, response::status::Custom, serde::json::serde_json::json};
+use serde_json::Value;
+
+#[get("/search?<query..>")]
+pub async fn search(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    query: SearchQuery,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let filter = match query.parse() {
+        Ok(filter) => filter,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(
+        move |c| match SearchRepository::search(c, auth.id, &filter) {
+            Ok(results) => Ok(Custom(Status::Ok, json!(results))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
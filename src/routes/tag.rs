@@ -0,0 +1,250 @@
+use crate::model::{normalize_tag, DateRangeQuery, NewTag, TagNames};
+use crate::repositories::{
+    GoalsRepository, IncomeRepository, TagRepository, Tagged, TransactionsRepository,
+};
+use crate::{AuthenticatedUser, DBConnection};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::{PgConnection, QueryResult};
+use rocket::{
+    delete, get,
+    http::Status,
+    post, put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+fn write_error(err: DieselError) -> Custom<Value> {
+    match err {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Custom(
+            Status::Conflict,
+            json!({"error": "a tag with this name already exists"}),
+        ),
+        _ => Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        ),
+    }
+}
+
+/// Whether the transaction, income or goal exists and belongs to the user.
+fn exists(c: &mut PgConnection, user_id: i32, tagged: Tagged) -> QueryResult<bool> {
+    Ok(match tagged {
+        Tagged::Transaction(id) => {
+            TransactionsRepository::find_transaction(c, user_id, id)?.is_some()
+        }
+        Tagged::Income(id) => IncomeRepository::find_income(c, user_id, id)?.is_some(),
+        Tagged::Goal(id) => GoalsRepository::find_goal(c, user_id, id)?.is_some(),
+    })
+}
+
+fn not_found(tagged: Tagged) -> Custom<Value> {
+    let error = match tagged {
+        Tagged::Transaction(_) => "Transaction not found",
+        Tagged::Income(_) => "Income not found",
+        Tagged::Goal(_) => "Goal not found",
+    };
+    Custom(Status::NotFound, json!({ "error": error }))
+}
+
+async fn view_tags_of(
+    db: DBConnection,
+    user_id: i32,
+    tagged: Tagged,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match exists(c, user_id, tagged) {
+        Ok(true) => match TagRepository::find_tagged(c, user_id, tagged) {
+            Ok(tags) => Ok(Custom(Status::Ok, json!(tags))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+        Ok(false) => Err(not_found(tagged)),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+async fn set_tags_of(
+    db: DBConnection,
+    user_id: i32,
+    tagged: Tagged,
+    mut names: TagNames,
+) -> Result<Custom<Value>, Custom<Value>> {
+    if let Err(error) = names.normalize() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(move |c| match exists(c, user_id, tagged) {
+        Ok(true) => match TagRepository::set_tags(c, user_id, tagged, &names.tags) {
+            Ok(tags) => Ok(Custom(Status::Ok, json!({"message": tags}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+        Ok(false) => Err(not_found(tagged)),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[post("/tag", format = "json", data = "<new_tag>")]
+pub async fn create_tag(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_tag: Json<NewTag>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut tag = new_tag.into_inner();
+    tag.user_id = auth.id;
+    tag.name = normalize_tag(&tag.name);
+    if let Err(error) = tag.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(move |c| match TagRepository::create_tag(c, tag) {
+        Ok(tag) => Ok(Custom(Status::Created, json!({"message": tag}))),
+        Err(err) => Err(write_error(err)),
+    })
+    .await
+}
+
+#[get("/tags")]
+pub async fn view_tags(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match TagRepository::find_tags(c, auth.id) {
+        Ok(tags) => Ok(Custom(Status::Ok, json!(tags))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[get("/tags/report?<range..>")]
+pub async fn tag_report(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    range: DateRangeQuery,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let (from, to) = match range.parse() {
+        Ok(range) => range,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+
+    db.run(move |c| match TagRepository::report(c, auth.id, from, to) {
+        Ok(report) => Ok(Custom(Status::Ok, json!(report))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[put("/tag/<id>", format = "json", data = "<tag>")]
+pub async fn rename_tag(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    tag: Json<NewTag>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut tag = tag.into_inner();
+    tag.name = normalize_tag(&tag.name);
+    if let Err(error) = tag.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(
+        move |c| match TagRepository::rename_tag(c, auth.id, id, &tag.name) {
+            Ok(Some(tag)) => Ok(Custom(Status::Ok, json!({"message": tag}))),
+            Ok(None) => Err(Custom(Status::NotFound, json!({"error":"Tag not found"}))),
+            Err(err) => Err(write_error(err)),
+        },
+    )
+    .await
+}
+
+#[delete("/tag/<id>")]
+pub async fn delete_tag(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| match TagRepository::delete_tag(c, auth.id, id) {
+        Ok(0) => Err(Custom(Status::NotFound, json!({"error":"Tag not found"}))),
+        Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Tag deleted"}))),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        )),
+    })
+    .await
+}
+
+#[get("/transaction/<id>/tags")]
+pub async fn view_transaction_tags(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    view_tags_of(db, auth.id, Tagged::Transaction(id)).await
+}
+
+#[put("/transaction/<id>/tags", format = "json", data = "<tags>")]
+pub async fn set_transaction_tags(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    tags: Json<TagNames>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    set_tags_of(db, auth.id, Tagged::Transaction(id), tags.into_inner()).await
+}
+
+#[get("/income/<id>/tags")]
+pub async fn view_income_tags(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    view_tags_of(db, auth.id, Tagged::Income(id)).await
+}
+
+#[put("/income/<id>/tags", format = "json", data = "<tags>")]
+pub async fn set_income_tags(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    tags: Json<TagNames>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    set_tags_of(db, auth.id, Tagged::Income(id), tags.into_inner()).await
+}
+
+#[get("/goal/<id>/tags")]
+pub async fn view_goal_tags(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    view_tags_of(db, auth.id, Tagged::Goal(id)).await
+}
+
+#[put("/goal/<id>/tags", format = "json", data = "<tags>")]
+pub async fn set_goal_tags(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    tags: Json<TagNames>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    set_tags_of(db, auth.id, Tagged::Goal(id), tags.into_inner()).await
+}
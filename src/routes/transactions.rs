@@ -1,11 +1,35 @@
-use crate::model::{NewTransaction, UpdateTransaction};
-use crate::repositories::TransactionsRepository;
+use crate::model::{
+    BulkOperation, BulkRequest, DateRangeQuery, ListQuery, MergeTransactions, NewTransaction,
+    Transaction, TransactionSplits, UpdateTransaction, FALLBACK_CATEGORY, MAX_BULK_ROWS,
+};
+use crate::repositories::{
+    AlertRepository, AttachmentRepository, ClassifierRepository, PayeeRepository,
+    RuleRepository, TransactionsRepository,
+};
+use crate::routes::account::check_account;
+use crate::routes::attachment::remove_files;
+use crate::routes::category::resolve_category;
+use crate::utils::attachments::AttachmentStore;
+use crate::utils::classifier::SUGGESTION_COUNT;
+use crate::utils::duplicates::{group_duplicates, DUPLICATE_WINDOW_DAYS};
+use crate::utils::query::TransactionQuery;
 use crate::{AuthenticatedUser, DBConnection};
+use bigdecimal::BigDecimal;
+use chrono::{Days, Local};
+use diesel::{Connection, PgConnection, QueryResult};
 use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::{delete, put};
-use rocket::{get, post, response::status::Custom};
+use rocket::{get, post, response::status::Custom, State};
 use serde_json::{json, Value};
+use std::collections::HashSet;
+use validator::ValidationError;
+
+/// Widest date window, in days, of `GET /transactions/duplicates`.
+const MAX_DUPLICATE_WINDOW_DAYS: u64 = 31;
+
+/// Days searched by `GET /transactions/duplicates` when no `from` is given.
+const DUPLICATE_SEARCH_DAYS: u64 = 90;
 
 #[post("/transaction", format = "json", data = "<new_transaction>")]
 pub async fn create_transaction(
@@ -16,49 +40,171 @@ pub async fn create_transaction(
     let mut transaction = new_transaction.into_inner();
     transaction.user_id = Some(auth.id);
 
-    match transaction.validate() {
-        Ok(()) => {
-            db.run(
-                move |c| match TransactionsRepository::create_transaction(c, transaction) {
-                    Ok(transaction) => Ok(Custom(Status::Created, json!({"message": transaction}))),
-                    Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
-                },
-            )
-            .await
-        }
-        Err(error) => Err(Custom(Status::BadRequest, json!({"errors":error}))),
-    }
+    db.run(move |c| {
+        // a category given in the request wins over the rules
+        let category = transaction.category.trim().to_owned();
+        let rule_ids = match RuleRepository::find_rule_set(c, auth.id) {
+            Ok(rules) => rules.apply(&mut transaction, category.is_empty()),
+            Err(_) => {
+                return Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                ))
+            }
+        };
+        let payee = match &transaction.description {
+            Some(description) => {
+                let payee = PayeeRepository::find_all_aliases(c, auth.id).and_then(|aliases| {
+                    PayeeRepository::find_for_description(c, auth.id, &aliases, description)
+                });
+                match payee {
+                    Ok(payee) => payee,
+                    Err(_) => {
+                        return Err(Custom(
+                            Status::InternalServerError,
+                            json!({"error":"something went wrong"}),
+                        ))
+                    }
+                }
+            }
+            None => None,
+        };
+        if let Some(payee) = &payee {
+            transaction.payee_id = Some(payee.id);
+        }
+        if !category.is_empty() {
+            transaction.category = category;
+        } else if transaction.category.is_empty() {
+            // no rule categorized it, so the payee's default category if any
+            transaction.category = payee
+                .and_then(|payee| payee.category)
+                .unwrap_or_else(|| FALLBACK_CATEGORY.to_owned());
+        }
+        if let Err(error) = transaction.validate() {
+            return Err(Custom(Status::BadRequest, json!({"errors":error})));
+        }
+
+        let category = resolve_category(c, auth.id, &transaction.category)?;
+        transaction.category_id = Some(category.id);
+        transaction.category = category.name;
+        check_account(c, auth.id, transaction.account_id)?;
+
+        let created = c.transaction::<_, diesel::result::Error, _>(|c| {
+            let transaction = TransactionsRepository::create_transaction(c, transaction)?;
+            let today = Local::now().date_naive();
+            let alerts = AlertRepository::check_thresholds(c, auth.id, &transaction, today)?;
+            let duplicates = TransactionsRepository::find_possible_duplicates(
+                c,
+                auth.id,
+                &transaction.amount,
+                transaction.date,
+                transaction.description.as_deref(),
+                Some(transaction.id),
+            )?;
+            let suggestion = ClassifierRepository::suggest(c, auth.id, &transaction, 1)?.pop();
+            Ok((transaction, alerts, duplicates, suggestion))
+        });
+        match created {
+            Ok((transaction, alerts, duplicates, suggestion)) => Ok(Custom(
+                Status::Created,
+                json!({
+                    "message": transaction,
+                    "alerts": alerts,
+                    "duplicates": duplicates,
+                    "rules": rule_ids,
+                    "suggestion": suggestion
+                }),
+            )),
+            Err(_) => Err(Custom(Status::InternalServerError, json!("error"))),
+        }
+    })
+    .await
 }
 
-#[get("/transactions")]
+#[get("/transactions?<q>&<query..>")]
 pub async fn view_transactions(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
+    q: Option<String>,
+    query: ListQuery,
 ) -> Result<Custom<Value>, Custom<Value>> {
-    db.run(
-        move |c| match TransactionsRepository::find_multiple_transactions(c, 100) {
-            Ok(Some(transactions)) => Ok(Custom(Status::Ok, json!(transactions))),
-            Ok(None) => Err(Custom(
-                Status::NotFound,
-                json!({"error": "Transaction not found"}),
+    let filter = match query.parse() {
+        Ok(filter) => filter,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+    let search = match q.as_deref().map(str::parse::<TransactionQuery>).transpose() {
+        Ok(search) => search,
+        Err(err) => {
+            let error = ValidationError::from(err);
+            return Err(Custom(Status::BadRequest, json!({"errors": error})));
+        }
+    };
+
+    db.run(move |c| {
+        let page =
+            TransactionsRepository::find_multiple_transactions(c, auth.id, &filter, search.as_ref());
+        match page {
+            Ok(page) => Ok(Custom(Status::Ok, json!(page))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+/// Groups of suspected duplicates among the transactions between `from` and
+/// `to`, which default to today and `DUPLICATE_SEARCH_DAYS` before `to`.
+#[get("/transactions/duplicates?<days>&<range..>")]
+pub async fn view_duplicates(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    days: Option<u64>,
+    range: DateRangeQuery,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let days = days
+        .unwrap_or(DUPLICATE_WINDOW_DAYS)
+        .min(MAX_DUPLICATE_WINDOW_DAYS);
+    let (from, to) = match range.parse() {
+        Ok(range) => range,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+    let to = to.unwrap_or_else(|| Local::now().date_naive());
+    let from = from.unwrap_or_else(|| {
+        to.checked_sub_days(Days::new(DUPLICATE_SEARCH_DAYS))
+            .unwrap_or(to)
+    });
+    if from > to {
+        return Err(Custom(
+            Status::BadRequest,
+            json!({"error": "from must not be after to"}),
+        ));
+    }
+
+    db.run(move |c| {
+        match TransactionsRepository::find_in_range(c, auth.id, Some(from), Some(to)) {
+            Ok(transactions) => Ok(Custom(
+                Status::Ok,
+                json!(group_duplicates(transactions, days)),
             )),
             Err(_) => Err(Custom(
                 Status::InternalServerError,
                 json!({"error":"something went wrong"}),
             )),
-        },
-    )
+        }
+    })
     .await
 }
 
 #[get("/transaction/<id>")]
 pub async fn view_transaction(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     id: i32,
 ) -> Result<Value, Custom<Value>> {
     db.run(
-        move |c| match TransactionsRepository::find_transaction(c, id) {
+        move |c| match TransactionsRepository::find_transaction(c, auth.id, id) {
             Ok(Some(transaction)) => Ok(json!(transaction)),
             Ok(None) => Err(Custom(
                 Status::NotFound,
@@ -73,29 +219,90 @@ pub async fn view_transaction(
     .await
 }
 
+#[get("/transaction/<id>/suggestions")]
+pub async fn suggest_categories(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(move |c| {
+        let suggestions = TransactionsRepository::find_transaction(c, auth.id, id).and_then(
+            |transaction| match transaction {
+                Some(transaction) => {
+                    ClassifierRepository::suggest(c, auth.id, &transaction, SUGGESTION_COUNT)
+                        .map(|suggestions| Some((transaction, suggestions)))
+                }
+                None => Ok(None),
+            },
+        );
+        match suggestions {
+            Ok(Some((transaction, suggestions))) => Ok(Custom(
+                Status::Ok,
+                json!({"category": transaction.category, "suggestions": suggestions}),
+            )),
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error":"Transaction not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
 #[put("/transaction/<id>", format = "json", data = "<transaction>")]
 pub async fn update_transaction(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
     id: i32,
     transaction: Json<UpdateTransaction>,
 ) -> Result<Custom<Value>, Custom<Value>> {
+    let mut transaction = transaction.into_inner();
+
     db.run(
-        move |c| match TransactionsRepository::find_transaction(c, id) {
+        move |c| match TransactionsRepository::find_transaction(c, auth.id, id) {
             Ok(Some(_)) => match transaction.validate() {
-                Ok(()) => match TransactionsRepository::update_transaction(
-                    c,
-                    id,
-                    transaction.into_inner(),
-                ) {
-                    Ok(transaction_res) => {
-                        Ok(Custom(Status::Ok, json!({"message":transaction_res})))
+                Ok(()) => {
+                    let category = resolve_category(c, auth.id, &transaction.category)?;
+                    transaction.category_id = Some(category.id);
+                    transaction.category = category.name;
+                    check_account(c, auth.id, transaction.account_id)?;
+
+                    // the splits have to be changed along with the amount
+                    match TransactionsRepository::find_splits(c, auth.id, id) {
+                        Ok(splits)
+                            if !splits.is_empty()
+                                && splits.iter().map(|split| &split.amount).sum::<BigDecimal>()
+                                    != transaction.amount =>
+                        {
+                            return Err(Custom(
+                                Status::BadRequest,
+                                json!({"error": "the transaction is split, change its splits to the new amount first"}),
+                            ))
+                        }
+                        Ok(_) => {}
+                        Err(_) => {
+                            return Err(Custom(
+                                Status::InternalServerError,
+                                json!({"error":"something went wrong"}),
+                            ))
+                        }
                     }
-                    Err(_) => Err(Custom(
-                        Status::InternalServerError,
-                        json!({"error":"something went wrong"}),
-                    )),
-                },
+
+                    match TransactionsRepository::update_transaction(c, auth.id, id, transaction)
+                    {
+                        Ok(transaction_res) => {
+                            Ok(Custom(Status::Ok, json!({"message":transaction_res})))
+                        }
+                        Err(_) => Err(Custom(
+                            Status::InternalServerError,
+                            json!({"error":"something went wrong"}),
+                        )),
+                    }
+                }
                 Err(errors) => Err(Custom(Status::BadRequest, json!({"error":errors}))),
             },
             Ok(None) => Err(Custom(
@@ -111,15 +318,106 @@ pub async fn update_transaction(
     .await
 }
 
+#[get("/transaction/<id>/splits")]
+pub async fn view_splits(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match TransactionsRepository::find_transaction(c, auth.id, id) {
+            Ok(Some(_)) => match TransactionsRepository::find_splits(c, auth.id, id) {
+                Ok(splits) => Ok(Custom(Status::Ok, json!(splits))),
+                Err(_) => Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                )),
+            },
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error":"Transaction not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[put("/transaction/<id>/splits", format = "json", data = "<splits>")]
+pub async fn set_splits(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    splits: Json<TransactionSplits>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut splits = splits.into_inner();
+
+    db.run(move |c| {
+        let transaction = match TransactionsRepository::find_transaction(c, auth.id, id) {
+            Ok(Some(transaction)) => transaction,
+            Ok(None) => {
+                return Err(Custom(
+                    Status::NotFound,
+                    json!({"error":"Transaction not found"}),
+                ))
+            }
+            Err(_) => {
+                return Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                ))
+            }
+        };
+        if let Err(error) = splits.validate(&transaction.amount) {
+            return Err(Custom(Status::BadRequest, json!({"errors": error})));
+        }
+        for split in &mut splits.splits {
+            let category = resolve_category(c, auth.id, &split.category)?;
+            split.transaction_id = id;
+            split.user_id = auth.id;
+            split.category_id = category.id;
+            split.category = category.name;
+        }
+
+        let updated = c.transaction::<_, diesel::result::Error, _>(|c| {
+            let splits = TransactionsRepository::set_splits(c, auth.id, id, splits.splits)?;
+            let today = Local::now().date_naive();
+            let alerts = AlertRepository::check_thresholds(c, auth.id, &transaction, today)?;
+            Ok((splits, alerts))
+        });
+        match updated {
+            Ok((splits, alerts)) => Ok(Custom(
+                Status::Ok,
+                json!({"message": splits, "alerts": alerts}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
 #[delete("/transaction/<id>")]
 pub async fn delete_transaction(
     db: DBConnection,
-    _auth: AuthenticatedUser,
+    auth: AuthenticatedUser,
+    store: &State<AttachmentStore>,
     id: i32,
 ) -> Result<Value, Custom<Value>> {
+    let store = store.inner().clone();
     db.run(
-        move |c| match TransactionsRepository::find_transaction(c, id) {
-            Ok(Some(_)) => match TransactionsRepository::delete_transaction(c, id) {
+        move |c| match TransactionsRepository::find_transaction(c, auth.id, id) {
+            // the attachment rows go with the transaction, their files after it
+            Ok(Some(_)) => match AttachmentRepository::find_attachments(c, auth.id, id)
+                .and_then(|attachments| {
+                    TransactionsRepository::delete_transaction(c, auth.id, id)?;
+                    remove_files(c, &store, &attachments)
+                }) {
                 Ok(_) => Ok(json!({"message":"transaction deleted"})),
                 Err(err) => {
                     eprintln!("Error fetching budget: {:?}", err);
@@ -141,3 +439,174 @@ pub async fn delete_transaction(
     )
     .await
 }
+
+/// The split duplicate whose lines the unsplit `keep` would take over when
+/// its amount differs from `keep`'s.
+fn split_mismatch(
+    c: &mut PgConnection,
+    user_id: i32,
+    keep: &Transaction,
+    duplicates: &[Transaction],
+) -> QueryResult<Option<i32>> {
+    if !TransactionsRepository::find_splits(c, user_id, keep.id)?.is_empty() {
+        return Ok(None);
+    }
+    for duplicate in duplicates {
+        if !TransactionsRepository::find_splits(c, user_id, duplicate.id)?.is_empty() {
+            return Ok((duplicate.amount != keep.amount).then_some(duplicate.id));
+        }
+    }
+    Ok(None)
+}
+
+#[post("/transaction/<id>/merge", format = "json", data = "<merge>")]
+pub async fn merge_transactions(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    merge: Json<MergeTransactions>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut duplicates = merge.into_inner().duplicates;
+    duplicates.sort_unstable();
+    duplicates.dedup();
+    if duplicates.is_empty() {
+        return Err(Custom(
+            Status::BadRequest,
+            json!({"error": "name at least one duplicate to merge"}),
+        ));
+    }
+    if duplicates.contains(&id) {
+        return Err(Custom(
+            Status::BadRequest,
+            json!({"error": "a transaction cannot be merged into itself"}),
+        ));
+    }
+
+    db.run(move |c| {
+        let found = TransactionsRepository::find_transaction(c, auth.id, id).and_then(|keep| {
+            let duplicates = duplicates
+                .iter()
+                .map(|duplicate| TransactionsRepository::find_transaction(c, auth.id, *duplicate))
+                .collect::<Result<Option<Vec<_>>, _>>()?;
+            Ok(keep.zip(duplicates))
+        });
+        match found {
+            Ok(Some((keep, duplicates))) => {
+                // the kept transaction takes over the lines of a split duplicate,
+                // which must add up to its amount
+                let split = match split_mismatch(c, auth.id, &keep, &duplicates) {
+                    Ok(split) => split,
+                    Err(_) => {
+                        return Err(Custom(
+                            Status::InternalServerError,
+                            json!({"error":"something went wrong"}),
+                        ))
+                    }
+                };
+                if let Some(split) = split {
+                    return Err(Custom(
+                        Status::Conflict,
+                        json!({"error": format!(
+                            "transaction {} is split over another amount, split transaction {} \
+                            the same way first",
+                            split, keep.id
+                        )}),
+                    ));
+                }
+                match TransactionsRepository::merge_transactions(c, auth.id, &keep, &duplicates) {
+                    Ok(merged) => Ok(Custom(Status::Ok, json!({"message": merged}))),
+                    Err(_) => Err(Custom(
+                        Status::InternalServerError,
+                        json!({"error":"something went wrong"}),
+                    )),
+                }
+            }
+            Ok(None) => Err(Custom(
+                Status::NotFound,
+                json!({"error":"Transaction not found"}),
+            )),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[post("/transactions/bulk", format = "json", data = "<bulk>")]
+pub async fn bulk_update(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    store: &State<AttachmentStore>,
+    bulk: Json<BulkRequest>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    if let Err(error) = bulk.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+    let BulkRequest {
+        mut ids,
+        filter,
+        mut operation,
+    } = bulk.into_inner();
+    let search = match filter.as_deref().map(str::parse::<TransactionQuery>).transpose() {
+        Ok(search) => search,
+        Err(err) => {
+            let error = ValidationError::from(err);
+            return Err(Custom(Status::BadRequest, json!({"errors": error})));
+        }
+    };
+    let store = store.inner().clone();
+
+    db.run(move |c| {
+        if let BulkOperation::SetCategory {
+            category,
+            category_id,
+        } = &mut operation
+        {
+            let resolved = resolve_category(c, auth.id, category)?;
+            *category_id = Some(resolved.id);
+            *category = resolved.name;
+        }
+        if let Some(search) = &search {
+            let limit = MAX_BULK_ROWS as i64 + 1;
+            ids = match TransactionsRepository::find_matching_ids(c, auth.id, search, limit) {
+                Ok(ids) => ids,
+                Err(_) => {
+                    return Err(Custom(
+                        Status::InternalServerError,
+                        json!({"error":"something went wrong"}),
+                    ))
+                }
+            };
+            if ids.len() > MAX_BULK_ROWS {
+                return Err(Custom(
+                    Status::BadRequest,
+                    json!({"error": format!(
+                        "the filter matches more than {} transactions, narrow it down",
+                        MAX_BULK_ROWS
+                    )}),
+                ));
+            }
+        } else {
+            // an id given twice would otherwise be changed twice
+            let mut seen = HashSet::new();
+            ids.retain(|id| seen.insert(*id));
+        }
+
+        let report = TransactionsRepository::apply_bulk(c, auth.id, &ids, &operation)
+            .and_then(|report| {
+                remove_files(c, &store, &report.attachments)?;
+                Ok(report)
+            });
+        match report {
+            Ok(report) if report.committed => Ok(Custom(Status::Ok, json!({"message": report}))),
+            Ok(report) => Err(Custom(Status::BadRequest, json!({"errors": report}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
@@ -0,0 +1,103 @@
+use crate::model::NewTransfer;
+use crate::repositories::{AccountRepository, TransferRepository};
+use crate::{AuthenticatedUser, DBConnection};
+use rocket::{
+    delete, get,
+    http::Status,
+    post,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+#[post("/transfer", format = "json", data = "<new_transfer>")]
+pub async fn create_transfer(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_transfer: Json<NewTransfer>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut transfer = new_transfer.into_inner();
+    transfer.user_id = auth.id;
+    if let Err(error) = transfer.validate() {
+        return Err(Custom(Status::BadRequest, json!({"errors": error})));
+    }
+
+    db.run(move |c| {
+        let accounts = AccountRepository::find_account(c, auth.id, transfer.from_account_id)
+            .and_then(|from| {
+                let to = AccountRepository::find_account(c, auth.id, transfer.to_account_id)?;
+                Ok(from.zip(to))
+            });
+        let (from, to) = match accounts {
+            Ok(Some(accounts)) => accounts,
+            Ok(None) => {
+                return Err(Custom(
+                    Status::BadRequest,
+                    json!({"error": "both accounts must be accounts of yours"}),
+                ))
+            }
+            Err(_) => {
+                return Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                ))
+            }
+        };
+        // amounts are never converted
+        if from.currency != to.currency {
+            return Err(Custom(
+                Status::BadRequest,
+                json!({"error": "money can only be transferred between accounts in the same currency"}),
+            ));
+        }
+
+        match TransferRepository::create_transfer(c, transfer) {
+            Ok(transfer) => Ok(Custom(Status::Created, json!({"message": transfer}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[get("/transfers?<account>")]
+pub async fn view_transfers(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    account: Option<i32>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match TransferRepository::find_transfers(c, auth.id, account) {
+            Ok(transfers) => Ok(Custom(Status::Ok, json!(transfers))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[delete("/transfer/<id>")]
+pub async fn delete_transfer(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match TransferRepository::delete_transfer(c, auth.id, id) {
+            Ok(0) => Err(Custom(
+                Status::NotFound,
+                json!({"error":"Transfer not found"}),
+            )),
+            Ok(_) => Ok(Custom(Status::Ok, json!({"message": "Transfer deleted"}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
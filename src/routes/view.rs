@@ -0,0 +1,209 @@
+use crate::model::{normalize_tag, NewSavedView, ViewFilters};
+use crate::repositories::{SavedViewRepository, TransactionsRepository};
+use crate::routes::category::resolve_category;
+use crate::{AuthenticatedUser, DBConnection};
+use chrono::Local;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::PgConnection;
+use rocket::{
+    delete, get,
+    http::Status,
+    post, put,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json},
+};
+use serde_json::Value;
+
+fn write_error(err: DieselError) -> Custom<Value> {
+    match err {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Custom(
+            Status::Conflict,
+            json!({"error": "a view with this name already exists"}),
+        ),
+        _ => Custom(
+            Status::InternalServerError,
+            json!({"error":"something went wrong"}),
+        ),
+    }
+}
+
+/// Validates a view and checks its categories, storing its filters the way
+/// they were understood.
+fn check_view(
+    c: &mut PgConnection,
+    user_id: i32,
+    view: &mut NewSavedView,
+) -> Result<(), Custom<Value>> {
+    view.user_id = user_id;
+    view.name = view.name.trim().to_owned();
+    let mut filters = match view.validate() {
+        Ok(filters) => filters,
+        Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+    };
+    for category in &filters.categories {
+        resolve_category(c, user_id, category)?;
+    }
+    filters.tag = filters.tag.as_deref().map(normalize_tag);
+    view.filters = json!(filters);
+    Ok(())
+}
+
+#[post("/view", format = "json", data = "<new_view>")]
+pub async fn create_view(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    new_view: Json<NewSavedView>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut view = new_view.into_inner();
+
+    db.run(move |c| {
+        check_view(c, auth.id, &mut view)?;
+        match SavedViewRepository::create_view(c, view) {
+            Ok(view) => Ok(Custom(Status::Created, json!({"message": view}))),
+            Err(err) => Err(write_error(err)),
+        }
+    })
+    .await
+}
+
+#[get("/views?<pinned>")]
+pub async fn view_views(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    pinned: Option<bool>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let pinned_only = pinned.unwrap_or(false);
+
+    db.run(
+        move |c| match SavedViewRepository::find_views(c, auth.id, pinned_only) {
+            Ok(views) => Ok(Custom(Status::Ok, json!(views))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[get("/view/<id>")]
+pub async fn view_view(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match SavedViewRepository::find_view(c, auth.id, id) {
+            Ok(Some(view)) => Ok(Custom(Status::Ok, json!(view))),
+            Ok(None) => Err(Custom(Status::NotFound, json!({"error":"View not found"}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
+
+#[get("/views/<id>/results?<cursor>&<limit>")]
+pub async fn view_results(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let today = Local::now().date_naive();
+
+    db.run(move |c| {
+        let view = match SavedViewRepository::find_view(c, auth.id, id) {
+            Ok(Some(view)) => view,
+            Ok(None) => return Err(Custom(Status::NotFound, json!({"error":"View not found"}))),
+            Err(_) => {
+                return Err(Custom(
+                    Status::InternalServerError,
+                    json!({"error":"something went wrong"}),
+                ))
+            }
+        };
+        let Ok(filters) = serde_json::from_value::<ViewFilters>(view.filters.clone()) else {
+            return Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            ));
+        };
+        let parsed = filters
+            .list_query(today, cursor, limit)
+            .parse()
+            .and_then(|filter| Ok((filter, filters.search()?)));
+        let (filter, search) = match parsed {
+            Ok(parsed) => parsed,
+            Err(error) => return Err(Custom(Status::BadRequest, json!({"errors": error}))),
+        };
+
+        match TransactionsRepository::find_view_results(
+            c,
+            auth.id,
+            &filter,
+            search.as_ref(),
+            &filters.categories,
+        ) {
+            Ok((page, totals)) => {
+                let rows: Vec<Value> = page.data.iter().map(|row| filters.project(row)).collect();
+                Ok(Custom(
+                    Status::Ok,
+                    json!({
+                        "view": view,
+                        "data": rows,
+                        "next_cursor": page.next_cursor,
+                        "totals": totals,
+                    }),
+                ))
+            }
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        }
+    })
+    .await
+}
+
+#[put("/view/<id>", format = "json", data = "<view>")]
+pub async fn update_view(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+    view: Json<NewSavedView>,
+) -> Result<Custom<Value>, Custom<Value>> {
+    let mut view = view.into_inner();
+
+    db.run(move |c| {
+        check_view(c, auth.id, &mut view)?;
+        match SavedViewRepository::update_view(c, auth.id, id, view) {
+            Ok(Some(view)) => Ok(Custom(Status::Ok, json!({"message": view}))),
+            Ok(None) => Err(Custom(Status::NotFound, json!({"error":"View not found"}))),
+            Err(err) => Err(write_error(err)),
+        }
+    })
+    .await
+}
+
+#[delete("/view/<id>")]
+pub async fn delete_view(
+    db: DBConnection,
+    auth: AuthenticatedUser,
+    id: i32,
+) -> Result<Custom<Value>, Custom<Value>> {
+    db.run(
+        move |c| match SavedViewRepository::delete_view(c, auth.id, id) {
+            Ok(0) => Err(Custom(Status::NotFound, json!({"error":"View not found"}))),
+            Ok(_) => Ok(Custom(Status::Ok, json!({"message": "View deleted"}))),
+            Err(_) => Err(Custom(
+                Status::InternalServerError,
+                json!({"error":"something went wrong"}),
+            )),
+        },
+    )
+    .await
+}
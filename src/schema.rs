@@ -1,5 +1,44 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    accounts (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Text,
+        kind -> Text,
+        currency -> Bpchar,
+        opening_balance -> Numeric,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    alerts (id) {
+        id -> Int4,
+        user_id -> Int4,
+        budget_id -> Int4,
+        transaction_id -> Nullable<Int4>,
+        category -> Text,
+        threshold -> Int4,
+        percent_used -> Numeric,
+        created_at -> Timestamp,
+        read_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    attachments (id) {
+        id -> Int4,
+        user_id -> Int4,
+        transaction_id -> Int4,
+        file_name -> Text,
+        content_type -> Text,
+        size -> Int8,
+        sha256 -> Bpchar,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     budgets (id) {
         id -> Int4,
@@ -8,6 +47,75 @@ diesel::table! {
         amount -> Numeric,
         start_date -> Date,
         end_date -> Date,
+        category_id -> Nullable<Int4>,
+        recurrence -> Nullable<Text>,
+        rollover -> Text,
+        rollover_amount -> Numeric,
+        previous_budget_id -> Nullable<Int4>,
+        alert_thresholds -> Array<Nullable<Int4>>,
+    }
+}
+
+diesel::table! {
+    categories (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Text,
+        parent_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    category_rules (id) {
+        id -> Int4,
+        user_id -> Int4,
+        position -> Int4,
+        description_contains -> Nullable<Text>,
+        description_regex -> Nullable<Text>,
+        min_amount -> Nullable<Numeric>,
+        max_amount -> Nullable<Numeric>,
+        day_of_month -> Nullable<Int4>,
+        category -> Nullable<Text>,
+        category_id -> Nullable<Int4>,
+        new_description -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    classifier_categories (user_id, category_id) {
+        user_id -> Int4,
+        category_id -> Int4,
+        documents -> Int4,
+        tokens -> Int4,
+    }
+}
+
+diesel::table! {
+    classifier_tokens (user_id, token, category_id) {
+        user_id -> Int4,
+        category_id -> Int4,
+        token -> Text,
+        count -> Int4,
+    }
+}
+
+diesel::table! {
+    envelope_allocations (id) {
+        id -> Int4,
+        user_id -> Int4,
+        category_id -> Int4,
+        month -> Date,
+        amount -> Numeric,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    goal_tags (goal_id, tag_id) {
+        goal_id -> Int4,
+        tag_id -> Int4,
     }
 }
 
@@ -22,6 +130,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    import_profiles (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Text,
+        delimiter -> Text,
+        has_header -> Bool,
+        date_column -> Int4,
+        date_format -> Text,
+        description_column -> Nullable<Int4>,
+        category_column -> Nullable<Int4>,
+        amount_column -> Nullable<Int4>,
+        debit_column -> Nullable<Int4>,
+        credit_column -> Nullable<Int4>,
+        decimal_separator -> Text,
+        sign_convention -> Text,
+        default_category -> Text,
+    }
+}
+
 diesel::table! {
     income (id) {
         id -> Int4,
@@ -29,6 +157,106 @@ diesel::table! {
         amount -> Numeric,
         source -> Text,
         date -> Date,
+        external_id -> Nullable<Text>,
+        account_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    income_tags (income_id, tag_id) {
+        income_id -> Int4,
+        tag_id -> Int4,
+    }
+}
+
+diesel::table! {
+    payee_aliases (id) {
+        id -> Int4,
+        user_id -> Int4,
+        payee_id -> Int4,
+        alias -> Text,
+    }
+}
+
+diesel::table! {
+    payees (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Text,
+        category -> Nullable<Text>,
+        category_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    recurring_occurrences (id) {
+        id -> Int4,
+        template_id -> Int4,
+        occurrence_date -> Date,
+        status -> Text,
+        amount -> Nullable<Numeric>,
+        description -> Nullable<Text>,
+        date -> Nullable<Date>,
+        transaction_id -> Nullable<Int4>,
+        income_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    recurring_templates (id) {
+        id -> Int4,
+        user_id -> Int4,
+        kind -> Text,
+        amount -> Numeric,
+        category -> Text,
+        category_id -> Nullable<Int4>,
+        description -> Nullable<Text>,
+        rrule -> Text,
+        start_date -> Date,
+        end_date -> Nullable<Date>,
+        created_at -> Timestamp,
+        account_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    saved_views (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Text,
+        filters -> Jsonb,
+        pinned -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    transaction_splits (id) {
+        id -> Int4,
+        transaction_id -> Int4,
+        user_id -> Int4,
+        category -> Text,
+        category_id -> Int4,
+        amount -> Numeric,
+        memo -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    transaction_tags (transaction_id, tag_id) {
+        transaction_id -> Int4,
+        tag_id -> Int4,
     }
 }
 
@@ -40,6 +268,23 @@ diesel::table! {
         category -> Text,
         description -> Nullable<Text>,
         date -> Date,
+        category_id -> Nullable<Int4>,
+        external_id -> Nullable<Text>,
+        payee_id -> Nullable<Int4>,
+        account_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    transfers (id) {
+        id -> Int4,
+        user_id -> Int4,
+        from_account_id -> Int4,
+        to_account_id -> Int4,
+        amount -> Numeric,
+        date -> Date,
+        description -> Nullable<Text>,
+        created_at -> Timestamp,
     }
 }
 
@@ -49,18 +294,85 @@ diesel::table! {
         username -> Text,
         email -> Text,
         password_hash -> Text,
+        budgeting_mode -> Text,
+        envelope_start -> Nullable<Date>,
+        rule_match_mode -> Text,
+        classifier_trained_at -> Nullable<Timestamp>,
     }
 }
 
+diesel::joinable!(accounts -> users (user_id));
+diesel::joinable!(alerts -> budgets (budget_id));
+diesel::joinable!(alerts -> transactions (transaction_id));
+diesel::joinable!(alerts -> users (user_id));
+diesel::joinable!(attachments -> transactions (transaction_id));
+diesel::joinable!(attachments -> users (user_id));
+diesel::joinable!(budgets -> categories (category_id));
 diesel::joinable!(budgets -> users (user_id));
+diesel::joinable!(categories -> users (user_id));
+diesel::joinable!(category_rules -> categories (category_id));
+diesel::joinable!(category_rules -> users (user_id));
+diesel::joinable!(classifier_categories -> categories (category_id));
+diesel::joinable!(classifier_categories -> users (user_id));
+diesel::joinable!(classifier_tokens -> categories (category_id));
+diesel::joinable!(classifier_tokens -> users (user_id));
+diesel::joinable!(envelope_allocations -> categories (category_id));
+diesel::joinable!(envelope_allocations -> users (user_id));
+diesel::joinable!(goal_tags -> goals (goal_id));
+diesel::joinable!(goal_tags -> tags (tag_id));
 diesel::joinable!(goals -> users (user_id));
+diesel::joinable!(import_profiles -> users (user_id));
+diesel::joinable!(income -> accounts (account_id));
 diesel::joinable!(income -> users (user_id));
+diesel::joinable!(income_tags -> income (income_id));
+diesel::joinable!(income_tags -> tags (tag_id));
+diesel::joinable!(payee_aliases -> payees (payee_id));
+diesel::joinable!(payee_aliases -> users (user_id));
+diesel::joinable!(payees -> categories (category_id));
+diesel::joinable!(payees -> users (user_id));
+diesel::joinable!(recurring_occurrences -> income (income_id));
+diesel::joinable!(recurring_occurrences -> recurring_templates (template_id));
+diesel::joinable!(recurring_occurrences -> transactions (transaction_id));
+diesel::joinable!(recurring_templates -> accounts (account_id));
+diesel::joinable!(recurring_templates -> categories (category_id));
+diesel::joinable!(recurring_templates -> users (user_id));
+diesel::joinable!(saved_views -> users (user_id));
+diesel::joinable!(tags -> users (user_id));
+diesel::joinable!(transaction_splits -> categories (category_id));
+diesel::joinable!(transaction_splits -> transactions (transaction_id));
+diesel::joinable!(transaction_splits -> users (user_id));
+diesel::joinable!(transaction_tags -> tags (tag_id));
+diesel::joinable!(transaction_tags -> transactions (transaction_id));
+diesel::joinable!(transactions -> accounts (account_id));
+diesel::joinable!(transactions -> categories (category_id));
+diesel::joinable!(transactions -> payees (payee_id));
 diesel::joinable!(transactions -> users (user_id));
+diesel::joinable!(transfers -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    accounts,
+    alerts,
+    attachments,
     budgets,
+    categories,
+    category_rules,
+    classifier_categories,
+    classifier_tokens,
+    envelope_allocations,
+    goal_tags,
     goals,
+    import_profiles,
     income,
+    income_tags,
+    payee_aliases,
+    payees,
+    recurring_occurrences,
+    recurring_templates,
+    saved_views,
+    tags,
+    transaction_splits,
+    transaction_tags,
     transactions,
+    transfers,
     users,
 );
@@ -0,0 +1,175 @@
+//! Storage of attachment files on the local disk.
+//!
+//! Files are stored by the SHA-256 of their content, under
+//! `<attachments_dir>/<first two hex digits>/<hash>`, so a receipt uploaded
+//! twice takes up space once. The directory is the `attachments_dir` setting
+//! (e.g. `ROCKET_ATTACHMENTS_DIR=/var/lib/finance_manager`).
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+/// Largest file that can be attached, in MiB.
+pub const MAX_ATTACHMENT_SIZE: u64 = 10;
+
+/// The types of file that can be attached: images and PDFs.
+const ALLOWED_TYPES: [&str; 6] = [
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "image/heif",
+    "application/pdf",
+];
+
+fn default_attachments_dir() -> PathBuf {
+    PathBuf::from("attachments")
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AttachmentStore {
+    #[serde(default = "default_attachments_dir")]
+    pub attachments_dir: PathBuf,
+}
+
+impl AttachmentStore {
+    /// Where the file with `sha256`, a hex encoded SHA-256, is stored.
+    pub fn path(&self, sha256: &str) -> PathBuf {
+        assert!(
+            sha256.len() == 64 && sha256.bytes().all(|byte| byte.is_ascii_hexdigit()),
+            "not a SHA-256: {:?}",
+            sha256
+        );
+        self.attachments_dir.join(&sha256[..2]).join(sha256)
+    }
+
+    /// Writes `content` unless a file with the same hash is already stored.
+    pub fn store(&self, sha256: &str, content: &[u8]) -> io::Result<()> {
+        let path = self.path(sha256);
+        if path.exists() {
+            return Ok(());
+        }
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        // written under a temporary name first, so a failed upload never
+        // leaves a truncated file under the final one
+        let partial = path.with_extension("partial");
+        fs::write(&partial, content)?;
+        fs::rename(&partial, &path)
+    }
+
+    /// Removes a stored file, which may already be gone.
+    pub fn remove(&self, sha256: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(sha256)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The hex encoded SHA-256 of `content`.
+pub fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// The MIME type of `content` judged by its first bytes, `None` when it is
+/// not a type that can be attached. What the client claims is ignored.
+pub fn sniff(content: &[u8]) -> Option<&'static str> {
+    let mime_type = infer::get(content)?.mime_type();
+    ALLOWED_TYPES.contains(&mime_type).then_some(mime_type)
+}
+
+/// A file name that is safe to store and send back in a header: no
+/// directories, quotes or control characters, and at most 255 characters.
+pub fn file_name(raw: Option<&str>) -> String {
+    let name: String = raw
+        .and_then(|raw| raw.rsplit(['/', '\\']).next())
+        .unwrap_or_default()
+        .chars()
+        .filter(|ch| !ch.is_control() && *ch != '"')
+        .take(255)
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => "attachment".to_owned(),
+        name => name.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn keeps_only_the_base_name() {
+        assert_eq!(file_name(Some("receipt.pdf")), "receipt.pdf");
+        assert_eq!(file_name(Some("/tmp/scans/receipt.pdf")), "receipt.pdf");
+        assert_eq!(file_name(Some("C:\\Users\\me\\receipt.pdf")), "receipt.pdf");
+        assert_eq!(file_name(Some("../../etc/passwd")), "passwd");
+        assert_eq!(file_name(Some("scans/..")), "attachment");
+        assert_eq!(file_name(Some("..")), "attachment");
+        assert_eq!(file_name(Some("scans/")), "attachment");
+        assert_eq!(file_name(None), "attachment");
+    }
+
+    #[test]
+    fn strips_quotes_and_control_characters() {
+        assert_eq!(file_name(Some("my \"receipt\".pdf")), "my receipt.pdf");
+        assert_eq!(file_name(Some("receipt\r\n.pdf")), "receipt.pdf");
+        assert_eq!(file_name(Some(" \"\" ")), "attachment");
+    }
+
+    #[test]
+    fn caps_the_length() {
+        let long = format!("{}.pdf", "é".repeat(300));
+        let name = file_name(Some(&long));
+
+        assert_eq!(name.chars().count(), 255);
+        assert!(name.chars().all(|ch| ch == 'é'));
+    }
+
+    #[test]
+    fn sniffs_allowed_types() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let jpeg = b"\xff\xd8\xff\xe0\0\x10JFIF\0";
+        let pdf = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n";
+
+        assert_eq!(sniff(png), Some("image/png"));
+        assert_eq!(sniff(jpeg), Some("image/jpeg"));
+        assert_eq!(sniff(pdf), Some("application/pdf"));
+    }
+
+    #[test]
+    fn refuses_other_types() {
+        assert_eq!(sniff(b"PK\x03\x04\x14\0\0\0"), None);
+        assert_eq!(sniff(b"MZ\x90\0\x03\0"), None);
+        assert_eq!(sniff(b"<html><body>receipt</body></html>"), None);
+        assert_eq!(sniff(b""), None);
+    }
+
+    #[test]
+    fn stores_files_by_hash() {
+        let store = AttachmentStore {
+            attachments_dir: PathBuf::from("attachments"),
+        };
+
+        assert_eq!(sha256(b""), EMPTY_SHA256);
+        assert_eq!(
+            store.path(EMPTY_SHA256),
+            PathBuf::from("attachments").join("e3").join(EMPTY_SHA256)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "not a SHA-256")]
+    fn refuses_paths_that_are_not_hashes() {
+        let store = AttachmentStore {
+            attachments_dir: PathBuf::from("attachments"),
+        };
+        store.path("../../etc/passwd");
+    }
+}
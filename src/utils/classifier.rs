@@ -0,0 +1,225 @@
+//! Category suggestions learned from a user's own history: a multinomial
+//! naive Bayes classifier over the words of a transaction's description and
+//! the size of its amount.
+//!
+//! The counts it learns from live in `classifier_categories` and
+//! `classifier_tokens`. They are built from all categorized transactions the
+//! first time a user asks for suggestions and then kept up to date as
+//! transactions are added, recategorized and removed.
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use std::collections::HashMap;
+
+/// How many suggestions are returned for a transaction.
+pub const SUGGESTION_COUNT: usize = 3;
+
+/// What the classifier learned about one category.
+pub struct CategoryCounts {
+    pub category_id: i32,
+    /// Transactions learned.
+    pub documents: i32,
+    /// Features learned, counting repeats.
+    pub tokens: i32,
+    /// How often each feature of the transaction being classified was seen.
+    pub features: HashMap<String, i32>,
+}
+
+impl CategoryCounts {
+    /// Takes a learned transaction with `features` back out.
+    pub fn remove(&mut self, features: &HashMap<String, i32>) {
+        self.documents -= 1;
+        for (feature, count) in features {
+            self.tokens -= count;
+            if let Some(seen) = self.features.get_mut(feature) {
+                *seen -= count;
+            }
+        }
+    }
+}
+
+/// The features of a transaction with how often each occurs: the lowercased
+/// words of its description, leaving out single characters and plain
+/// numbers, and the power of two its amount falls under.
+pub fn features(description: Option<&str>, amount: &BigDecimal) -> HashMap<String, i32> {
+    let mut features: HashMap<String, i32> = HashMap::new();
+    if let Some(description) = description {
+        for word in description
+            .to_lowercase()
+            .split(|ch: char| !ch.is_alphanumeric())
+            .filter(|word| word.chars().count() > 1)
+            .filter(|word| !word.chars().all(|ch| ch.is_ascii_digit()))
+        {
+            *features.entry(word.to_owned()).or_default() += 1;
+        }
+    }
+    *features.entry(amount_bucket(amount)).or_default() += 1;
+    features
+}
+
+fn amount_bucket(amount: &BigDecimal) -> String {
+    let amount = amount.to_f64().unwrap_or_default().abs();
+    if amount < 1.0 {
+        "amount:<1".to_owned()
+    } else {
+        format!("amount:2^{}", amount.log2().floor() as i32)
+    }
+}
+
+/// The probability of each category given `features`, most likely first.
+/// `vocabulary` is the number of distinct features the user's classifier
+/// knows; features no category has seen are ignored.
+pub fn rank(
+    categories: &[CategoryCounts],
+    features: &HashMap<String, i32>,
+    vocabulary: i64,
+) -> Vec<(i32, f64)> {
+    let categories: Vec<&CategoryCounts> = categories
+        .iter()
+        .filter(|category| category.documents > 0)
+        .collect();
+    let documents: i32 = categories.iter().map(|category| category.documents).sum();
+    if documents == 0 {
+        return Vec::new();
+    }
+    let known: Vec<(&String, i32)> = features
+        .iter()
+        .filter(|(feature, _)| {
+            categories.iter().any(|category| {
+                category
+                    .features
+                    .get(*feature)
+                    .is_some_and(|seen| *seen > 0)
+            })
+        })
+        .map(|(feature, count)| (feature, *count))
+        .collect();
+    let vocabulary = vocabulary.max(1) as f64;
+
+    let scores: Vec<(i32, f64)> = categories
+        .iter()
+        .map(|category| {
+            let mut score = (category.documents as f64 / documents as f64).ln();
+            for (feature, count) in &known {
+                let seen = category.features.get(*feature).copied().unwrap_or_default();
+                // add-one smoothing, so a feature the category never had
+                // lowers its score instead of ruling it out
+                let likelihood =
+                    (seen.max(0) as f64 + 1.0) / (category.tokens.max(0) as f64 + vocabulary);
+                score += *count as f64 * likelihood.ln();
+            }
+            (category.category_id, score)
+        })
+        .collect();
+
+    // shift by the best score before exponentiating so small log
+    // probabilities do not all round to zero
+    let best = scores
+        .iter()
+        .map(|(_, score)| *score)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let total: f64 = scores.iter().map(|(_, score)| (score - best).exp()).sum();
+    let mut ranked: Vec<(i32, f64)> = scores
+        .into_iter()
+        .map(|(category_id, score)| (category_id, (score - best).exp() / total))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(
+        category_id: i32,
+        documents: i32,
+        tokens: i32,
+        seen: &[(&str, i32)],
+    ) -> CategoryCounts {
+        CategoryCounts {
+            category_id,
+            documents,
+            tokens,
+            features: seen
+                .iter()
+                .map(|(feature, count)| (feature.to_string(), *count))
+                .collect(),
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn counts_words_and_the_amount() {
+        let found = features(Some("NETFLIX.com 12 x Netflix"), &BigDecimal::from(12));
+
+        assert_eq!(found.len(), 3);
+        assert_eq!(found["netflix"], 2);
+        assert_eq!(found["com"], 1);
+        assert_eq!(found["amount:2^3"], 1);
+        assert_eq!(features(None, &BigDecimal::from(5)).len(), 1);
+    }
+
+    #[test]
+    fn buckets_amounts_by_powers_of_two() {
+        let bucket = |amount: &str| amount_bucket(&amount.parse().unwrap());
+
+        assert_eq!(bucket("0.99"), "amount:<1");
+        assert_eq!(bucket("0"), "amount:<1");
+        assert_eq!(bucket("1"), "amount:2^0");
+        assert_eq!(bucket("1.99"), "amount:2^0");
+        assert_eq!(bucket("2"), "amount:2^1");
+        assert_eq!(bucket("-8"), "amount:2^3");
+        assert_eq!(bucket("1023.99"), "amount:2^9");
+    }
+
+    #[test]
+    fn smooths_features_a_category_never_saw() {
+        let categories = [counts(1, 3, 6, &[("netflix", 3)]), counts(2, 1, 2, &[])];
+        let features = HashMap::from([("netflix".to_owned(), 1)]);
+        let ranked = rank(&categories, &features, 4);
+
+        // 3/4 * (3 + 1) / (6 + 4) against 1/4 * (0 + 1) / (2 + 4)
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].0, 1);
+        assert!(close(ranked[0].1, 0.3 / (0.3 + 0.25 / 6.0)));
+        assert_eq!(ranked[1].0, 2);
+        assert!(ranked[1].1 > 0.0);
+    }
+
+    #[test]
+    fn probabilities_sum_to_one() {
+        let categories = [
+            counts(1, 5, 20, &[("coffee", 4), ("amount:2^2", 5)]),
+            counts(2, 8, 30, &[("coffee", 1), ("amount:2^5", 8)]),
+            counts(3, 2, 6, &[("amount:2^2", 1)]),
+        ];
+        let features = features(Some("Coffee Bar"), &BigDecimal::from(5));
+        let ranked = rank(&categories, &features, 12);
+
+        assert_eq!(ranked.len(), 3);
+        assert!(close(ranked.iter().map(|(_, p)| p).sum::<f64>(), 1.0));
+        assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(ranked[0].0, 1);
+    }
+
+    #[test]
+    fn ignores_unknown_features_and_empty_categories() {
+        let categories = [
+            counts(1, 3, 6, &[("netflix", 3)]),
+            counts(2, 1, 2, &[]),
+            counts(3, 0, 0, &[]),
+        ];
+        let features = HashMap::from([("spotify".to_owned(), 1)]);
+        let ranked = rank(&categories, &features, 4);
+
+        // only the share of transactions is left
+        assert_eq!(ranked.len(), 2);
+        assert!(close(ranked[0].1, 0.75));
+        assert!(close(ranked[1].1, 0.25));
+        assert!(rank(&[counts(1, 0, 0, &[])], &features, 4).is_empty());
+        assert!(rank(&[], &features, 4).is_empty());
+    }
+}
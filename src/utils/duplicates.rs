@@ -0,0 +1,237 @@
+//! Fuzzy matching of transactions that were probably entered twice: the
+//! same amount, dates at most a few days apart and similar descriptions.
+//!
+//! Descriptions are compared with the Dice coefficient of their character
+//! bigrams, so `AMAZON MKTPLACE` and `Amazon Marketplace` match while
+//! `Rent` and `Groceries` do not. A transaction without a description
+//! matches nothing, since the amount and date alone are too common to tell
+//! a duplicate from a second purchase.
+
+use crate::model::Transaction;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+/// How many days apart the dates of two duplicates may be by default.
+pub const DUPLICATE_WINDOW_DAYS: u64 = 3;
+
+/// Smallest description similarity, between 0 and 1, of two duplicates.
+pub const MIN_SIMILARITY: f64 = 0.5;
+
+/// Similarity of two descriptions between 0 (nothing in common) and 1,
+/// 0 when either is missing.
+pub fn similarity(a: Option<&str>, b: Option<&str>) -> f64 {
+    let (Some(a), Some(b)) = (bigrams(a), bigrams(b)) else {
+        return 0.0;
+    };
+    let total = a.len() + b.len();
+    let mut remaining: HashMap<&(char, char), usize> = HashMap::new();
+    for bigram in &b {
+        *remaining.entry(bigram).or_default() += 1;
+    }
+    let mut shared = 0;
+    for bigram in &a {
+        if let Some(count) = remaining.get_mut(bigram).filter(|count| **count > 0) {
+            *count -= 1;
+            shared += 1;
+        }
+    }
+    2.0 * shared as f64 / total as f64
+}
+
+/// Bigrams of the lowercased letters and digits of each word, `None` when
+/// there are none.
+fn bigrams(description: Option<&str>) -> Option<Vec<(char, char)>> {
+    let description = description?.to_lowercase();
+    let bigrams: Vec<(char, char)> = description
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| {
+            // pad words so that single letters still count
+            let chars: Vec<char> = std::iter::once(' ')
+                .chain(word.chars())
+                .chain(std::iter::once(' '))
+                .collect();
+            chars
+                .windows(2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    (!bigrams.is_empty()).then_some(bigrams)
+}
+
+fn within_window(a: NaiveDate, b: NaiveDate, window_days: u64) -> bool {
+    (a - b).num_days().unsigned_abs() <= window_days
+}
+
+/// Whether a transaction of `amount` on `date` described as `description`
+/// looks like a duplicate of `other`.
+pub fn is_duplicate(
+    amount: &BigDecimal,
+    date: NaiveDate,
+    description: Option<&str>,
+    other: &Transaction,
+    window_days: u64,
+) -> bool {
+    *amount == other.amount
+        && within_window(date, other.date, window_days)
+        && similarity(description, other.description.as_deref()) >= MIN_SIMILARITY
+}
+
+/// Splits `transactions` into groups of suspected duplicates. Two
+/// transactions are in the same group when a chain of duplicate pairs links
+/// them; transactions without duplicates are left out. Groups are ordered
+/// by their first date and their members by date.
+pub fn group_duplicates(
+    mut transactions: Vec<Transaction>,
+    window_days: u64,
+) -> Vec<Vec<Transaction>> {
+    transactions.sort_by(|a, b| (&a.amount, a.date, a.id).cmp(&(&b.amount, b.date, b.id)));
+
+    let mut parents: Vec<usize> = (0..transactions.len()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+    for (index, transaction) in transactions.iter().enumerate() {
+        // sorted by amount and date, so candidates follow each other
+        for (offset, other) in transactions[index + 1..].iter().enumerate() {
+            if other.amount != transaction.amount
+                || !within_window(transaction.date, other.date, window_days)
+            {
+                break;
+            }
+            if is_duplicate(
+                &transaction.amount,
+                transaction.date,
+                transaction.description.as_deref(),
+                other,
+                window_days,
+            ) {
+                let (a, b) = (
+                    root(&mut parents, index),
+                    root(&mut parents, index + 1 + offset),
+                );
+                parents[a] = b;
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<Transaction>> = HashMap::new();
+    for (index, transaction) in transactions.into_iter().enumerate() {
+        let group = root(&mut parents, index);
+        groups.entry(group).or_default().push(transaction);
+    }
+    let mut groups: Vec<Vec<Transaction>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort_by_key(|transaction| (transaction.date, transaction.id));
+            group
+        })
+        .collect();
+    groups.sort_by_key(|group| (group[0].date, group[0].id));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(id: i32, amount: i32, day: u32, description: Option<&str>) -> Transaction {
+        Transaction {
+            id,
+            user_id: Some(1),
+            amount: BigDecimal::from(amount),
+            category: "Other".to_owned(),
+            description: description.map(str::to_owned),
+            date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+            category_id: None,
+            external_id: None,
+            payee_id: None,
+            account_id: None,
+        }
+    }
+
+    fn ids(groups: &[Vec<Transaction>]) -> Vec<Vec<i32>> {
+        groups
+            .iter()
+            .map(|group| group.iter().map(|transaction| transaction.id).collect())
+            .collect()
+    }
+
+    #[test]
+    fn compares_descriptions() {
+        assert_eq!(similarity(Some("Netflix"), Some("netflix")), 1.0);
+        assert!(similarity(Some("NETFLIX.COM"), Some("Netflix")) >= MIN_SIMILARITY);
+        assert!(similarity(Some("AMAZON MKTPLACE"), Some("Amazon Marketplace")) >= MIN_SIMILARITY);
+        assert!(similarity(Some("Rent"), Some("Groceries")) < MIN_SIMILARITY);
+        assert_eq!(similarity(Some("a"), Some("b")), 0.0);
+    }
+
+    #[test]
+    fn missing_descriptions_match_nothing() {
+        assert_eq!(similarity(None, Some("Coffee")), 0.0);
+        assert_eq!(similarity(Some("Coffee"), None), 0.0);
+        assert_eq!(similarity(None, None), 0.0);
+        assert_eq!(similarity(Some("--"), Some("--")), 0.0);
+    }
+
+    #[test]
+    fn allows_dates_up_to_the_window_apart() {
+        let other = transaction(1, 10, 10, Some("Coffee"));
+        let amount = BigDecimal::from(10);
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+
+        assert!(is_duplicate(&amount, date(13), Some("Coffee"), &other, 3));
+        assert!(is_duplicate(&amount, date(7), Some("Coffee"), &other, 3));
+        assert!(!is_duplicate(&amount, date(14), Some("Coffee"), &other, 3));
+        assert!(!is_duplicate(&amount, date(6), Some("Coffee"), &other, 3));
+        assert!(is_duplicate(&amount, date(10), Some("Coffee"), &other, 0));
+        assert!(!is_duplicate(
+            &BigDecimal::from(11),
+            date(10),
+            Some("Coffee"),
+            &other,
+            3
+        ));
+    }
+
+    #[test]
+    fn groups_chains_of_duplicates() {
+        let transactions = vec![
+            transaction(5, 10, 9, Some("Coffee Shop")),
+            transaction(1, 10, 1, Some("Coffee Shop")),
+            // linked to 1 and 5 only through 3
+            transaction(3, 10, 5, Some("COFFEE SHOP #12")),
+            transaction(2, 10, 20, Some("Coffee Shop")),
+            transaction(4, 25, 1, Some("Coffee Shop")),
+            transaction(6, 25, 2, Some("Rent")),
+            transaction(7, 25, 2, None),
+            transaction(8, 25, 3, None),
+        ];
+        let groups = group_duplicates(transactions, 4);
+
+        assert_eq!(ids(&groups), [vec![1, 3, 5]]);
+        assert!(group_duplicates(Vec::new(), 3).is_empty());
+    }
+
+    #[test]
+    fn orders_groups_by_date() {
+        let transactions = vec![
+            transaction(1, 30, 20, Some("Gym")),
+            transaction(2, 30, 21, Some("Gym")),
+            transaction(3, 10, 5, Some("Taxi")),
+            transaction(4, 10, 5, Some("Taxi")),
+        ];
+
+        assert_eq!(
+            ids(&group_duplicates(transactions, 3)),
+            [vec![3, 4], vec![1, 2]]
+        );
+    }
+}
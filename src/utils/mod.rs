@@ -1,2 +1,10 @@
+pub mod attachments;
+pub mod classifier;
+pub mod duplicates;
 pub mod hashing;
 pub mod jwt_token;
+pub mod payees;
+pub mod query;
+pub mod rules;
+pub mod schedule;
+pub mod search;
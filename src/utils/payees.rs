@@ -0,0 +1,171 @@
+//! Maps the raw descriptions of transactions to payees.
+//!
+//! A description is first normalized: card processor prefixes (`SQ *`,
+//! `PAYPAL *`, ..), everything after a `*` reference, domain endings, words
+//! with digits (store numbers, references), filler words such as `MKTP` and a
+//! trailing state or country code are dropped, and a few well-known
+//! abbreviations are spelled out. `AMZN MKTP US*2K3` and `Amazon.com` both
+//! become `amazon`.
+//!
+//! A normalized description belongs to the payee with the longest alias
+//! that it equals or starts with, word for word.
+
+/// Prefixes payment processors put in front of the merchant's name.
+const PROCESSOR_PREFIXES: [&str; 6] = ["sq *", "sq*", "tst*", "tst *", "paypal *", "pp*"];
+
+/// Domain endings dropped from names like `Amazon.com`.
+const DOMAIN_ENDINGS: [&str; 5] = [".com", ".net", ".org", ".co.uk", ".de"];
+
+/// Words that say nothing about who was paid.
+const FILLER_WORDS: [&str; 12] = [
+    "pos",
+    "purchase",
+    "debit",
+    "card",
+    "payment",
+    "mktp",
+    "marketplace",
+    "inc",
+    "llc",
+    "ltd",
+    "gmbh",
+    "www",
+];
+
+/// State, province and country codes banks put after the merchant's name.
+const REGION_CODES: [&str; 81] = [
+    "al", "ak", "az", "ar", "ca", "co", "ct", "de", "dc", "fl", "ga", "hi", "id", "il", "in", "ia",
+    "ks", "ky", "la", "me", "md", "ma", "mi", "mn", "ms", "mo", "mt", "ne", "nv", "nh", "nj", "nm",
+    "ny", "nc", "nd", "oh", "ok", "or", "pa", "ri", "sc", "sd", "tn", "tx", "ut", "vt", "va", "wa",
+    "wv", "wi", "wy", "ab", "bc", "mb", "nb", "nl", "ns", "on", "pe", "qc", "sk", "us", "gb", "uk",
+    "ie", "fr", "be", "lu", "at", "ch", "it", "es", "pt", "dk", "se", "no", "fi", "pl", "au", "nz",
+    "mx",
+];
+
+/// Abbreviations banks use for well-known merchants.
+const ABBREVIATIONS: [(&str, &str); 4] = [
+    ("amzn", "amazon"),
+    ("wal-mart", "walmart"),
+    ("wm", "walmart"),
+    ("mcdonalds", "mcdonald's"),
+];
+
+/// The normalized form of a description or alias, `None` when nothing is
+/// left of it.
+pub fn normalize(description: &str) -> Option<String> {
+    let mut text = description.trim().to_lowercase();
+    // statement imports describe entries as `Counterparty: remittance`
+    if let Some((counterparty, _)) = text.split_once(": ") {
+        text = counterparty.to_owned();
+    }
+    for prefix in PROCESSOR_PREFIXES {
+        if let Some(rest) = text.strip_prefix(prefix) {
+            text = rest.to_owned();
+            break;
+        }
+    }
+    if let Some((name, _)) = text.split_once('*') {
+        text = name.to_owned();
+    }
+    for ending in DOMAIN_ENDINGS {
+        text = text.replace(ending, " ");
+    }
+
+    let mut words: Vec<String> = text
+        .split(|ch: char| !ch.is_alphanumeric() && ch != '\'' && ch != '-' && ch != '&')
+        .map(|word| word.trim_matches(|ch: char| !ch.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+        .filter(|word| !word.chars().any(|ch| ch.is_ascii_digit()))
+        .filter(|word| !FILLER_WORDS.contains(word))
+        .map(|word| {
+            ABBREVIATIONS
+                .iter()
+                .find(|(abbreviation, _)| *abbreviation == word)
+                .map_or(word, |(_, name)| name)
+                .to_owned()
+        })
+        .collect();
+    // a state or country code such as `US` or `CA` after the name
+    if words.len() > 1
+        && words
+            .last()
+            .is_some_and(|word| REGION_CODES.contains(&word.as_str()))
+    {
+        words.pop();
+    }
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Whether the normalized description `key` belongs to `alias`.
+pub fn matches(key: &str, alias: &str) -> bool {
+    key.strip_prefix(alias)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+/// The alias among `aliases` that `key` belongs to, preferring the longest.
+pub fn best_match<'a, T>(key: &str, aliases: &'a [T], alias: impl Fn(&T) -> &str) -> Option<&'a T> {
+    aliases
+        .iter()
+        .filter(|candidate| matches(key, alias(candidate)))
+        .max_by_key(|candidate| alias(candidate).len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_merchant_names() {
+        assert_eq!(normalize("AMZN MKTP US*2K3").as_deref(), Some("amazon"));
+        assert_eq!(normalize("Amazon.com").as_deref(), Some("amazon"));
+        assert_eq!(
+            normalize("SQ *BLUE BOTTLE 0042").as_deref(),
+            Some("blue bottle")
+        );
+        assert_eq!(
+            normalize("POS Purchase WAL-MART #1234").as_deref(),
+            Some("walmart")
+        );
+        assert_eq!(
+            normalize("Stadtwerke Muenchen: Invoice 4711").as_deref(),
+            Some("stadtwerke muenchen")
+        );
+    }
+
+    #[test]
+    fn drops_only_known_region_codes() {
+        assert_eq!(normalize("SHELL OIL TX").as_deref(), Some("shell oil"));
+        assert_eq!(normalize("Tim Hortons ON").as_deref(), Some("tim hortons"));
+        assert_eq!(normalize("Pizza Go").as_deref(), Some("pizza go"));
+        assert_eq!(normalize("Studio XY").as_deref(), Some("studio xy"));
+        // nothing but the code is left otherwise
+        assert_eq!(normalize("US").as_deref(), Some("us"));
+    }
+
+    #[test]
+    fn nothing_left_is_none() {
+        assert_eq!(normalize(""), None);
+        assert_eq!(normalize("  "), None);
+        assert_eq!(normalize("123456 REF99"), None);
+        assert_eq!(normalize("POS DEBIT"), None);
+    }
+
+    #[test]
+    fn matches_whole_words() {
+        assert!(matches("amazon", "amazon"));
+        assert!(matches("amazon prime", "amazon"));
+        assert!(!matches("amazonia", "amazon"));
+        assert!(!matches("amazon", "amazon prime"));
+    }
+
+    #[test]
+    fn prefers_the_longest_alias() {
+        let aliases = ["amazon", "amazon prime", "amazon prime video club"];
+
+        let found = best_match("amazon prime video", &aliases, |alias| alias);
+        assert_eq!(found, Some(&"amazon prime"));
+        let found = best_match("amazon fresh", &aliases, |alias| alias);
+        assert_eq!(found, Some(&"amazon"));
+        assert_eq!(best_match("walmart", &aliases, |alias| alias), None);
+    }
+}
@@ -0,0 +1,467 @@
+//! The filter language of `GET /transactions?q=` and `transactions find`,
+//! e.g. `category:Rent amount>500 date:2026-01..2026-03 -desc:refund`.
+//!
+//! A query is a list of terms separated by spaces, all of which must hold.
+//! A term is `field:value`, or `field>value` (also `>=`, `<`, `<=`) for
+//! amounts and dates, and is negated by a leading `-`. A value with spaces
+//! is written in double quotes. A bare word looks for that text in the
+//! description.
+//!
+//! Amounts and dates take ranges such as `100..500`, `..500` or `2026-01..`.
+//! A date is a day (`2026-01-15`), a month (`2026-01`) or a year (`2026`),
+//! and stands for all of its days.
+
+use bigdecimal::BigDecimal;
+use chrono::{Months, NaiveDate};
+use std::ops::Bound;
+use std::str::FromStr;
+use thiserror::Error;
+use validator::ValidationError;
+
+/// A parsed query: transactions match when every term does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionQuery {
+    pub terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub filter: Filter,
+    /// Column of the term in the query, counting from 1.
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// The category or one of its subcategories, by name or path.
+    Category(String),
+    /// Text contained in the description, ignoring case.
+    Description(String),
+    /// The payee, by name.
+    Payee(String),
+    Tag(String),
+    Amount(Bound<BigDecimal>, Bound<BigDecimal>),
+    Date(Bound<NaiveDate>, Bound<NaiveDate>),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("column {position}: {message}")]
+pub struct QueryError {
+    /// Column of the error in the query, counting from 1.
+    pub position: usize,
+    pub message: String,
+}
+
+impl From<QueryError> for ValidationError {
+    fn from(err: QueryError) -> Self {
+        let mut error = ValidationError::new("invalid q");
+        error.message = Some(err.message.into());
+        error.add_param("position".into(), &err.position);
+        error
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Is,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Operator {
+    fn as_str(self) -> &'static str {
+        match self {
+            Operator::Is => ":",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+        }
+    }
+}
+
+const FIELDS: &str = "category, description, payee, tag, amount, date";
+
+fn error(position: usize, message: impl Into<String>) -> QueryError {
+    QueryError {
+        position: position + 1,
+        message: message.into(),
+    }
+}
+
+impl FromStr for TransactionQuery {
+    type Err = QueryError;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = query.chars().collect();
+        let mut terms = Vec::new();
+        let mut at = 0;
+        loop {
+            while at < chars.len() && chars[at].is_whitespace() {
+                at += 1;
+            }
+            if at == chars.len() {
+                break;
+            }
+            let (term, next) = parse_term(&chars, at)?;
+            terms.push(term);
+            at = next;
+        }
+        if terms.is_empty() {
+            return Err(error(0, "the query is empty"));
+        }
+        Ok(TransactionQuery { terms })
+    }
+}
+
+/// Parses the term starting at `start`, returning it with the index after it.
+fn parse_term(chars: &[char], start: usize) -> Result<(Term, usize), QueryError> {
+    let negated = chars[start] == '-';
+    let mut at = if negated { start + 1 } else { start };
+
+    let field_start = at;
+    while at < chars.len() && (chars[at].is_alphabetic() || chars[at] == '_') {
+        at += 1;
+    }
+    let field: String = chars[field_start..at].iter().collect();
+    let operator = match (chars.get(at), chars.get(at + 1)) {
+        (Some(':'), _) | (Some('='), _) => Some((Operator::Is, 1)),
+        (Some('>'), Some('=')) => Some((Operator::GreaterOrEqual, 2)),
+        (Some('>'), _) => Some((Operator::Greater, 1)),
+        (Some('<'), Some('=')) => Some((Operator::LessOrEqual, 2)),
+        (Some('<'), _) => Some((Operator::Less, 1)),
+        _ => None,
+    };
+
+    let Some((operator, length)) = operator.filter(|_| !field.is_empty()) else {
+        // a bare word
+        let (word, next) = parse_value(chars, field_start)?;
+        if word.is_empty() {
+            return Err(error(field_start, "expected a word after `-`"));
+        }
+        let term = Term {
+            negated,
+            filter: Filter::Description(word),
+            position: start + 1,
+        };
+        return Ok((term, next));
+    };
+    let operator_start = at;
+    let value_start = at + length;
+    let (value, next) = parse_value(chars, value_start)?;
+    if value.is_empty() {
+        return Err(error(
+            value_start,
+            format!("expected a value after `{}{}`", field, operator.as_str()),
+        ));
+    }
+
+    let text_only = |filter: Filter| match operator {
+        Operator::Is => Ok(filter),
+        _ => Err(error(
+            operator_start,
+            format!("`{}` can only be compared with `:`", field),
+        )),
+    };
+    let filter = match field.to_lowercase().as_str() {
+        "category" | "cat" => text_only(Filter::Category(value))?,
+        "description" | "desc" => text_only(Filter::Description(value))?,
+        "payee" => text_only(Filter::Payee(value))?,
+        "tag" => text_only(Filter::Tag(value))?,
+        "amount" => {
+            let (lower, upper) = parse_bounds(&value, value_start, operator, parse_amount)?;
+            Filter::Amount(lower, upper)
+        }
+        "date" => {
+            let (lower, upper) = parse_bounds(&value, value_start, operator, parse_date)?;
+            Filter::Date(lower, upper)
+        }
+        _ => {
+            return Err(error(
+                field_start,
+                format!("unknown field `{}`, expected one of {}", field, FIELDS),
+            ))
+        }
+    };
+    let term = Term {
+        negated,
+        filter,
+        position: start + 1,
+    };
+    Ok((term, next))
+}
+
+/// Reads a value up to the next space, or a double quoted one.
+fn parse_value(chars: &[char], start: usize) -> Result<(String, usize), QueryError> {
+    if chars.get(start) == Some(&'"') {
+        let Some(length) = chars[start + 1..].iter().position(|ch| *ch == '"') else {
+            return Err(error(start, "unterminated quote"));
+        };
+        let end = start + 1 + length;
+        return Ok((chars[start + 1..end].iter().collect(), end + 1));
+    }
+    let mut end = start;
+    while end < chars.len() && !chars[end].is_whitespace() {
+        end += 1;
+    }
+    Ok((chars[start..end].iter().collect(), end))
+}
+
+/// Parses a single amount or date at a column into the first and last value
+/// it stands for.
+type ParseValue<T> = fn(&str, usize) -> Result<(T, T), QueryError>;
+
+/// Parses an amount or date value into its bounds.
+fn parse_bounds<T: Clone + PartialOrd>(
+    value: &str,
+    start: usize,
+    operator: Operator,
+    parse: ParseValue<T>,
+) -> Result<(Bound<T>, Bound<T>), QueryError> {
+    let Some((from, to)) = value.split_once("..") else {
+        let (first, last) = parse(value, start)?;
+        return Ok(match operator {
+            Operator::Is => (Bound::Included(first), Bound::Included(last)),
+            Operator::Greater => (Bound::Excluded(last), Bound::Unbounded),
+            Operator::GreaterOrEqual => (Bound::Included(first), Bound::Unbounded),
+            Operator::Less => (Bound::Unbounded, Bound::Excluded(first)),
+            Operator::LessOrEqual => (Bound::Unbounded, Bound::Included(last)),
+        });
+    };
+    if operator != Operator::Is {
+        return Err(error(start, "a range can only be used with `:`"));
+    }
+    let to_start = start + from.chars().count() + 2;
+    let lower = match from {
+        "" => None,
+        from => Some(parse(from, start)?.0),
+    };
+    let upper = match to {
+        "" => None,
+        to => Some(parse(to, to_start)?.1),
+    };
+    match (lower, upper) {
+        (None, None) => Err(error(start, "a range needs a start or an end")),
+        (Some(lower), Some(upper)) if lower > upper => {
+            Err(error(start, "the range ends before it starts"))
+        }
+        (lower, upper) => Ok((
+            lower.map_or(Bound::Unbounded, Bound::Included),
+            upper.map_or(Bound::Unbounded, Bound::Included),
+        )),
+    }
+}
+
+fn parse_amount(value: &str, start: usize) -> Result<(BigDecimal, BigDecimal), QueryError> {
+    match value.parse::<BigDecimal>() {
+        Ok(amount) => Ok((amount.clone(), amount)),
+        Err(_) => Err(error(start, format!("`{}` is not an amount", value))),
+    }
+}
+
+/// The first and last day of `2026-01-15`, `2026-01` or `2026`.
+fn parse_date(value: &str, start: usize) -> Result<(NaiveDate, NaiveDate), QueryError> {
+    let invalid = || {
+        error(
+            start,
+            format!(
+                "`{}` is not a date, expected YYYY-MM-DD, YYYY-MM or YYYY",
+                value
+            ),
+        )
+    };
+    let parts: Vec<&str> = value.split('-').collect();
+    let number = |part: &str| part.parse::<u32>().map_err(|_| invalid());
+    match parts.as_slice() {
+        [_, _, _] => {
+            let day = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| invalid())?;
+            Ok((day, day))
+        }
+        [year, month] => {
+            let first = NaiveDate::from_ymd_opt(number(year)? as i32, number(month)?, 1)
+                .ok_or_else(invalid)?;
+            let last = first
+                .checked_add_months(Months::new(1))
+                .and_then(|next| next.pred_opt())
+                .ok_or_else(invalid)?;
+            Ok((first, last))
+        }
+        [year] => {
+            let year = number(year)? as i32;
+            let first = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(invalid)?;
+            let last = NaiveDate::from_ymd_opt(year, 12, 31).ok_or_else(invalid)?;
+            Ok((first, last))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Vec<Term> {
+        query.parse::<TransactionQuery>().unwrap().terms
+    }
+
+    fn filters(query: &str) -> Vec<Filter> {
+        parse(query).into_iter().map(|term| term.filter).collect()
+    }
+
+    fn fail(query: &str) -> (usize, String) {
+        let error = query.parse::<TransactionQuery>().unwrap_err();
+        (error.position, error.message)
+    }
+
+    fn amount(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn reads_terms() {
+        let terms = parse(r#"category:Rent  -desc:refund payee:"Whole Foods" coffee tag=trip"#);
+
+        assert_eq!(
+            terms,
+            [
+                Term {
+                    negated: false,
+                    filter: Filter::Category("Rent".to_owned()),
+                    position: 1,
+                },
+                Term {
+                    negated: true,
+                    filter: Filter::Description("refund".to_owned()),
+                    position: 16,
+                },
+                Term {
+                    negated: false,
+                    filter: Filter::Payee("Whole Foods".to_owned()),
+                    position: 29,
+                },
+                Term {
+                    negated: false,
+                    filter: Filter::Description("coffee".to_owned()),
+                    position: 49,
+                },
+                Term {
+                    negated: false,
+                    filter: Filter::Tag("trip".to_owned()),
+                    position: 56,
+                },
+            ]
+        );
+        assert_eq!(filters("CAT:Rent"), [Filter::Category("Rent".to_owned())]);
+        assert_eq!(
+            filters("-\"gift card\""),
+            [Filter::Description("gift card".to_owned())]
+        );
+    }
+
+    #[test]
+    fn reads_amount_comparisons() {
+        assert_eq!(
+            filters("amount>500 amount>=500 amount<500 amount<=500 amount:500"),
+            [
+                Filter::Amount(Bound::Excluded(amount("500")), Bound::Unbounded),
+                Filter::Amount(Bound::Included(amount("500")), Bound::Unbounded),
+                Filter::Amount(Bound::Unbounded, Bound::Excluded(amount("500"))),
+                Filter::Amount(Bound::Unbounded, Bound::Included(amount("500"))),
+                Filter::Amount(
+                    Bound::Included(amount("500")),
+                    Bound::Included(amount("500"))
+                ),
+            ]
+        );
+        assert_eq!(
+            filters("amount:100..500.5 amount:..20"),
+            [
+                Filter::Amount(
+                    Bound::Included(amount("100")),
+                    Bound::Included(amount("500.5"))
+                ),
+                Filter::Amount(Bound::Unbounded, Bound::Included(amount("20"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_dates_as_their_days() {
+        assert_eq!(
+            filters("date:2026-01..2026-03 date>2026 date<2026-02 date:2024-02-29"),
+            [
+                Filter::Date(
+                    Bound::Included(date(2026, 1, 1)),
+                    Bound::Included(date(2026, 3, 31))
+                ),
+                Filter::Date(Bound::Excluded(date(2026, 12, 31)), Bound::Unbounded),
+                Filter::Date(Bound::Unbounded, Bound::Excluded(date(2026, 2, 1))),
+                Filter::Date(
+                    Bound::Included(date(2024, 2, 29)),
+                    Bound::Included(date(2024, 2, 29))
+                ),
+            ]
+        );
+        assert_eq!(
+            filters("date:2024-02.."),
+            [Filter::Date(
+                Bound::Included(date(2024, 2, 1)),
+                Bound::Unbounded
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(fail("   "), (1, "the query is empty".to_owned()));
+        assert_eq!(
+            fail("rent colour:red"),
+            (
+                6,
+                format!("unknown field `colour`, expected one of {}", FIELDS)
+            )
+        );
+        assert_eq!(fail("amount>abc"), (8, "`abc` is not an amount".to_owned()));
+        assert_eq!(
+            fail("category>Rent"),
+            (9, "`category` can only be compared with `:`".to_owned())
+        );
+        assert_eq!(
+            fail("rent payee:"),
+            (12, "expected a value after `payee:`".to_owned())
+        );
+        assert_eq!(fail(r#"desc:"open"#), (6, "unterminated quote".to_owned()));
+        assert_eq!(fail("rent -"), (7, "expected a word after `-`".to_owned()));
+        assert_eq!(
+            fail("amount>1..5"),
+            (8, "a range can only be used with `:`".to_owned())
+        );
+        assert_eq!(
+            fail("amount:.."),
+            (8, "a range needs a start or an end".to_owned())
+        );
+        assert_eq!(
+            fail("date:2026..2025"),
+            (6, "the range ends before it starts".to_owned())
+        );
+        assert_eq!(
+            fail("date:2026-01..2026-13"),
+            (
+                15,
+                "`2026-13` is not a date, expected YYYY-MM-DD, YYYY-MM or YYYY".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        assert_eq!(fail("café amount>x").0, 13);
+    }
+}
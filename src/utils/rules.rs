@@ -0,0 +1,299 @@
+//! Runs a user's categorization rules against transactions. A rule matches
+//! when all of its conditions hold: the description contains
+//! `description_contains` (ignoring case), matches `description_regex`, the
+//! amount lies between `min_amount` and `max_amount` and the date falls on
+//! `day_of_month`.
+//!
+//! Rules run in order of their position. With the `first` match mode only
+//! the first matching rule applies; with `all` every matching rule applies,
+//! later rules overriding what earlier ones set. Conditions always look at
+//! the original description.
+
+use crate::model::{CategoryRule, NewTransaction, MATCH_ALL};
+use bigdecimal::BigDecimal;
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+
+/// What the matching rules assign to a transaction.
+#[derive(Default)]
+pub struct RuleOutcome {
+    pub rule_ids: Vec<i32>,
+    /// Category name and id.
+    pub category: Option<(String, i32)>,
+    pub description: Option<String>,
+    /// The rules that set `category` and `description`.
+    pub category_rule: Option<i32>,
+    pub description_rule: Option<i32>,
+}
+
+pub struct RuleSet {
+    rules: Vec<(CategoryRule, Option<Regex>)>,
+    match_all: bool,
+}
+
+impl RuleSet {
+    /// `rules` must be ordered by position. A rule whose regex does not
+    /// compile never matches.
+    pub fn new(rules: Vec<CategoryRule>, match_mode: &str) -> RuleSet {
+        let rules = rules
+            .into_iter()
+            .filter_map(|rule| match &rule.description_regex {
+                Some(pattern) => {
+                    let regex = Regex::new(pattern).ok()?;
+                    Some((rule, Some(regex)))
+                }
+                None => Some((rule, None)),
+            })
+            .collect();
+        RuleSet {
+            rules,
+            match_all: match_mode == MATCH_ALL,
+        }
+    }
+
+    pub fn evaluate(
+        &self,
+        amount: &BigDecimal,
+        date: NaiveDate,
+        description: Option<&str>,
+    ) -> RuleOutcome {
+        let mut outcome = RuleOutcome::default();
+        for (rule, regex) in &self.rules {
+            let Some(new_description) = matches(rule, regex.as_ref(), amount, date, description)
+            else {
+                continue;
+            };
+            outcome.rule_ids.push(rule.id);
+            if let (Some(category), Some(category_id)) = (&rule.category, rule.category_id) {
+                outcome.category = Some((category.clone(), category_id));
+                outcome.category_rule = Some(rule.id);
+            }
+            if new_description.is_some() {
+                outcome.description = new_description;
+                outcome.description_rule = Some(rule.id);
+            }
+            if !self.match_all {
+                break;
+            }
+        }
+        outcome
+    }
+
+    /// Applies the matching rules to a new transaction, leaving its category
+    /// alone unless `set_category`, and returns the ids of the rules whose
+    /// changes were kept.
+    pub fn apply(&self, transaction: &mut NewTransaction, set_category: bool) -> Vec<i32> {
+        let outcome = self.evaluate(
+            &transaction.amount,
+            transaction.date,
+            transaction.description.as_deref(),
+        );
+        let mut kept = Vec::new();
+        if let Some((category, category_id)) = outcome.category.filter(|_| set_category) {
+            transaction.category = category;
+            transaction.category_id = Some(category_id);
+            kept.extend(outcome.category_rule);
+        }
+        if let Some(description) = outcome.description {
+            transaction.description = Some(description);
+            kept.extend(outcome.description_rule);
+        }
+        // in the order the rules ran
+        outcome
+            .rule_ids
+            .into_iter()
+            .filter(|id| kept.contains(id))
+            .collect()
+    }
+}
+
+/// `None` when the rule does not match, otherwise the description it sets.
+fn matches(
+    rule: &CategoryRule,
+    regex: Option<&Regex>,
+    amount: &BigDecimal,
+    date: NaiveDate,
+    description: Option<&str>,
+) -> Option<Option<String>> {
+    if rule.min_amount.as_ref().is_some_and(|min| amount < min)
+        || rule.max_amount.as_ref().is_some_and(|max| amount > max)
+        || rule
+            .day_of_month
+            .is_some_and(|day| date.day() != day as u32)
+    {
+        return None;
+    }
+    if let Some(text) = &rule.description_contains {
+        if !description?.to_lowercase().contains(&text.to_lowercase()) {
+            return None;
+        }
+    }
+
+    let captures = match regex {
+        Some(regex) => Some(regex.captures(description?)?),
+        None => None,
+    };
+    Some(
+        rule.new_description
+            .as_ref()
+            .map(|template| match captures {
+                Some(captures) => {
+                    let mut expanded = String::new();
+                    captures.expand(template, &mut expanded);
+                    expanded.trim().to_owned()
+                }
+                None => template.clone(),
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::MATCH_FIRST;
+    use chrono::NaiveDateTime;
+
+    fn rule(id: i32) -> CategoryRule {
+        CategoryRule {
+            id,
+            user_id: 1,
+            position: id,
+            description_contains: None,
+            description_regex: None,
+            min_amount: None,
+            max_amount: None,
+            day_of_month: None,
+            category: None,
+            category_id: None,
+            new_description: None,
+            created_at: NaiveDateTime::default(),
+        }
+    }
+
+    fn categorize(mut rule: CategoryRule, category: &str, category_id: i32) -> CategoryRule {
+        rule.category = Some(category.to_owned());
+        rule.category_id = Some(category_id);
+        rule
+    }
+
+    fn transaction(description: &str) -> NewTransaction {
+        NewTransaction {
+            user_id: Some(1),
+            amount: BigDecimal::from(20),
+            category: "Other".to_owned(),
+            description: Some(description.to_owned()),
+            date: NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+            category_id: None,
+            external_id: None,
+            payee_id: None,
+            account_id: None,
+        }
+    }
+
+    fn rules() -> Vec<CategoryRule> {
+        let mut coffee = categorize(rule(1), "Coffee", 10);
+        coffee.description_contains = Some("STARBUCKS".to_owned());
+        let mut cleanup = rule(2);
+        cleanup.description_regex = Some(r"^(?P<name>\w+) #\d+".to_owned());
+        cleanup.new_description = Some("$name store".to_owned());
+        let mut dining = categorize(rule(3), "Dining", 11);
+        dining.min_amount = Some(BigDecimal::from(10));
+        vec![coffee, cleanup, dining]
+    }
+
+    #[test]
+    fn first_mode_stops_at_the_first_match() {
+        let rules = RuleSet::new(rules(), MATCH_FIRST);
+        let outcome = rules.evaluate(
+            &BigDecimal::from(20),
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+            Some("Starbucks #123"),
+        );
+
+        assert_eq!(outcome.rule_ids, [1]);
+        assert_eq!(outcome.category, Some(("Coffee".to_owned(), 10)));
+        assert_eq!(outcome.description, None);
+    }
+
+    #[test]
+    fn all_mode_lets_later_rules_override() {
+        let rules = RuleSet::new(rules(), MATCH_ALL);
+        let outcome = rules.evaluate(
+            &BigDecimal::from(20),
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+            Some("Starbucks #123"),
+        );
+
+        assert_eq!(outcome.rule_ids, [1, 2, 3]);
+        assert_eq!(outcome.category, Some(("Dining".to_owned(), 11)));
+        assert_eq!(outcome.category_rule, Some(3));
+        assert_eq!(outcome.description.as_deref(), Some("Starbucks store"));
+        assert_eq!(outcome.description_rule, Some(2));
+    }
+
+    #[test]
+    fn checks_amount_and_day() {
+        let mut rent = categorize(rule(1), "Rent", 12);
+        rent.max_amount = Some(BigDecimal::from(1000));
+        rent.day_of_month = Some(1);
+        let rules = RuleSet::new(vec![rent], MATCH_FIRST);
+        let first = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        assert_eq!(
+            rules.evaluate(&BigDecimal::from(900), first, None).rule_ids,
+            [1]
+        );
+        assert!(rules
+            .evaluate(&BigDecimal::from(1001), first, None)
+            .rule_ids
+            .is_empty());
+        let second = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+        assert!(rules
+            .evaluate(&BigDecimal::from(900), second, None)
+            .rule_ids
+            .is_empty());
+    }
+
+    #[test]
+    fn expands_captures_into_the_new_description() {
+        let mut rule = rule(1);
+        rule.description_regex = Some(r"(?i)^paypal \*(\w+)".to_owned());
+        rule.new_description = Some("${1} via PayPal ".to_owned());
+        let rules = RuleSet::new(vec![rule], MATCH_FIRST);
+        let mut transaction = transaction("PAYPAL *Steam 4029357733");
+
+        assert_eq!(rules.apply(&mut transaction, true), [1]);
+        assert_eq!(transaction.description.as_deref(), Some("Steam via PayPal"));
+        assert_eq!(transaction.category, "Other");
+    }
+
+    #[test]
+    fn drops_rules_with_invalid_regexes() {
+        let mut broken = categorize(rule(1), "Broken", 13);
+        broken.description_regex = Some("(unclosed".to_owned());
+        let mut coffee = categorize(rule(2), "Coffee", 10);
+        coffee.description_contains = Some("coffee".to_owned());
+        let rules = RuleSet::new(vec![broken, coffee], MATCH_FIRST);
+        let mut transaction = transaction("(unclosed coffee");
+
+        assert_eq!(rules.apply(&mut transaction, true), [2]);
+        assert_eq!(transaction.category, "Coffee");
+    }
+
+    #[test]
+    fn reports_only_the_rules_whose_changes_were_kept() {
+        let rules = RuleSet::new(rules(), MATCH_ALL);
+
+        let mut categorized = transaction("Starbucks #123");
+        assert_eq!(rules.apply(&mut categorized, true), [2, 3]);
+        assert_eq!(categorized.category, "Dining");
+        assert_eq!(categorized.category_id, Some(11));
+
+        // a category given by the user is kept, so only the cleanup counts
+        let mut given = transaction("Starbucks #123");
+        assert_eq!(rules.apply(&mut given, false), [2]);
+        assert_eq!(given.category, "Other");
+        assert_eq!(given.category_id, None);
+        assert_eq!(given.description.as_deref(), Some("Starbucks store"));
+    }
+}
@@ -0,0 +1,377 @@
+//! A subset of iCalendar RRULEs (RFC 5545) used by recurring templates, e.g.
+//!
+//! - `FREQ=MONTHLY;BYMONTHDAY=1`: monthly on day 1
+//! - `FREQ=WEEKLY;INTERVAL=2`: every two weeks
+//! - `FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1`: last business day
+//!
+//! Supported parts are `FREQ` (`DAILY`, `WEEKLY`, `MONTHLY`, `YEARLY`),
+//! `INTERVAL`, `BYMONTHDAY` (negative counts from the end of the month),
+//! `BYDAY` and `BYSETPOS`. Month days past the end of a month fall on its
+//! last day.
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+
+/// Stops runaway expansion of rules that never produce a date.
+const MAX_PERIODS: u32 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub month_day: Option<i32>,
+    pub weekdays: Vec<Weekday>,
+    pub set_position: Option<i32>,
+}
+
+impl Schedule {
+    pub fn parse(rule: &str) -> Result<Schedule, &'static str> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut month_day = None;
+        let mut weekdays = Vec::new();
+        let mut set_position = None;
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or("schedule parts must look like KEY=VALUE")?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err("FREQ must be DAILY, WEEKLY, MONTHLY or YEARLY"),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| (1..=1000).contains(interval))
+                        .ok_or("INTERVAL must be a number between 1 and 1000")?
+                }
+                "BYMONTHDAY" => {
+                    month_day = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|day: &i32| day != &0 && (-31..=31).contains(day))
+                            .ok_or("BYMONTHDAY must be between 1 and 31 or -31 and -1")?,
+                    )
+                }
+                "BYDAY" => {
+                    weekdays = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or("BYDAY must list days such as MO,TU,WE")?
+                }
+                "BYSETPOS" => {
+                    set_position = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|position: &i32| {
+                                position != &0 && (-31..=31).contains(position)
+                            })
+                            .ok_or("BYSETPOS must be between 1 and 31 or -31 and -1")?,
+                    )
+                }
+                _ => return Err("unsupported schedule part"),
+            }
+        }
+
+        let frequency = frequency.ok_or("schedule needs a FREQ")?;
+        if month_day.is_some() && frequency != Frequency::Monthly {
+            return Err("BYMONTHDAY is only supported with FREQ=MONTHLY");
+        }
+        if !weekdays.is_empty() && !matches!(frequency, Frequency::Weekly | Frequency::Monthly) {
+            return Err("BYDAY is only supported with FREQ=WEEKLY or FREQ=MONTHLY");
+        }
+        if month_day.is_some() && !weekdays.is_empty() {
+            return Err("BYMONTHDAY and BYDAY cannot be combined");
+        }
+        if set_position.is_some() && (frequency != Frequency::Monthly || weekdays.is_empty()) {
+            return Err("BYSETPOS needs FREQ=MONTHLY and BYDAY");
+        }
+        Ok(Schedule {
+            frequency,
+            interval,
+            month_day,
+            weekdays,
+            set_position,
+        })
+    }
+
+    /// The dates of a schedule starting on `start` that fall between `from`
+    /// and `to`, both inclusive, in order.
+    pub fn occurrences(&self, start: NaiveDate, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+        for period in 0..MAX_PERIODS {
+            let Some((period_start, candidates)) = self.period(start, period * self.interval)
+            else {
+                break;
+            };
+            if period_start > to {
+                break;
+            }
+            dates.extend(
+                candidates
+                    .into_iter()
+                    .filter(|date| date >= &start && date >= &from && date <= &to),
+            );
+        }
+        dates
+    }
+
+    /// The first day of the period `offset` frequency units after the one
+    /// containing `start`, with the dates the schedule produces in it.
+    fn period(&self, start: NaiveDate, offset: u32) -> Option<(NaiveDate, Vec<NaiveDate>)> {
+        match self.frequency {
+            Frequency::Daily => {
+                let date = start.checked_add_days(Days::new(offset.into()))?;
+                Some((date, vec![date]))
+            }
+            Frequency::Weekly => {
+                let date = start.checked_add_days(Days::new(u64::from(offset) * 7))?;
+                if self.weekdays.is_empty() {
+                    return Some((date, vec![date]));
+                }
+                let monday =
+                    date.checked_sub_days(Days::new(date.weekday().num_days_from_monday().into()))?;
+                let dates = monday
+                    .iter_days()
+                    .take(7)
+                    .filter(|day| self.weekdays.contains(&day.weekday()))
+                    .collect();
+                Some((monday, dates))
+            }
+            Frequency::Monthly => {
+                let first = start.with_day(1)?.checked_add_months(Months::new(offset))?;
+                let last = first.checked_add_months(Months::new(1))?.pred_opt()?;
+                let dates = if let Some(day) = self.month_day {
+                    let day = if day > 0 {
+                        (day as u32).min(last.day())
+                    } else {
+                        (last.day() as i32 + day + 1).max(1) as u32
+                    };
+                    vec![first.with_day(day)?]
+                } else if !self.weekdays.is_empty() {
+                    let days: Vec<NaiveDate> = first
+                        .iter_days()
+                        .take_while(|day| day <= &last)
+                        .filter(|day| self.weekdays.contains(&day.weekday()))
+                        .collect();
+                    match self.set_position {
+                        Some(position) if position > 0 => days
+                            .get(position as usize - 1)
+                            .copied()
+                            .into_iter()
+                            .collect(),
+                        Some(position) => days
+                            .len()
+                            .checked_sub(position.unsigned_abs() as usize)
+                            .and_then(|index| days.get(index).copied())
+                            .into_iter()
+                            .collect(),
+                        None => days,
+                    }
+                } else {
+                    vec![first.with_day(start.day().min(last.day()))?]
+                };
+                Some((first, dates))
+            }
+            Frequency::Yearly => {
+                let first = start
+                    .with_day(1)?
+                    .checked_add_months(Months::new(offset * 12))?;
+                let last = first.checked_add_months(Months::new(1))?.pred_opt()?;
+                Some((first, vec![first.with_day(start.day().min(last.day()))?]))
+            }
+        }
+    }
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn occurrences(rule: &str, start: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        Schedule::parse(rule).unwrap().occurrences(start, start, to)
+    }
+
+    #[test]
+    fn repeats_daily_and_weekly() {
+        assert_eq!(
+            occurrences("FREQ=DAILY;INTERVAL=3", date(2024, 2, 27), date(2024, 3, 5)),
+            [date(2024, 2, 27), date(2024, 3, 1), date(2024, 3, 4)]
+        );
+        assert_eq!(
+            occurrences(
+                "RRULE:FREQ=WEEKLY;INTERVAL=2",
+                date(2024, 1, 3),
+                date(2024, 2, 1)
+            ),
+            [date(2024, 1, 3), date(2024, 1, 17), date(2024, 1, 31)]
+        );
+        // the Monday of the first week is before the start
+        assert_eq!(
+            occurrences(
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR",
+                date(2024, 1, 3),
+                date(2024, 1, 21)
+            ),
+            [date(2024, 1, 5), date(2024, 1, 15), date(2024, 1, 19)]
+        );
+    }
+
+    #[test]
+    fn keeps_the_day_of_month() {
+        assert_eq!(
+            occurrences("FREQ=MONTHLY", date(2024, 1, 31), date(2024, 4, 30)),
+            [
+                date(2024, 1, 31),
+                date(2024, 2, 29),
+                date(2024, 3, 31),
+                date(2024, 4, 30)
+            ]
+        );
+        assert_eq!(
+            occurrences(
+                "FREQ=MONTHLY;BYMONTHDAY=31",
+                date(2023, 1, 15),
+                date(2023, 3, 1)
+            ),
+            [date(2023, 1, 31), date(2023, 2, 28)]
+        );
+        assert_eq!(
+            occurrences(
+                "FREQ=MONTHLY;BYMONTHDAY=-2",
+                date(2024, 1, 1),
+                date(2024, 3, 1)
+            ),
+            [date(2024, 1, 30), date(2024, 2, 28)]
+        );
+        assert_eq!(
+            occurrences("FREQ=YEARLY", date(2024, 2, 29), date(2026, 12, 31)),
+            [date(2024, 2, 29), date(2025, 2, 28), date(2026, 2, 28)]
+        );
+    }
+
+    #[test]
+    fn picks_days_by_set_position() {
+        assert_eq!(
+            occurrences(
+                "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+                date(2024, 1, 1),
+                date(2024, 6, 30)
+            ),
+            [
+                date(2024, 1, 31),
+                date(2024, 2, 29),
+                date(2024, 3, 29),
+                date(2024, 4, 30),
+                date(2024, 5, 31),
+                date(2024, 6, 28),
+            ]
+        );
+        assert_eq!(
+            occurrences(
+                "FREQ=MONTHLY;BYDAY=TU;BYSETPOS=2",
+                date(2024, 1, 1),
+                date(2024, 3, 31)
+            ),
+            [date(2024, 1, 9), date(2024, 2, 13), date(2024, 3, 12)]
+        );
+        // February 2023 has no fifth Monday
+        assert_eq!(
+            occurrences(
+                "FREQ=MONTHLY;BYDAY=MO;BYSETPOS=5",
+                date(2023, 1, 1),
+                date(2023, 3, 31)
+            ),
+            [date(2023, 1, 30)]
+        );
+    }
+
+    #[test]
+    fn limits_to_the_range() {
+        let schedule = Schedule::parse("FREQ=MONTHLY;BYMONTHDAY=1").unwrap();
+
+        assert_eq!(
+            schedule.occurrences(date(2024, 1, 1), date(2024, 3, 1), date(2024, 4, 30)),
+            [date(2024, 3, 1), date(2024, 4, 1)]
+        );
+        assert!(schedule
+            .occurrences(date(2024, 5, 2), date(2024, 1, 1), date(2024, 5, 31))
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_unsupported_rules() {
+        for (rule, error) in [
+            ("BYMONTHDAY=1", "schedule needs a FREQ"),
+            (
+                "FREQ=HOURLY",
+                "FREQ must be DAILY, WEEKLY, MONTHLY or YEARLY",
+            ),
+            (
+                "FREQ=DAILY;INTERVAL=0",
+                "INTERVAL must be a number between 1 and 1000",
+            ),
+            (
+                "FREQ=MONTHLY;BYMONTHDAY=0",
+                "BYMONTHDAY must be between 1 and 31 or -31 and -1",
+            ),
+            (
+                "FREQ=WEEKLY;BYDAY=XX",
+                "BYDAY must list days such as MO,TU,WE",
+            ),
+            (
+                "FREQ=WEEKLY;BYMONTHDAY=1",
+                "BYMONTHDAY is only supported with FREQ=MONTHLY",
+            ),
+            (
+                "FREQ=MONTHLY;BYDAY=MO;BYMONTHDAY=1",
+                "BYMONTHDAY and BYDAY cannot be combined",
+            ),
+            (
+                "FREQ=MONTHLY;BYSETPOS=-1",
+                "BYSETPOS needs FREQ=MONTHLY and BYDAY",
+            ),
+            ("FREQ=DAILY;COUNT=3", "unsupported schedule part"),
+            ("FREQ", "schedule parts must look like KEY=VALUE"),
+        ] {
+            assert_eq!(Schedule::parse(rule), Err(error), "{}", rule);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Turns what a user typed into a search box into a PostgreSQL `tsquery`.
+//!
+//! Every word has to match, and matches as a prefix, so `whole gro` finds
+//! `Whole Foods groceries`. A word written as `-word` must not match. Only
+//! letters and digits are kept, so the text can never break the `tsquery`
+//! syntax.
+
+/// Most words taken from one search.
+const MAX_SEARCH_TERMS: usize = 20;
+
+/// The `tsquery` for `text`, `None` when it has no word to look for.
+pub fn prefix_query(text: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    for token in text.split_whitespace() {
+        let (negated, token) = match token.strip_prefix('-') {
+            Some(token) => (true, token),
+            None => (false, token),
+        };
+        for word in token
+            .split(|ch: char| !ch.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            let word = word.to_lowercase();
+            if negated {
+                terms.push(format!("!{}:*", word));
+            } else {
+                terms.push(format!("{}:*", word));
+            }
+        }
+    }
+    terms.truncate(MAX_SEARCH_TERMS);
+    // a query made of exclusions only would match nearly everything
+    let searches_something = terms.iter().any(|term| !term.starts_with('!'));
+    searches_something.then(|| terms.join(" & "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_every_word_as_a_prefix() {
+        assert_eq!(prefix_query("whole fo").as_deref(), Some("whole:* & fo:*"));
+        assert_eq!(
+            prefix_query("  Whole\tFOODS ").as_deref(),
+            Some("whole:* & foods:*")
+        );
+    }
+
+    #[test]
+    fn excludes_negated_words() {
+        assert_eq!(
+            prefix_query("coffee -decaf").as_deref(),
+            Some("coffee:* & !decaf:*")
+        );
+        assert_eq!(
+            prefix_query("-decaf coffee").as_deref(),
+            Some("!decaf:* & coffee:*")
+        );
+        assert_eq!(prefix_query("-decaf -tea"), None);
+    }
+
+    #[test]
+    fn keeps_only_letters_and_digits() {
+        assert_eq!(prefix_query("a&b|c:*").as_deref(), Some("a:* & b:* & c:*"));
+        assert_eq!(prefix_query("-o'neil").as_deref(), None);
+        assert_eq!(prefix_query("Café #12").as_deref(), Some("café:* & 12:*"));
+        assert_eq!(prefix_query("!() & |"), None);
+        assert_eq!(prefix_query(""), None);
+    }
+
+    #[test]
+    fn checks_the_words_left_after_truncating() {
+        let excluded = vec!["-x"; MAX_SEARCH_TERMS].join(" ");
+
+        assert_eq!(prefix_query(&format!("{} coffee", excluded)), None);
+        let query = prefix_query(&format!("coffee {}", excluded)).unwrap();
+        assert!(query.starts_with("coffee:* & !x:*"));
+        assert_eq!(query.split(" & ").count(), MAX_SEARCH_TERMS);
+    }
+}